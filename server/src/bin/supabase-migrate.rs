@@ -0,0 +1,498 @@
+use supabase_migrate::error::PreviewError;
+use supabase_migrate::models::merge::MergeResponse;
+use supabase_migrate::models::migrate::{ApplyReport, DryRunResponse, PreviewResponse};
+use supabase_migrate::models::policy::PolicyConfig;
+use supabase_migrate::models::provider::{ProviderApplyRequest, ProviderPreview};
+use supabase_migrate::models::transform::TransformConfig;
+use supabase_migrate::services::backup::create_backup;
+use supabase_migrate::services::diff::{DEFAULT_TEXT_DIFF_THRESHOLD, add_text_diffs};
+use supabase_migrate::services::management_api::ManagementApi;
+use supabase_migrate::services::merge::{conflict_count, plan_merge};
+use supabase_migrate::services::policy::{enforce, evaluate, evaluate_plans, load_policy_config};
+use supabase_migrate::services::providers::{fetch_auth_configs, plan_providers, provider_diffs};
+use supabase_migrate::services::reconcile::{
+    ServicePlan, describe_plans, execute_plan, plan_config,
+};
+use supabase_migrate::services::redact::{Redactor, parse_redact_patterns};
+use supabase_migrate::services::report::{ReportFormat, render_html, render_markdown};
+use supabase_migrate::services::selection::select_plans;
+use supabase_migrate::services::snapshot::{
+    build_partial_snapshot, build_snapshot, diff_snapshots, parse_snapshot,
+};
+use supabase_migrate::services::transform::load_transform_config;
+use supabase_migrate::services::{PreviewSide, Service, preview_services};
+
+use clap::{Parser, Subcommand, ValueEnum};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use uuid::Uuid;
+
+// Exit code used by `--fail-on-diff` when the compared configs differ, and by
+// `--fail-on-conflict` when a three-way comparison finds conflicts
+const DIFF_EXIT_CODE: u8 = 2;
+
+/// Preview, snapshot and apply Supabase project configuration from the terminal.
+#[derive(Parser)]
+#[command(name = "supabase-migrate", version)]
+struct Cli {
+    /// Supabase personal access token.
+    #[arg(
+        long,
+        env = "SUPABASE_ACCESS_TOKEN",
+        hide_env_values = true,
+        global = true
+    )]
+    token: Option<String>,
+
+    /// Policy rules (JSON or YAML) to check previews against and enforce on applies.
+    #[arg(long, env = "POLICY_CONFIG_PATH", global = true)]
+    policy: Option<PathBuf>,
+
+    /// Transform rules (JSON or YAML) rewriting environment specific values.
+    #[arg(long, env = "TRANSFORM_CONFIG_PATH", global = true)]
+    transforms: Option<PathBuf>,
+
+    /// Changed strings at least this long get a line-level diff in previews.
+    #[arg(long, env = "TEXT_DIFF_THRESHOLD", default_value_t = DEFAULT_TEXT_DIFF_THRESHOLD, global = true)]
+    text_diff_threshold: usize,
+
+    /// Key patterns whose values are masked in output, on top of the known credentials.
+    #[arg(
+        long,
+        env = "REDACT_KEY_PATTERNS",
+        value_delimiter = ',',
+        global = true
+    )]
+    redact: Vec<String>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Compare the configuration of two live projects.
+    Preview {
+        #[arg(long)]
+        source: String,
+        #[arg(long)]
+        dest: String,
+        /// Services to compare, e.g. `auth,postgrest`. Defaults to every service.
+        #[arg(long, value_delimiter = ',', value_parser = parse_service)]
+        services: Vec<Service>,
+        /// Output format: json, markdown or html.
+        #[arg(long, default_value = "json")]
+        format: ReportFormat,
+        /// Exit with status 2 when the projects differ.
+        #[arg(long)]
+        fail_on_diff: bool,
+    },
+    /// Export a project's configuration as a snapshot document.
+    Snapshot {
+        #[arg(long)]
+        project: String,
+        #[arg(long, value_enum, default_value_t = Format::Json)]
+        format: Format,
+        /// Write the snapshot to a file instead of stdout.
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Reconcile a live project with a snapshot file or another live project.
+    Apply {
+        #[arg(long)]
+        dest: String,
+        /// Snapshot or config document (JSON or YAML) to apply.
+        #[arg(long, conflicts_with = "source", required_unless_present = "source")]
+        file: Option<PathBuf>,
+        /// Live project to copy the configuration from.
+        #[arg(long)]
+        source: Option<String>,
+        /// Snapshot both projects were last in sync at. Only the keys the source changed
+        /// since then are applied.
+        #[arg(long, requires = "source")]
+        baseline: Option<PathBuf>,
+        /// Key patterns to apply, e.g. `EXTERNAL_GOOGLE_*,!SMTP_*`. Defaults to every key.
+        #[arg(long, value_delimiter = ',')]
+        keys: Vec<String>,
+        /// Print the planned writes without making them.
+        #[arg(long)]
+        dry_run: bool,
+        /// Write the overwritten destination values to this file before applying.
+        #[arg(long)]
+        backup: Option<PathBuf>,
+    },
+    /// Three-way comparison of two live projects against the snapshot they were last in
+    /// sync at, showing which side changed each key.
+    Merge {
+        #[arg(long)]
+        source: String,
+        #[arg(long)]
+        dest: String,
+        /// Snapshot file (JSON or YAML) of the last synced state.
+        #[arg(long)]
+        baseline: PathBuf,
+        /// Exit with status 2 when both sides changed a key differently.
+        #[arg(long)]
+        fail_on_conflict: bool,
+    },
+    /// Compare the third-party OAuth providers of two projects, or migrate their settings.
+    Providers {
+        #[arg(long)]
+        source: String,
+        #[arg(long)]
+        dest: String,
+        /// Migrate the settings instead of only comparing them.
+        #[arg(long)]
+        apply: bool,
+        /// Providers to migrate, e.g. `google,github`. Defaults to every provider that differs.
+        #[arg(long, value_delimiter = ',', requires = "apply")]
+        providers: Vec<String>,
+        /// Client secret for the destination as `PROVIDER=SECRET`, required for providers
+        /// being enabled, moving to a different client id or without a destination secret.
+        #[arg(long = "secret", value_parser = parse_secret, requires = "apply")]
+        secrets: Vec<(String, String)>,
+        /// Print the planned writes without making them.
+        #[arg(long, requires = "apply")]
+        dry_run: bool,
+    },
+    /// Compare two snapshot files without contacting Supabase.
+    DiffFile {
+        source: PathBuf,
+        dest: PathBuf,
+        /// Output format: json, markdown or html.
+        #[arg(long, default_value = "json")]
+        format: ReportFormat,
+        /// Exit with status 2 when the snapshots differ.
+        #[arg(long)]
+        fail_on_diff: bool,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Json,
+    Yaml,
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    match run(cli).await {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(cli: Cli) -> Result<ExitCode, PreviewError> {
+    // Nothing is kept between runs, so masks only need to match within one run's output
+    let redactor = Redactor::new(
+        parse_redact_patterns(Some(&cli.redact.join(","))),
+        Uuid::new_v4().to_string(),
+    );
+    let policy = match &cli.policy {
+        Some(path) => {
+            load_policy_config(&path.to_string_lossy()).map_err(PreviewError::BadRequest)?
+        }
+        None => PolicyConfig::default(),
+    };
+    let transforms = match &cli.transforms {
+        Some(path) => {
+            load_transform_config(&path.to_string_lossy()).map_err(PreviewError::BadRequest)?
+        }
+        None => TransformConfig::default(),
+    };
+
+    match cli.command {
+        Command::Preview {
+            source,
+            dest,
+            services,
+            format,
+            fail_on_diff,
+        } => {
+            let api = management_api(cli.token)?;
+            let services = if services.is_empty() {
+                Service::ALL.to_vec()
+            } else {
+                services
+            };
+
+            let configs = preview_services(
+                &api,
+                &services,
+                PreviewSide::Project(&source),
+                PreviewSide::Project(&dest),
+                &transforms,
+            )
+            .await?;
+            let policy = evaluate(&policy, &configs, &dest, &redactor);
+
+            let differs = !configs.is_empty();
+            print_report(
+                &redactor,
+                PreviewResponse { configs, policy },
+                format,
+                cli.text_diff_threshold,
+            )?;
+            Ok(diff_exit_code(fail_on_diff, differs))
+        }
+        Command::Snapshot {
+            project,
+            format,
+            output,
+        } => {
+            let api = management_api(cli.token)?;
+            let snapshot = build_snapshot(&api, &project).await?;
+            let body = match format {
+                Format::Json => serde_json::to_string_pretty(&snapshot)?,
+                Format::Yaml => serde_yaml::to_string(&snapshot).map_err(|e| {
+                    PreviewError::ApiError(format!("Failed to serialize snapshot: {:?}", e))
+                })?,
+            };
+
+            match output {
+                Some(path) => write_file(&path, &body)?,
+                None => println!("{}", body.trim_end()),
+            }
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Apply {
+            dest,
+            file,
+            source,
+            baseline,
+            keys,
+            dry_run,
+            backup,
+        } => {
+            let api = management_api(cli.token)?;
+            let (plans, declared_ref) = match (file, source, baseline) {
+                (Some(path), _, _) => {
+                    let declared = parse_snapshot(&read_file(&path)?)?;
+                    let plans = plan_config(&api, &dest, &declared, &transforms).await?;
+                    (plans, declared.project_ref)
+                }
+                (None, Some(source), Some(baseline)) => {
+                    let baseline = parse_snapshot(&read_file(&baseline)?)?;
+                    let (_, plans) =
+                        plan_merge(&api, &baseline, &source, &dest, &transforms).await?;
+                    (plans, source)
+                }
+                (None, Some(source), None) => {
+                    // Unlike a snapshot document, the source keeps its credentials so they are migrated
+                    let declared = build_partial_snapshot(&api, &source, &Service::ALL).await?;
+                    let plans = plan_config(&api, &dest, &declared, &transforms).await?;
+                    (plans, source)
+                }
+                (None, None, _) => {
+                    return Err(PreviewError::BadRequest(
+                        "Either --file or --source is required".to_string(),
+                    ));
+                }
+            };
+            let plans = select_plans(&keys, plans, &declared_ref, &dest)?;
+            apply_plans(&api, dest, plans, &policy, &redactor, dry_run, backup).await
+        }
+        Command::Merge {
+            source,
+            dest,
+            baseline,
+            fail_on_conflict,
+        } => {
+            let api = management_api(cli.token)?;
+            let baseline = parse_snapshot(&read_file(&baseline)?)?;
+            let (mut configs, plans) =
+                plan_merge(&api, &baseline, &source, &dest, &transforms).await?;
+            let policy = evaluate_plans(&policy, &plans, &dest, &redactor)?;
+
+            let conflicts = conflict_count(&configs);
+            redactor.redact_merges(&mut configs);
+            print_json(&MergeResponse {
+                source_ref: source,
+                dest_ref: dest,
+                configs,
+                conflicts,
+                operations: describe_plans(plans, &redactor),
+                policy,
+            })?;
+            Ok(if fail_on_conflict && conflicts > 0 {
+                ExitCode::from(DIFF_EXIT_CODE)
+            } else {
+                ExitCode::SUCCESS
+            })
+        }
+        Command::Providers {
+            source,
+            dest,
+            apply,
+            providers,
+            secrets,
+            dry_run,
+        } => {
+            let api = management_api(cli.token)?;
+            let (source_config, dest_config) =
+                fetch_auth_configs(&api, &source, &dest, &transforms).await?;
+            if !apply {
+                let mut providers = provider_diffs(&source_config, &dest_config)?;
+                redactor.redact_providers(&mut providers);
+                print_json(&ProviderPreview {
+                    providers,
+                    source_ref: source,
+                    dest_ref: dest,
+                })?;
+                return Ok(ExitCode::SUCCESS);
+            }
+
+            let request = ProviderApplyRequest {
+                providers,
+                secrets: secrets.into_iter().collect(),
+            };
+            let plan = plan_providers(&source_config, &dest_config, &request, &source, &dest)?;
+            apply_plans(&api, dest, vec![plan], &policy, &redactor, dry_run, None).await
+        }
+        Command::DiffFile {
+            source,
+            dest,
+            format,
+            fail_on_diff,
+        } => {
+            let source = parse_snapshot(&read_file(&source)?)?;
+            let dest = parse_snapshot(&read_file(&dest)?)?;
+            let configs = diff_snapshots(&source, &dest, &transforms).await?;
+            let policy = evaluate(&policy, &configs, &dest.project_ref, &redactor);
+
+            let differs = !configs.is_empty();
+            print_report(
+                &redactor,
+                PreviewResponse { configs, policy },
+                format,
+                cli.text_diff_threshold,
+            )?;
+            Ok(diff_exit_code(fail_on_diff, differs))
+        }
+    }
+}
+
+/// Checks the plans against the policy and executes them, or only prints them on a dry run.
+async fn apply_plans(
+    api: &ManagementApi,
+    dest: String,
+    plans: Vec<ServicePlan>,
+    policy: &PolicyConfig,
+    redactor: &Redactor,
+    dry_run: bool,
+    backup: Option<PathBuf>,
+) -> Result<ExitCode, PreviewError> {
+    let policy = evaluate_plans(policy, &plans, &dest, redactor)?;
+
+    if dry_run {
+        print_json(&DryRunResponse {
+            project_ref: dest,
+            dry_run: true,
+            services: describe_plans(plans, redactor),
+            policy,
+        })?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    enforce(&policy, false)?;
+
+    let stored_backup = create_backup(&dest, &plans);
+    if let (Some(path), Some(stored_backup)) = (&backup, &stored_backup) {
+        write_file(path, &serde_json::to_string_pretty(stored_backup)?)?;
+    }
+
+    let mut services = Vec::new();
+    for plan in plans {
+        services.push(execute_plan(api, &dest, plan).await);
+    }
+    let report = ApplyReport {
+        project_ref: dest,
+        backup_id: stored_backup.map(|stored_backup| stored_backup.id),
+        services,
+    };
+
+    print_json(&report)?;
+    Ok(if report.succeeded() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
+
+fn management_api(token: Option<String>) -> Result<ManagementApi, PreviewError> {
+    token
+        .filter(|token| !token.is_empty())
+        .map(ManagementApi::new)
+        .ok_or_else(|| {
+            PreviewError::BadRequest(
+                "A personal access token is required: pass --token or set SUPABASE_ACCESS_TOKEN"
+                    .to_string(),
+            )
+        })
+}
+
+fn parse_service(value: &str) -> Result<Service, String> {
+    Service::ALL
+        .into_iter()
+        .find(|service| service.key() == value)
+        .ok_or_else(|| {
+            let keys: Vec<&str> = Service::ALL.iter().map(Service::key).collect();
+            format!(
+                "unknown service `{}`, expected one of {}",
+                value,
+                keys.join(", ")
+            )
+        })
+}
+
+fn parse_secret(value: &str) -> Result<(String, String), String> {
+    value
+        .split_once('=')
+        .filter(|(provider, _)| !provider.is_empty())
+        .map(|(provider, secret)| (provider.to_lowercase(), secret.to_string()))
+        .ok_or_else(|| "expected PROVIDER=SECRET".to_string())
+}
+
+fn diff_exit_code(fail_on_diff: bool, differs: bool) -> ExitCode {
+    if fail_on_diff && differs {
+        ExitCode::from(DIFF_EXIT_CODE)
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+fn print_report(
+    redactor: &Redactor,
+    mut preview: PreviewResponse,
+    format: ReportFormat,
+    text_diff_threshold: usize,
+) -> Result<(), PreviewError> {
+    redactor.redact_configs(&mut preview.configs);
+    add_text_diffs(&mut preview.configs, text_diff_threshold);
+    match format {
+        ReportFormat::Json => print_json(&preview)?,
+        ReportFormat::Markdown => print!("{}", render_markdown(&preview)),
+        ReportFormat::Html => print!("{}", render_html(&preview)),
+    }
+    Ok(())
+}
+
+fn print_json<T: Serialize>(value: &T) -> Result<(), PreviewError> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+fn read_file(path: &Path) -> Result<String, PreviewError> {
+    std::fs::read_to_string(path)
+        .map_err(|e| PreviewError::BadRequest(format!("Failed to read {}: {}", path.display(), e)))
+}
+
+fn write_file(path: &Path, body: &str) -> Result<(), PreviewError> {
+    std::fs::write(path, body)
+        .map_err(|e| PreviewError::ApiError(format!("Failed to write {}: {}", path.display(), e)))
+}
@@ -0,0 +1,73 @@
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Json},
+};
+use serde::Serialize;
+use std::fmt;
+
+// Define error response
+#[derive(Debug, Serialize)]
+pub struct ErrorResponse {
+    pub error: String,
+}
+
+// Error type shared by the HTTP handlers, the services and the CLI
+#[derive(Debug)]
+pub enum PreviewError {
+    Unauthorized,
+    ApiError(String),
+    JsonError(serde_json::Error),
+    SessionError(String),
+    BadRequest(String),
+    NotFound(String),
+    Forbidden(String),
+    Conflict(String),
+}
+
+impl PreviewError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            PreviewError::Unauthorized => StatusCode::UNAUTHORIZED,
+            PreviewError::ApiError(_) | PreviewError::SessionError(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+            PreviewError::JsonError(_) | PreviewError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            PreviewError::NotFound(_) => StatusCode::NOT_FOUND,
+            PreviewError::Forbidden(_) => StatusCode::FORBIDDEN,
+            PreviewError::Conflict(_) => StatusCode::CONFLICT,
+        }
+    }
+}
+
+impl fmt::Display for PreviewError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PreviewError::Unauthorized => write!(f, "Unauthorized"),
+            PreviewError::ApiError(msg) => write!(f, "{}", msg),
+            PreviewError::JsonError(err) => write!(f, "JSON error: {}", err),
+            PreviewError::SessionError(msg) => write!(f, "Session error: {}", msg),
+            PreviewError::BadRequest(msg) => write!(f, "{}", msg),
+            PreviewError::NotFound(msg) => write!(f, "{}", msg),
+            PreviewError::Forbidden(msg) => write!(f, "{}", msg),
+            PreviewError::Conflict(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for PreviewError {}
+
+impl IntoResponse for PreviewError {
+    fn into_response(self) -> axum::response::Response {
+        let body = Json(ErrorResponse {
+            error: self.to_string(),
+        });
+
+        (self.status(), body).into_response()
+    }
+}
+
+impl From<serde_json::Error> for PreviewError {
+    fn from(err: serde_json::Error) -> Self {
+        PreviewError::JsonError(err)
+    }
+}
@@ -1,4 +1,5 @@
 use crate::models::AppState;
+use crate::services::profile::clear_session_profile;
 use axum::extract::State;
 use axum::http::StatusCode;
 use axum::response::IntoResponse;
@@ -14,6 +15,7 @@ pub async fn signout_handler(
     match token_result {
         Ok(Some(_token)) => match session.remove::<String>("supabase_access_token").await {
             Ok(_) => {
+                clear_session_profile(&session).await;
                 eprintln!("Successfully signed out user");
                 StatusCode::OK
             }
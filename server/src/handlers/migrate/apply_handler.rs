@@ -0,0 +1,78 @@
+use crate::error::PreviewError;
+use crate::handlers::migrate::job_handler::plans_response;
+use crate::models::AppState;
+use crate::models::history::{ApplyOrigin, HistoryKind};
+use crate::models::migrate::{ApplyOptions, ApplyRequest};
+use crate::services::management_api::ManagementApi;
+use crate::services::reconcile::{ServicePlan, plan_service};
+use crate::services::selection::select_plans;
+use crate::services::transform::transform_source;
+use crate::services::triggers::{host_rewrites, plan_triggers};
+use crate::services::{Service, fetch_service_config};
+
+use axum::{
+    extract::{Path, Query, State},
+    response::{Json, Response},
+};
+use tower_sessions::Session;
+
+/// Brings one service of the destination in line with the source, e.g. `/apply/roles`.
+pub async fn apply_service_handler(
+    State(app_state): State<AppState>,
+    Path(service): Path<Service>,
+    Query(options): Query<ApplyOptions>,
+    session: Session,
+    Json(request): Json<ApplyRequest>,
+) -> Result<Response, PreviewError> {
+    if service != Service::Triggers && !request.host_rewrites.is_empty() {
+        return Err(PreviewError::BadRequest(format!(
+            "host_rewrites only apply to triggers, not {}",
+            service
+        )));
+    }
+
+    let api = ManagementApi::from_session(&session).await?;
+    let source = fetch_service_config(&api, service, &request.source_id).await?;
+    let dest = fetch_service_config(&api, service, &request.dest_id).await?;
+    let rules = app_state
+        .config
+        .transforms
+        .rules_for(&request.source_id, &request.dest_id);
+    let source = transform_source(rules, service, source, &dest);
+
+    let plan = ServicePlan {
+        service,
+        operations: plan_service(
+            service,
+            &source,
+            &dest,
+            &request.source_id,
+            &request.dest_id,
+        ),
+        source,
+        dest,
+    };
+    let mut plans = select_plans(
+        &request.keys,
+        vec![plan],
+        &request.source_id,
+        &request.dest_id,
+    )?;
+
+    // Selection re-plans with the project hostnames only, so triggers are planned again
+    // from the selected source with the caller's rewrites.
+    if service == Service::Triggers {
+        let rewrites = host_rewrites(&request.source_id, &request.dest_id, &request.host_rewrites);
+        for plan in &mut plans {
+            plan.operations = plan_triggers(&plan.source, &plan.dest, &rewrites);
+        }
+    }
+
+    let origin = ApplyOrigin {
+        kind: HistoryKind::Apply,
+        source_ref: Some(request.source_id),
+        project_ref: request.dest_id,
+        approval: None,
+    };
+    plans_response(&app_state, &session, api, origin, plans, &options).await
+}
@@ -0,0 +1,96 @@
+use crate::error::PreviewError;
+use crate::handlers::migrate::job_handler::plans_response;
+use crate::models::AppState;
+use crate::models::backup::Backup;
+use crate::models::history::{ApplyOrigin, HistoryKind};
+use crate::models::migrate::ApplyOptions;
+use crate::services::backup::plan_rollback;
+use crate::services::fetch_service_config;
+use crate::services::management_api::ManagementApi;
+use crate::services::profile::{accessible_projects, require_project_access};
+use crate::services::reconcile::ServicePlan;
+use crate::services::snapshot::snapshot_config;
+
+use axum::{
+    extract::{Path, Query, State},
+    response::{IntoResponse, Json, Response},
+};
+use tower_sessions::Session;
+
+/// Lists the backups of the projects the signed in user can access.
+pub async fn list_backups_handler(
+    State(app_state): State<AppState>,
+    session: Session,
+) -> Result<impl IntoResponse, PreviewError> {
+    let api = ManagementApi::from_session(&session).await?;
+    let projects = accessible_projects(&api).await?;
+    Ok(Json(app_state.history.list_backups(&projects)?))
+}
+
+pub async fn get_backup_handler(
+    State(app_state): State<AppState>,
+    Path(backup_id): Path<String>,
+    session: Session,
+) -> Result<impl IntoResponse, PreviewError> {
+    let api = ManagementApi::from_session(&session).await?;
+    // Rollbacks read the stored backup, so only the response is masked
+    let mut backup = find_backup(&app_state, &api, &backup_id).await?;
+    for service_backup in &mut backup.services {
+        app_state
+            .redactor
+            .redact_config(service_backup.service, &mut service_backup.value);
+    }
+    Ok(Json(backup))
+}
+
+/// Restores the values captured by a backup on its project. The rollback itself is applied
+/// like any other change, so it gets its own backup.
+pub async fn rollback_handler(
+    State(app_state): State<AppState>,
+    Path(backup_id): Path<String>,
+    Query(options): Query<ApplyOptions>,
+    session: Session,
+) -> Result<Response, PreviewError> {
+    let api = ManagementApi::from_session(&session).await?;
+    let backup = find_backup(&app_state, &api, &backup_id).await?;
+
+    let mut plans = Vec::new();
+    for service_backup in &backup.services {
+        let service = service_backup.service;
+        let live = fetch_service_config(&api, service, &backup.project_ref)
+            .await
+            .map_err(|e| {
+                PreviewError::ApiError(format!("Failed to get {} config: {:?}", service, e))
+            })?;
+        let live = snapshot_config(service, live);
+
+        plans.push(ServicePlan {
+            service,
+            operations: plan_rollback(service_backup, &live, &backup.project_ref),
+            source: service_backup.value.clone(),
+            dest: live,
+        });
+    }
+
+    let origin = ApplyOrigin {
+        kind: HistoryKind::Rollback,
+        source_ref: None,
+        project_ref: backup.project_ref,
+        approval: None,
+    };
+    plans_response(&app_state, &session, api, origin, plans, &options).await
+}
+
+async fn find_backup(
+    app_state: &AppState,
+    api: &ManagementApi,
+    backup_id: &str,
+) -> Result<Backup, PreviewError> {
+    let not_found = || format!("Backup {} not found", backup_id);
+    let backup = app_state
+        .history
+        .get_backup(backup_id)?
+        .ok_or_else(|| PreviewError::NotFound(not_found()))?;
+    require_project_access(api, &backup.project_ref, not_found).await?;
+    Ok(backup)
+}
@@ -0,0 +1,62 @@
+use crate::error::PreviewError;
+use crate::handlers::migrate::job_handler::plans_response;
+use crate::models::AppState;
+use crate::models::history::{ApplyOrigin, HistoryKind};
+use crate::models::migrate::ApplyOptions;
+use crate::services::management_api::ManagementApi;
+use crate::services::reconcile::plan_config;
+use crate::services::selection::{select_plans, split_patterns};
+use crate::services::snapshot::parse_snapshot;
+
+use axum::{
+    extract::{Query, State},
+    response::Response,
+};
+use serde::Deserialize;
+use tower_sessions::Session;
+
+#[derive(Debug, Deserialize)]
+pub struct ApplyConfigQuery {
+    pub dest_id: String,
+    /// Comma separated key patterns to apply, e.g. `EXTERNAL_GOOGLE_*,!SMTP_*`.
+    pub keys: Option<String>,
+    pub dry_run: Option<bool>,
+    pub background: Option<bool>,
+}
+
+/// Reconciles a live project with a posted snapshot or config document, writing only the
+/// keys that differ.
+pub async fn apply_config_handler(
+    State(app_state): State<AppState>,
+    Query(params): Query<ApplyConfigQuery>,
+    session: Session,
+    body: String,
+) -> Result<Response, PreviewError> {
+    let declared = parse_snapshot(&body)?;
+    let api = ManagementApi::from_session(&session).await?;
+    let plans = plan_config(
+        &api,
+        &params.dest_id,
+        &declared,
+        &app_state.config.transforms,
+    )
+    .await?;
+    let plans = select_plans(
+        &split_patterns(params.keys.as_deref()),
+        plans,
+        &declared.project_ref,
+        &params.dest_id,
+    )?;
+
+    let options = ApplyOptions {
+        dry_run: params.dry_run,
+        background: params.background,
+    };
+    let origin = ApplyOrigin {
+        kind: HistoryKind::Apply,
+        source_ref: Some(declared.project_ref).filter(|project_ref| !project_ref.is_empty()),
+        project_ref: params.dest_id,
+        approval: None,
+    };
+    plans_response(&app_state, &session, api, origin, plans, &options).await
+}
@@ -0,0 +1,81 @@
+use crate::error::PreviewError;
+use crate::models::AppState;
+use crate::models::drift::DriftStatus;
+use crate::services::drift::{check_pair, find_pair};
+use crate::services::management_api::ManagementApi;
+use crate::services::profile::accessible_projects;
+
+use axum::{
+    extract::{Path, State},
+    response::{IntoResponse, Json},
+};
+use tower_sessions::Session;
+
+/// Lists the drift of the pairs whose projects the signed in user can both access.
+pub async fn list_drift_handler(
+    State(app_state): State<AppState>,
+    session: Session,
+) -> Result<impl IntoResponse, PreviewError> {
+    let api = ManagementApi::from_session(&session).await?;
+    let projects = accessible_projects(&api).await?;
+
+    let mut statuses: Vec<DriftStatus> = app_state
+        .drift
+        .lock()
+        .map_err(|e| PreviewError::ApiError(format!("Failed to read drift status: {:?}", e)))?
+        .values()
+        .filter(|status| accessible(&projects, status))
+        .map(|status| redacted(&app_state, status.clone()))
+        .collect();
+    statuses.sort_by(|a, b| a.pair.name.cmp(&b.pair.name));
+
+    Ok(Json(statuses))
+}
+
+/// Reads the drift of one pair. Pairs with a project the signed in user cannot access are
+/// reported as missing.
+pub async fn get_drift_handler(
+    State(app_state): State<AppState>,
+    Path(pair): Path<String>,
+    session: Session,
+) -> Result<impl IntoResponse, PreviewError> {
+    let api = ManagementApi::from_session(&session).await?;
+    find_pair(&app_state.config.drift, &pair)?;
+    let projects = accessible_projects(&api).await?;
+
+    let statuses = app_state
+        .drift
+        .lock()
+        .map_err(|e| PreviewError::ApiError(format!("Failed to read drift status: {:?}", e)))?;
+    let status = statuses
+        .get(&pair)
+        .filter(|status| accessible(&projects, status))
+        .cloned()
+        .ok_or_else(|| PreviewError::NotFound(format!("Drift pair {} not found", pair)))?;
+
+    Ok(Json(redacted(&app_state, status)))
+}
+
+/// Checks a pair right away with the signed in user's token instead of waiting for the
+/// scheduler.
+pub async fn check_drift_handler(
+    State(app_state): State<AppState>,
+    Path(pair): Path<String>,
+    session: Session,
+) -> Result<impl IntoResponse, PreviewError> {
+    let api = ManagementApi::from_session(&session).await?;
+
+    let status = check_pair(&app_state, &api, &pair).await?;
+    Ok(Json(redacted(&app_state, status)))
+}
+
+fn accessible(projects: &[String], status: &DriftStatus) -> bool {
+    projects.contains(&status.pair.source) && projects.contains(&status.pair.dest)
+}
+
+// Statuses keep the real values so later checks compare like with like
+fn redacted(app_state: &AppState, mut status: DriftStatus) -> DriftStatus {
+    app_state.redactor.redact_configs(&mut status.configs);
+    app_state.redactor.redact_changes(&mut status.changes);
+    status
+}
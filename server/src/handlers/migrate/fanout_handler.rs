@@ -0,0 +1,170 @@
+use crate::error::PreviewError;
+use crate::handlers::migrate::job_handler::apply_outcome;
+use crate::models::AppState;
+use crate::models::fanout::{
+    FanOutApplyQuery, FanOutApplyResponse, FanOutDestination, FanOutPreview, FanOutRequest,
+    FanOutResult,
+};
+use crate::models::history::{ApplyOrigin, HistoryEntry, HistoryKind};
+use crate::models::job::JobStatus;
+use crate::models::migrate::ApplyOptions;
+use crate::services::fanout::{
+    MAX_CONCURRENT_DESTINATIONS, fan_out_matrix, preview_fan_out, validate_fan_out,
+};
+use crate::services::history::record_entry;
+use crate::services::management_api::ManagementApi;
+use crate::services::now_rfc3339;
+use crate::services::policy::evaluate;
+use crate::services::profile::current_actor;
+use crate::services::reconcile::plan_config;
+use crate::services::selection::select_plans;
+use crate::services::snapshot::build_partial_snapshot;
+
+use axum::{
+    extract::{Query, State},
+    response::{IntoResponse, Json},
+};
+use futures_util::stream::{self, StreamExt};
+use tower_sessions::Session;
+use uuid::Uuid;
+
+/// Compares one source with several destinations and returns a key by destination matrix.
+/// Each destination is recorded in the history as its own preview.
+pub async fn fan_out_preview_handler(
+    State(app_state): State<AppState>,
+    session: Session,
+    Json(request): Json<FanOutRequest>,
+) -> Result<impl IntoResponse, PreviewError> {
+    validate_fan_out(&request)?;
+    let api = ManagementApi::from_session(&session).await?;
+    let mut results = preview_fan_out(&api, &request, &app_state.config.transforms).await?;
+
+    let actor = current_actor(&session, &api).await;
+    let services: Vec<String> = request
+        .services
+        .iter()
+        .map(|service| service.name().to_string())
+        .collect();
+    let mut destinations = Vec::new();
+    for (dest_ref, configs) in &results {
+        let destination = match configs {
+            Ok(configs) => {
+                record_entry(
+                    &app_state.history,
+                    &HistoryEntry {
+                        id: Uuid::new_v4().to_string(),
+                        kind: HistoryKind::Preview,
+                        actor: actor.clone(),
+                        approved_by: None,
+                        plan_id: None,
+                        created_at: now_rfc3339(),
+                        source_ref: Some(request.source_id.clone()),
+                        dest_ref: dest_ref.clone(),
+                        services: services.clone(),
+                        status: JobStatus::Succeeded,
+                        diffs: configs.clone(),
+                        outcome: None,
+                    },
+                );
+                FanOutDestination {
+                    dest_ref: dest_ref.clone(),
+                    differences: configs.iter().map(|config| config.diffs.len()).sum(),
+                    policy: evaluate(
+                        &app_state.config.policy,
+                        configs,
+                        dest_ref,
+                        &app_state.redactor,
+                    ),
+                    error: None,
+                }
+            }
+            Err(e) => FanOutDestination {
+                dest_ref: dest_ref.clone(),
+                differences: 0,
+                policy: Vec::new(),
+                error: Some(e.to_string()),
+            },
+        };
+        destinations.push(destination);
+    }
+
+    for (_, configs) in &mut results {
+        if let Ok(configs) = configs {
+            app_state.redactor.redact_configs(configs);
+        }
+    }
+    Ok(Json(FanOutPreview {
+        matrix: fan_out_matrix(&results),
+        source_ref: request.source_id,
+        destinations,
+    }))
+}
+
+/// Applies one source to several destinations concurrently. Each destination is planned,
+/// checked against the policy and recorded on its own, so one failing destination does not
+/// stop the others.
+pub async fn apply_fan_out_handler(
+    State(app_state): State<AppState>,
+    Query(params): Query<FanOutApplyQuery>,
+    session: Session,
+    Json(request): Json<FanOutRequest>,
+) -> Result<impl IntoResponse, PreviewError> {
+    validate_fan_out(&request)?;
+    let api = ManagementApi::from_session(&session).await?;
+    let declared = build_partial_snapshot(&api, &request.source_id, &request.services).await?;
+    let actor = current_actor(&session, &api).await;
+    let options = ApplyOptions {
+        dry_run: params.dry_run,
+        background: params.background,
+    };
+
+    let (app_state, api, declared, actor, options) =
+        (&app_state, &api, &declared, &actor, &options);
+    let source_id = &request.source_id;
+    let keys = &request.keys;
+    let results = stream::iter(request.dest_ids.clone())
+        .map(|dest_id| async move {
+            let outcome = async {
+                let plans =
+                    plan_config(api, &dest_id, declared, &app_state.config.transforms).await?;
+                let plans = select_plans(keys, plans, &declared.project_ref, &dest_id)?;
+                let origin = ApplyOrigin {
+                    kind: HistoryKind::Apply,
+                    source_ref: Some(source_id.clone()),
+                    project_ref: dest_id.clone(),
+                    approval: None,
+                };
+                apply_outcome(
+                    app_state,
+                    actor.clone(),
+                    api.clone(),
+                    origin,
+                    plans,
+                    options,
+                )
+                .await
+            }
+            .await;
+
+            match outcome {
+                Ok(outcome) => FanOutResult {
+                    dest_ref: dest_id,
+                    outcome: Some(outcome),
+                    error: None,
+                },
+                Err(e) => FanOutResult {
+                    dest_ref: dest_id,
+                    outcome: None,
+                    error: Some(e.to_string()),
+                },
+            }
+        })
+        .buffered(MAX_CONCURRENT_DESTINATIONS)
+        .collect()
+        .await;
+
+    Ok(Json(FanOutApplyResponse {
+        source_ref: request.source_id,
+        results,
+    }))
+}
@@ -0,0 +1,124 @@
+use crate::error::PreviewError;
+use crate::handlers::migrate::preview_handler::report_response;
+use crate::models::AppState;
+use crate::models::history::{
+    HistoryCompareQuery, HistoryEntry, HistoryKind, HistoryQuery, HistoryReportQuery,
+    PreviewComparison,
+};
+use crate::models::migrate::PreviewResponse;
+use crate::services::history::compare_previews;
+use crate::services::management_api::ManagementApi;
+use crate::services::policy::evaluate;
+use crate::services::profile::accessible_projects;
+use crate::services::report::ReportFormat;
+
+use axum::{
+    extract::{Path, Query, State},
+    response::{IntoResponse, Json, Response},
+};
+use tower_sessions::Session;
+
+/// Lists the entries whose projects the signed in user can all access.
+pub async fn list_history_handler(
+    State(app_state): State<AppState>,
+    Query(query): Query<HistoryQuery>,
+    session: Session,
+) -> Result<impl IntoResponse, PreviewError> {
+    let api = ManagementApi::from_session(&session).await?;
+    let projects = accessible_projects(&api).await?;
+    Ok(Json(app_state.history.list(&query, &projects)?))
+}
+
+pub async fn get_history_handler(
+    State(app_state): State<AppState>,
+    Path(history_id): Path<String>,
+    session: Session,
+) -> Result<impl IntoResponse, PreviewError> {
+    let api = ManagementApi::from_session(&session).await?;
+    let mut entry = find_entry(&app_state, &api, &history_id).await?;
+    app_state.redactor.redact_configs(&mut entry.diffs);
+    Ok(Json(entry))
+}
+
+/// Renders the diffs recorded by a preview as a Markdown or HTML report.
+pub async fn history_report_handler(
+    State(app_state): State<AppState>,
+    Path(history_id): Path<String>,
+    Query(query): Query<HistoryReportQuery>,
+    session: Session,
+) -> Result<Response, PreviewError> {
+    let api = ManagementApi::from_session(&session).await?;
+    let entry = find_preview(&app_state, &api, &history_id).await?;
+    // Checked against the rules as they are now, not as they were when the preview ran
+    let policy = evaluate(
+        &app_state.config.policy,
+        &entry.diffs,
+        &entry.dest_ref,
+        &app_state.redactor,
+    );
+
+    Ok(report_response(
+        &app_state,
+        PreviewResponse {
+            configs: entry.diffs,
+            policy,
+        },
+        query.format.unwrap_or(ReportFormat::Markdown),
+    ))
+}
+
+/// Shows how the differences between projects moved from one recorded preview to another.
+pub async fn compare_history_handler(
+    State(app_state): State<AppState>,
+    Query(query): Query<HistoryCompareQuery>,
+    session: Session,
+) -> Result<impl IntoResponse, PreviewError> {
+    let api = ManagementApi::from_session(&session).await?;
+    let from = find_preview(&app_state, &api, &query.from).await?;
+    let to = find_preview(&app_state, &api, &query.to).await?;
+
+    let mut changes = compare_previews(&from.diffs, &to.diffs);
+    app_state.redactor.redact_changes(&mut changes);
+    Ok(Json(PreviewComparison {
+        changes,
+        from: from.id,
+        to: to.id,
+    }))
+}
+
+// Reads an entry whose live projects the signed in user can all access. Other entries are
+// reported as missing.
+async fn find_entry(
+    app_state: &AppState,
+    api: &ManagementApi,
+    history_id: &str,
+) -> Result<HistoryEntry, PreviewError> {
+    let not_found = || format!("History entry {} not found", history_id);
+    let entry = app_state
+        .history
+        .get(history_id)?
+        .ok_or_else(|| PreviewError::NotFound(not_found()))?;
+
+    let projects = accessible_projects(api).await?;
+    let accessible =
+        |project: &String| project.starts_with("snapshot:") || projects.contains(project);
+    if !accessible(&entry.dest_ref) || !entry.source_ref.iter().all(accessible) {
+        return Err(PreviewError::NotFound(not_found()));
+    }
+    Ok(entry)
+}
+
+async fn find_preview(
+    app_state: &AppState,
+    api: &ManagementApi,
+    history_id: &str,
+) -> Result<HistoryEntry, PreviewError> {
+    let entry = find_entry(app_state, api, history_id).await?;
+    if entry.kind != HistoryKind::Preview {
+        return Err(PreviewError::BadRequest(format!(
+            "History entry {} is not a preview",
+            history_id
+        )));
+    }
+    Ok(entry)
+}
@@ -0,0 +1,226 @@
+use crate::error::PreviewError;
+use crate::models::AppState;
+use crate::models::history::{ApplyOrigin, HistoryKind};
+use crate::models::job::{Job, JobEvent, JobStatus};
+use crate::models::migrate::{ApplyOptions, ApplyOutcome, DryRunResponse};
+use crate::services::history::{apply_entry, record_entry};
+use crate::services::jobs::{cancel_job, start_job};
+use crate::services::management_api::ManagementApi;
+use crate::services::notify::{migration_notification, notify};
+use crate::services::policy::{enforce, evaluate_plans};
+use crate::services::profile::{accessible_projects, current_actor, require_project_access};
+use crate::services::reconcile::{ServicePlan, apply_plans, describe_plans};
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{
+        IntoResponse, Json, Response,
+        sse::{Event, KeepAlive, Sse},
+    },
+};
+use futures_util::stream::{self, Stream, StreamExt};
+use serde_json::json;
+use tokio::sync::broadcast::error::RecvError;
+use tower_sessions::Session;
+
+/// Answers an apply request according to its options: the redacted plan for a dry run, a
+/// queued job for a background apply, or the report of an apply run inline.
+pub async fn plans_response(
+    app_state: &AppState,
+    session: &Session,
+    api: ManagementApi,
+    origin: ApplyOrigin,
+    plans: Vec<ServicePlan>,
+    options: &ApplyOptions,
+) -> Result<Response, PreviewError> {
+    let actor = current_actor(session, &api).await;
+
+    Ok(
+        match apply_outcome(app_state, actor, api, origin, plans, options).await? {
+            ApplyOutcome::DryRun(response) => Json(response).into_response(),
+            ApplyOutcome::Queued(job) => (StatusCode::ACCEPTED, Json(job)).into_response(),
+            ApplyOutcome::Applied(report) => Json(report).into_response(),
+        },
+    )
+}
+
+/// Runs an apply for `actor` according to its options. Applies that hit blocking policy
+/// rules are refused. Every outcome is recorded in the migration history, applies of an
+/// approved plan with the plan's id.
+pub async fn apply_outcome(
+    app_state: &AppState,
+    actor: Option<String>,
+    api: ManagementApi,
+    origin: ApplyOrigin,
+    plans: Vec<ServicePlan>,
+    options: &ApplyOptions,
+) -> Result<ApplyOutcome, PreviewError> {
+    let mut history = apply_entry(actor, &origin, &plans, &app_state.redactor)?;
+
+    // Rollbacks restore values that were live before, so they are not held to the policy
+    let policy = match origin.kind {
+        HistoryKind::Rollback => Vec::new(),
+        _ => evaluate_plans(
+            &app_state.config.policy,
+            &plans,
+            &origin.project_ref,
+            &app_state.redactor,
+        )?,
+    };
+
+    if options.dry_run.unwrap_or(false) {
+        let response = DryRunResponse {
+            project_ref: origin.project_ref,
+            dry_run: true,
+            services: describe_plans(plans, &app_state.redactor),
+            policy,
+        };
+        history.kind = HistoryKind::DryRun;
+        history.outcome = serde_json::to_value(&response.services).ok();
+        record_entry(&app_state.history, &history);
+        return Ok(ApplyOutcome::DryRun(response));
+    }
+
+    if let Err(e) = enforce(&policy, history.approved_by.is_some()) {
+        history.status = JobStatus::Failed;
+        history.outcome = Some(json!({"error": e.to_string(), "policy": policy}));
+        record_entry(&app_state.history, &history);
+        return Err(e);
+    }
+
+    if options.background.unwrap_or(false) {
+        return Ok(ApplyOutcome::Queued(start_job(
+            app_state, api, plans, history,
+        )?));
+    }
+
+    let report = apply_plans(app_state, &api, &origin.project_ref, plans).await?;
+    if !report.succeeded() {
+        history.status = JobStatus::Failed;
+    }
+    history.outcome = serde_json::to_value(&report).ok();
+    record_entry(&app_state.history, &history);
+    notify(
+        &app_state.config.notifications,
+        migration_notification(&history, history.status, &report),
+    );
+
+    Ok(ApplyOutcome::Applied(report))
+}
+
+/// Lists the jobs of the projects the signed in user can access, newest first.
+pub async fn list_jobs_handler(
+    State(app_state): State<AppState>,
+    session: Session,
+) -> Result<impl IntoResponse, PreviewError> {
+    let api = ManagementApi::from_session(&session).await?;
+    let projects = accessible_projects(&api).await?;
+
+    let mut jobs: Vec<Job> = app_state
+        .jobs
+        .lock()
+        .map_err(|e| PreviewError::ApiError(format!("Failed to read jobs: {:?}", e)))?
+        .values()
+        .filter(|entry| projects.contains(&entry.job.project_ref))
+        .map(|entry| entry.job.clone())
+        .collect();
+    jobs.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+
+    Ok(Json(jobs))
+}
+
+pub async fn get_job_handler(
+    State(app_state): State<AppState>,
+    Path(job_id): Path<String>,
+    session: Session,
+) -> Result<impl IntoResponse, PreviewError> {
+    let api = ManagementApi::from_session(&session).await?;
+    Ok(Json(find_job(&app_state, &api, &job_id).await?))
+}
+
+/// Cancels a job on behalf of the user who started it or an admin.
+pub async fn cancel_job_handler(
+    State(app_state): State<AppState>,
+    Path(job_id): Path<String>,
+    session: Session,
+) -> Result<impl IntoResponse, PreviewError> {
+    let api = ManagementApi::from_session(&session).await?;
+    let job = find_job(&app_state, &api, &job_id).await?;
+
+    let actor = current_actor(&session, &api).await;
+    let is_owner = actor.is_some() && job.actor == actor;
+    let is_admin = actor.is_some_and(|actor| app_state.config.admins.contains(&actor));
+    if !is_owner && !is_admin {
+        return Err(PreviewError::Forbidden(format!(
+            "Job {} can only be cancelled by {} or an admin",
+            job_id,
+            job.actor.as_deref().unwrap_or("the user who started it")
+        )));
+    }
+
+    Ok(Json(cancel_job(&app_state, &job_id)?))
+}
+
+/// Streams a job's progress as Server-Sent Events. The first `snapshot` event carries the
+/// current job; `step`, `log` and `finished` events follow until the job is done.
+pub async fn job_events_handler(
+    State(app_state): State<AppState>,
+    Path(job_id): Path<String>,
+    session: Session,
+) -> Result<Sse<impl Stream<Item = Result<Event, axum::Error>>>, PreviewError> {
+    let api = ManagementApi::from_session(&session).await?;
+    find_job(&app_state, &api, &job_id).await?;
+
+    // Subscribe under the same lock the runner publishes under, so no event falls between
+    // the snapshot and the subscription.
+    let (job, receiver) = {
+        let jobs = app_state
+            .jobs
+            .lock()
+            .map_err(|e| PreviewError::ApiError(format!("Failed to read jobs: {:?}", e)))?;
+        let entry = jobs
+            .get(&job_id)
+            .ok_or_else(|| PreviewError::NotFound(format!("Job {} not found", job_id)))?;
+        (entry.job.clone(), entry.events.subscribe())
+    };
+
+    let snapshot = Event::default().event("snapshot").json_data(&job);
+    let receiver = (!job.status.is_finished()).then_some(receiver);
+
+    let updates = stream::unfold(receiver, |receiver| async move {
+        let mut receiver = receiver?;
+        loop {
+            match receiver.recv().await {
+                Ok(event) => {
+                    let finished = matches!(event, JobEvent::Finished { .. });
+                    let sse = Event::default().event(event.name()).json_data(&event);
+                    return Some((sse, (!finished).then_some(receiver)));
+                }
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+
+    Ok(Sse::new(stream::once(async { snapshot }).chain(updates)).keep_alive(KeepAlive::default()))
+}
+
+// Reads a job of a project the signed in user can access. Jobs of other projects are
+// reported as missing.
+async fn find_job(
+    app_state: &AppState,
+    api: &ManagementApi,
+    job_id: &str,
+) -> Result<Job, PreviewError> {
+    let not_found = || format!("Job {} not found", job_id);
+    let job = app_state
+        .jobs
+        .lock()
+        .map_err(|e| PreviewError::ApiError(format!("Failed to read jobs: {:?}", e)))?
+        .get(job_id)
+        .map(|entry| entry.job.clone())
+        .ok_or_else(|| PreviewError::NotFound(not_found()))?;
+    require_project_access(api, &job.project_ref, not_found).await?;
+    Ok(job)
+}
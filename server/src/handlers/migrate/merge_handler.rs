@@ -0,0 +1,123 @@
+use crate::error::PreviewError;
+use crate::handlers::migrate::job_handler::plans_response;
+use crate::models::AppState;
+use crate::models::history::{ApplyOrigin, HistoryEntry, HistoryKind};
+use crate::models::job::JobStatus;
+use crate::models::merge::MergeResponse;
+use crate::models::migrate::ApplyOptions;
+use crate::services::history::record_entry;
+use crate::services::management_api::ManagementApi;
+use crate::services::merge::{conflict_count, plan_merge};
+use crate::services::now_rfc3339;
+use crate::services::policy::evaluate;
+use crate::services::profile::current_actor;
+use crate::services::reconcile::{describe_plans, plan_diffs};
+use crate::services::snapshot::parse_snapshot;
+
+use axum::{
+    extract::{Query, State},
+    response::{IntoResponse, Json, Response},
+};
+use serde::Deserialize;
+use tower_sessions::Session;
+use uuid::Uuid;
+
+#[derive(Debug, Deserialize)]
+pub struct MergeQuery {
+    pub source_id: String,
+    pub dest_id: String,
+    pub dry_run: Option<bool>,
+    pub background: Option<bool>,
+}
+
+/// Three-way comparison of two live projects against a posted baseline snapshot. Only the
+/// keys changed on the source side are proposed, and only those are recorded as differences.
+pub async fn merge_preview_handler(
+    State(app_state): State<AppState>,
+    Query(params): Query<MergeQuery>,
+    session: Session,
+    body: String,
+) -> Result<impl IntoResponse, PreviewError> {
+    let baseline = parse_snapshot(&body)?;
+    let api = ManagementApi::from_session(&session).await?;
+    let (mut configs, plans) = plan_merge(
+        &api,
+        &baseline,
+        &params.source_id,
+        &params.dest_id,
+        &app_state.config.transforms,
+    )
+    .await?;
+
+    let diffs = plan_diffs(&plans)?;
+    let policy = evaluate(
+        &app_state.config.policy,
+        &diffs,
+        &params.dest_id,
+        &app_state.redactor,
+    );
+    record_entry(
+        &app_state.history,
+        &HistoryEntry {
+            id: Uuid::new_v4().to_string(),
+            kind: HistoryKind::Preview,
+            actor: current_actor(&session, &api).await,
+            approved_by: None,
+            plan_id: None,
+            created_at: now_rfc3339(),
+            source_ref: Some(params.source_id.clone()),
+            dest_ref: params.dest_id.clone(),
+            services: baseline
+                .services
+                .keys()
+                .map(|service| service.name().to_string())
+                .collect(),
+            status: JobStatus::Succeeded,
+            diffs,
+            outcome: None,
+        },
+    );
+
+    let conflicts = conflict_count(&configs);
+    app_state.redactor.redact_merges(&mut configs);
+    Ok(Json(MergeResponse {
+        source_ref: params.source_id,
+        dest_ref: params.dest_id,
+        configs,
+        conflicts,
+        operations: describe_plans(plans, &app_state.redactor),
+        policy,
+    }))
+}
+
+/// Applies the source side changes found by a three-way comparison, leaving keys changed in
+/// the destination or in conflict untouched.
+pub async fn apply_merge_handler(
+    State(app_state): State<AppState>,
+    Query(params): Query<MergeQuery>,
+    session: Session,
+    body: String,
+) -> Result<Response, PreviewError> {
+    let baseline = parse_snapshot(&body)?;
+    let api = ManagementApi::from_session(&session).await?;
+    let (_, plans) = plan_merge(
+        &api,
+        &baseline,
+        &params.source_id,
+        &params.dest_id,
+        &app_state.config.transforms,
+    )
+    .await?;
+
+    let options = ApplyOptions {
+        dry_run: params.dry_run,
+        background: params.background,
+    };
+    let origin = ApplyOrigin {
+        kind: HistoryKind::Apply,
+        source_ref: Some(params.source_id),
+        project_ref: params.dest_id,
+        approval: None,
+    };
+    plans_response(&app_state, &session, api, origin, plans, &options).await
+}
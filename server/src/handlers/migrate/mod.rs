@@ -1,3 +1,30 @@
+pub mod apply_handler;
+pub mod backup_handler;
+pub mod config_handler;
+pub mod drift_handler;
+pub mod fanout_handler;
+pub mod history_handler;
+pub mod job_handler;
+pub mod merge_handler;
+pub mod plan_handler;
 pub mod preview_handler;
+pub mod provider_handler;
+pub mod snapshot_handler;
 
-pub use preview_handler::preview_handler;
\ No newline at end of file
+pub use apply_handler::apply_service_handler;
+pub use backup_handler::{get_backup_handler, list_backups_handler, rollback_handler};
+pub use config_handler::apply_config_handler;
+pub use drift_handler::{check_drift_handler, get_drift_handler, list_drift_handler};
+pub use fanout_handler::{apply_fan_out_handler, fan_out_preview_handler};
+pub use history_handler::{
+    compare_history_handler, get_history_handler, history_report_handler, list_history_handler,
+};
+pub use job_handler::{cancel_job_handler, get_job_handler, job_events_handler, list_jobs_handler};
+pub use merge_handler::{apply_merge_handler, merge_preview_handler};
+pub use plan_handler::{
+    apply_plan_handler, approve_plan_handler, create_plan_handler, get_plan_handler,
+    list_plans_handler,
+};
+pub use preview_handler::preview_handler;
+pub use provider_handler::{apply_providers_handler, provider_preview_handler};
+pub use snapshot_handler::{snapshot_handler, snapshot_preview_handler};
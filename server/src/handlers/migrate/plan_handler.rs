@@ -0,0 +1,101 @@
+use crate::error::PreviewError;
+use crate::handlers::migrate::job_handler::plans_response;
+use crate::models::AppState;
+use crate::models::history::{ApplyOrigin, HistoryKind, PlanApproval};
+use crate::models::migrate::ApplyOptions;
+use crate::models::plan::{PlanApplyQuery, PlanRequest};
+use crate::services::management_api::ManagementApi;
+use crate::services::plans::{approve_plan, claim_plan, create_plan, find_plan, release_plan};
+use crate::services::profile::{accessible_projects, current_identity};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+};
+use tower_sessions::Session;
+
+pub async fn create_plan_handler(
+    State(app_state): State<AppState>,
+    session: Session,
+    Json(request): Json<PlanRequest>,
+) -> Result<impl IntoResponse, PreviewError> {
+    let api = ManagementApi::from_session(&session).await?;
+    let author = current_identity(&session, &api).await?;
+    let plan = create_plan(&app_state, &api, request, author).await?;
+
+    Ok((StatusCode::CREATED, Json(plan)))
+}
+
+/// Lists the plans between projects the signed in user can access.
+pub async fn list_plans_handler(
+    State(app_state): State<AppState>,
+    session: Session,
+) -> Result<impl IntoResponse, PreviewError> {
+    let api = ManagementApi::from_session(&session).await?;
+    let projects = accessible_projects(&api).await?;
+    Ok(Json(app_state.history.list_plans(&projects)?))
+}
+
+pub async fn get_plan_handler(
+    State(app_state): State<AppState>,
+    Path(plan_id): Path<String>,
+    session: Session,
+) -> Result<impl IntoResponse, PreviewError> {
+    let api = ManagementApi::from_session(&session).await?;
+    let plan = find_plan(&app_state, &plan_id)?;
+
+    let projects = accessible_projects(&api).await?;
+    if !projects.contains(&plan.source_ref) || !projects.contains(&plan.dest_ref) {
+        return Err(PreviewError::NotFound(format!(
+            "Plan {} not found",
+            plan_id
+        )));
+    }
+    Ok(Json(plan))
+}
+
+pub async fn approve_plan_handler(
+    State(app_state): State<AppState>,
+    Path(plan_id): Path<String>,
+    session: Session,
+) -> Result<impl IntoResponse, PreviewError> {
+    let api = ManagementApi::from_session(&session).await?;
+    let approver = current_identity(&session, &api).await?;
+
+    Ok(Json(
+        approve_plan(&app_state, &api, &plan_id, approver).await?,
+    ))
+}
+
+/// Applies an approved plan exactly as it was approved. The apply is recorded in the history
+/// with the plan's id.
+pub async fn apply_plan_handler(
+    State(app_state): State<AppState>,
+    Path(plan_id): Path<String>,
+    Query(query): Query<PlanApplyQuery>,
+    session: Session,
+) -> Result<Response, PreviewError> {
+    let api = ManagementApi::from_session(&session).await?;
+    let (plan, service_plans) = claim_plan(&app_state, &api, &plan_id).await?;
+
+    let origin = ApplyOrigin {
+        kind: HistoryKind::Apply,
+        source_ref: Some(plan.source_ref),
+        project_ref: plan.dest_ref,
+        approval: plan.approved_by.map(|approver| PlanApproval {
+            plan_id: plan.id,
+            approved_by: approver.name,
+        }),
+    };
+    let options = ApplyOptions {
+        dry_run: None,
+        background: query.background,
+    };
+
+    let response = plans_response(&app_state, &session, api, origin, service_plans, &options).await;
+    if response.is_err() {
+        release_plan(&app_state, &plan_id);
+    }
+    response
+}
@@ -0,0 +1,106 @@
+use crate::error::PreviewError;
+use crate::handlers::migrate::job_handler::plans_response;
+use crate::models::AppState;
+use crate::models::history::{ApplyOrigin, HistoryEntry, HistoryKind};
+use crate::models::job::JobStatus;
+use crate::models::migrate::{ApplyOptions, ProjectConfig};
+use crate::models::provider::{ProviderApplyRequest, ProviderPreview, ProviderQuery};
+use crate::services::Service;
+use crate::services::history::record_entry;
+use crate::services::management_api::ManagementApi;
+use crate::services::now_rfc3339;
+use crate::services::profile::current_actor;
+use crate::services::providers::{fetch_auth_configs, plan_providers, provider_diffs};
+
+use axum::{
+    extract::{Query, State},
+    response::{IntoResponse, Json, Response},
+};
+use tower_sessions::Session;
+use uuid::Uuid;
+
+/// Auth differences between two projects grouped by third-party OAuth provider, showing for
+/// each whether it is being enabled and whether a destination secret is needed to apply it.
+pub async fn provider_preview_handler(
+    State(app_state): State<AppState>,
+    Query(params): Query<ProviderQuery>,
+    session: Session,
+) -> Result<impl IntoResponse, PreviewError> {
+    let api = ManagementApi::from_session(&session).await?;
+    let (source, dest) = fetch_auth_configs(
+        &api,
+        &params.source_id,
+        &params.dest_id,
+        &app_state.config.transforms,
+    )
+    .await?;
+    let mut providers = provider_diffs(&source, &dest)?;
+
+    let diffs: Vec<_> = providers
+        .iter()
+        .flat_map(|provider| provider.diffs.iter().cloned())
+        .collect();
+    record_entry(
+        &app_state.history,
+        &HistoryEntry {
+            id: Uuid::new_v4().to_string(),
+            kind: HistoryKind::Preview,
+            actor: current_actor(&session, &api).await,
+            approved_by: None,
+            plan_id: None,
+            created_at: now_rfc3339(),
+            source_ref: Some(params.source_id.clone()),
+            dest_ref: params.dest_id.clone(),
+            services: vec![Service::Auth.name().to_string()],
+            status: JobStatus::Succeeded,
+            diffs: if diffs.is_empty() {
+                Vec::new()
+            } else {
+                vec![ProjectConfig {
+                    name: Service::Auth.name().to_string(),
+                    diffs,
+                }]
+            },
+            outcome: None,
+        },
+    );
+
+    app_state.redactor.redact_providers(&mut providers);
+    Ok(Json(ProviderPreview {
+        source_ref: params.source_id,
+        dest_ref: params.dest_id,
+        providers,
+    }))
+}
+
+/// Migrates the settings of the requested providers. Providers being enabled, moving to a
+/// different client id or without a secret on the destination need a client secret for the
+/// destination in the request body, since the source's secret is never copied.
+pub async fn apply_providers_handler(
+    State(app_state): State<AppState>,
+    Query(params): Query<ProviderQuery>,
+    session: Session,
+    Json(request): Json<ProviderApplyRequest>,
+) -> Result<Response, PreviewError> {
+    let api = ManagementApi::from_session(&session).await?;
+    let (source, dest) = fetch_auth_configs(
+        &api,
+        &params.source_id,
+        &params.dest_id,
+        &app_state.config.transforms,
+    )
+    .await?;
+    let plan = plan_providers(&source, &dest, &request, &params.source_id, &params.dest_id)?;
+
+    let options = ApplyOptions {
+        dry_run: params.dry_run,
+        background: params.background,
+    };
+    let origin = ApplyOrigin {
+        kind: HistoryKind::Apply,
+        source_ref: Some(params.source_id),
+        project_ref: params.dest_id,
+        approval: None,
+    };
+    plans_response(&app_state, &session, api, origin, vec![plan], &options).await
+}
@@ -0,0 +1,133 @@
+use crate::error::PreviewError;
+use crate::handlers::migrate::preview_handler::report_response;
+use crate::models::AppState;
+use crate::models::history::{HistoryEntry, HistoryKind};
+use crate::models::job::JobStatus;
+use crate::models::migrate::PreviewResponse;
+use crate::services::history::record_entry;
+use crate::services::management_api::ManagementApi;
+use crate::services::policy::evaluate;
+use crate::services::profile::current_actor;
+use crate::services::report::ReportFormat;
+use crate::services::snapshot::{build_snapshot, parse_snapshot};
+use crate::services::{PreviewSide, Service, now_rfc3339, preview_services};
+
+use axum::{
+    extract::{Query, State},
+    http::header::CONTENT_TYPE,
+    response::{IntoResponse, Json, Response},
+};
+use serde::Deserialize;
+use tower_sessions::Session;
+use uuid::Uuid;
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SnapshotSide {
+    #[default]
+    Source,
+    Dest,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SnapshotPreviewQuery {
+    pub project_id: String,
+    #[serde(default)]
+    pub snapshot_side: SnapshotSide,
+    pub format: Option<ReportFormat>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SnapshotQuery {
+    pub project_id: String,
+    pub format: Option<String>,
+}
+
+pub async fn snapshot_handler(
+    State(_app_state): State<AppState>,
+    Query(params): Query<SnapshotQuery>,
+    session: Session,
+) -> Result<Response, PreviewError> {
+    let format = params.format.as_deref().unwrap_or("json");
+    if !matches!(format, "json" | "yaml" | "yml") {
+        return Err(PreviewError::BadRequest(format!(
+            "Unsupported snapshot format: {}",
+            format
+        )));
+    }
+
+    let api = ManagementApi::from_session(&session).await?;
+    let snapshot = build_snapshot(&api, &params.project_id).await?;
+
+    if format == "json" {
+        return Ok(Json(snapshot).into_response());
+    }
+
+    let body = serde_yaml::to_string(&snapshot)
+        .map_err(|e| PreviewError::ApiError(format!("Failed to serialize snapshot: {:?}", e)))?;
+    Ok(([(CONTENT_TYPE, "application/yaml")], body).into_response())
+}
+
+/// Compares a live project against an uploaded snapshot, using the snapshot as the source
+/// or destination side depending on `snapshot_side`.
+pub async fn snapshot_preview_handler(
+    State(app_state): State<AppState>,
+    Query(params): Query<SnapshotPreviewQuery>,
+    session: Session,
+    body: String,
+) -> Result<impl IntoResponse, PreviewError> {
+    let snapshot = parse_snapshot(&body)?;
+    let api = ManagementApi::from_session(&session).await?;
+    let services: Vec<Service> = snapshot.services.keys().copied().collect();
+    let (source, dest) = match params.snapshot_side {
+        SnapshotSide::Source => (
+            PreviewSide::Snapshot(&snapshot),
+            PreviewSide::Project(&params.project_id),
+        ),
+        SnapshotSide::Dest => (
+            PreviewSide::Project(&params.project_id),
+            PreviewSide::Snapshot(&snapshot),
+        ),
+    };
+    let configs =
+        preview_services(&api, &services, source, dest, &app_state.config.transforms).await?;
+
+    // The snapshot side is recorded as `snapshot:<ref>` so it is not mistaken for a live project
+    let snapshot_ref = format!("snapshot:{}", snapshot.project_ref);
+    let (source_ref, dest_ref) = match params.snapshot_side {
+        SnapshotSide::Source => (snapshot_ref, params.project_id),
+        SnapshotSide::Dest => (params.project_id, snapshot_ref),
+    };
+    let policy = evaluate(
+        &app_state.config.policy,
+        &configs,
+        &dest_ref,
+        &app_state.redactor,
+    );
+    record_entry(
+        &app_state.history,
+        &HistoryEntry {
+            id: Uuid::new_v4().to_string(),
+            kind: HistoryKind::Preview,
+            actor: current_actor(&session, &api).await,
+            approved_by: None,
+            plan_id: None,
+            created_at: now_rfc3339(),
+            source_ref: Some(source_ref),
+            dest_ref,
+            services: services
+                .iter()
+                .map(|service| service.name().to_string())
+                .collect(),
+            status: JobStatus::Succeeded,
+            diffs: configs.clone(),
+            outcome: None,
+        },
+    );
+
+    Ok(report_response(
+        &app_state,
+        PreviewResponse { configs, policy },
+        params.format.unwrap_or_default(),
+    ))
+}
@@ -1,5 +1,6 @@
 use crate::models::AppState;
 use crate::models::oauth::{CallbackParams, OAuthSessionData, TokenResponse};
+use crate::services::profile::clear_session_profile;
 use axum::{
     extract::{Query, State},
     response::{IntoResponse, Redirect},
@@ -166,12 +167,11 @@ pub async fn callback_handler(
         )
         .into_response();
     }
+    // A new token may belong to a different user
+    clear_session_profile(&session).await;
 
-    if let Some(refresh_token) = token_data.refresh_token {
-        eprintln!(
-            "Refresh Token received (store securely if needed for long-term use): {}",
-            refresh_token
-        );
+    if token_data.refresh_token.is_some() {
+        eprintln!("Refresh Token received (store securely if needed for long-term use)");
         // Optionally store refresh token in session as well
         // session.insert("supabase_refresh_token", refresh_token).await.ok();
     }
@@ -0,0 +1,6 @@
+//! Diff, snapshot and apply engine shared by the HTTP server and the `supabase-migrate` CLI.
+
+pub mod error;
+pub mod handlers;
+pub mod models;
+pub mod services;
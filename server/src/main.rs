@@ -1,28 +1,50 @@
-mod handlers;
-mod models;
+use supabase_migrate::{handlers, models, services};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Changed Box to Box<dyn std::error::Error> for better error handling
-    use crate::handlers::auth::{signout_handler, status_handler};
     use axum::{
         Router,
         routing::{get, post},
     };
-    use handlers::migrate::preview_handler;
+    use handlers::auth::{signout_handler, status_handler};
+    use handlers::migrate::{
+        apply_config_handler, apply_fan_out_handler, apply_merge_handler, apply_plan_handler,
+        apply_providers_handler, apply_service_handler, approve_plan_handler, cancel_job_handler,
+        check_drift_handler, compare_history_handler, create_plan_handler, fan_out_preview_handler,
+        get_backup_handler, get_drift_handler, get_history_handler, get_job_handler,
+        get_plan_handler, history_report_handler, job_events_handler, list_backups_handler,
+        list_drift_handler, list_history_handler, list_jobs_handler, list_plans_handler,
+        merge_preview_handler, preview_handler, provider_preview_handler, rollback_handler,
+        snapshot_handler, snapshot_preview_handler,
+    };
     use handlers::oauth::{callback_handler, login_handler};
     use handlers::test_handler;
     use models::{AppConfig, AppState};
     use reqwest::Method;
     use reqwest::header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE};
+    use services::drift::{initial_statuses, spawn_drift_scheduler};
+    use services::history::HistoryStore;
+    use services::redact::Redactor;
+    use std::sync::{Arc, Mutex};
     use time::Duration;
     use tower_http::cors::CorsLayer; // Any for methods/headers is fine
     use tower_sessions::{Expiry, MemoryStore, SessionManagerLayer};
 
     let app_config = AppConfig::from_env()?;
+    let history = HistoryStore::open(&app_config.history_path)?;
+    let redactor = Redactor::new(
+        app_config.redact_patterns.clone(),
+        history.redaction_salt()?,
+    );
     let app_state = AppState {
         config: app_config.clone(),
+        jobs: Default::default(),
+        history,
+        drift: Arc::new(Mutex::new(initial_statuses(&app_config.drift))),
+        redactor,
     };
+    spawn_drift_scheduler(app_state.clone());
     let server_addr = app_state.config.server_addr.to_owned();
 
     let session_store = MemoryStore::default();
@@ -49,6 +71,34 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let app = Router::new()
         .route("/", get(test_handler))
         .route("/preview", get(preview_handler))
+        .route("/preview/snapshot", post(snapshot_preview_handler))
+        .route("/preview/merge", post(merge_preview_handler))
+        .route("/preview/fan-out", post(fan_out_preview_handler))
+        .route("/preview/providers", get(provider_preview_handler))
+        .route("/snapshot", get(snapshot_handler))
+        .route("/apply/config", post(apply_config_handler))
+        .route("/apply/merge", post(apply_merge_handler))
+        .route("/apply/fan-out", post(apply_fan_out_handler))
+        .route("/apply/providers", post(apply_providers_handler))
+        .route("/apply/{service}", post(apply_service_handler))
+        .route("/backups", get(list_backups_handler))
+        .route("/backups/{backup_id}", get(get_backup_handler))
+        .route("/rollback/{backup_id}", post(rollback_handler))
+        .route("/drift", get(list_drift_handler))
+        .route("/drift/{pair}", get(get_drift_handler))
+        .route("/drift/{pair}/check", post(check_drift_handler))
+        .route("/plans", get(list_plans_handler).post(create_plan_handler))
+        .route("/plans/{plan_id}", get(get_plan_handler))
+        .route("/plans/{plan_id}/approve", post(approve_plan_handler))
+        .route("/plans/{plan_id}/apply", post(apply_plan_handler))
+        .route("/history", get(list_history_handler))
+        .route("/history/compare", get(compare_history_handler))
+        .route("/history/{history_id}", get(get_history_handler))
+        .route("/history/{history_id}/report", get(history_report_handler))
+        .route("/jobs", get(list_jobs_handler))
+        .route("/jobs/{job_id}", get(get_job_handler))
+        .route("/jobs/{job_id}/events", get(job_events_handler))
+        .route("/jobs/{job_id}/cancel", post(cancel_job_handler))
         .route("/auth", get(status_handler))
         .route("/signout", post(signout_handler))
         .route("/connect-supabase/login", get(login_handler))
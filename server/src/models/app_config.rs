@@ -1,3 +1,19 @@
+use crate::models::drift::{DriftConfig, DriftStatus};
+use crate::models::job::JobEntry;
+use crate::models::notification::NotificationSink;
+use crate::models::policy::PolicyConfig;
+use crate::models::transform::TransformConfig;
+use crate::services::diff::DEFAULT_TEXT_DIFF_THRESHOLD;
+use crate::services::drift::load_drift_config;
+use crate::services::history::HistoryStore;
+use crate::services::notify::parse_sinks;
+use crate::services::policy::load_policy_config;
+use crate::services::redact::{Redactor, parse_redact_patterns};
+use crate::services::transform::load_transform_config;
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
 #[derive(Clone)]
 pub struct AppConfig {
     pub client_id: String,
@@ -5,6 +21,26 @@ pub struct AppConfig {
     pub redirect_url: String,
     pub client_addr: String,
     pub server_addr: String,
+    pub history_path: String,
+    /// Personal access token for background work without a user session, e.g. drift checks.
+    pub access_token: Option<String>,
+    /// Emails of users who may act on other users' work, such as cancelling their jobs, from
+    /// `ADMIN_EMAILS` (comma separated).
+    pub admins: Vec<String>,
+    pub drift: DriftConfig,
+    /// Webhooks told about finished migrations and drift, from `WEBHOOK_URLS` (JSON payloads)
+    /// and `SLACK_WEBHOOK_URLS` (Slack incoming webhooks), both comma separated.
+    pub notifications: Vec<NotificationSink>,
+    /// Rules checked on previews and enforced on applies, from `POLICY_CONFIG_PATH`.
+    pub policy: PolicyConfig,
+    /// Per project pair rewrites of environment specific values, from `TRANSFORM_CONFIG_PATH`.
+    pub transforms: TransformConfig,
+    /// Changed strings at least this long get a line-level diff in previews, from
+    /// `TEXT_DIFF_THRESHOLD`.
+    pub text_diff_threshold: usize,
+    /// Key patterns masked in previews on top of the known credentials, from
+    /// `REDACT_KEY_PATTERNS` (comma separated).
+    pub redact_patterns: Vec<String>,
 }
 
 impl AppConfig {
@@ -24,12 +60,57 @@ impl AppConfig {
             env::var("CLIENT_ADDR").map_err(|e| format!("CLIENT_ADDR not found: {}", e))?;
         let server_addr =
             env::var("SERVER_ADDR").map_err(|e| format!("SERVER_ADDR not found: {}", e))?;
+        let history_path = env::var("HISTORY_DB_PATH").unwrap_or_else(|_| "history.db".to_string());
+        let access_token = env::var("SUPABASE_ACCESS_TOKEN").ok();
+        let admins = env::var("ADMIN_EMAILS")
+            .map(|emails| {
+                emails
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|email| !email.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+        let drift = match env::var("DRIFT_CONFIG_PATH") {
+            Ok(path) => load_drift_config(&path)?,
+            Err(_) => DriftConfig::default(),
+        };
+        let notifications = parse_sinks(
+            env::var("WEBHOOK_URLS").ok().as_deref(),
+            env::var("SLACK_WEBHOOK_URLS").ok().as_deref(),
+        );
+        let policy = match env::var("POLICY_CONFIG_PATH") {
+            Ok(path) => load_policy_config(&path)?,
+            Err(_) => PolicyConfig::default(),
+        };
+        let transforms = match env::var("TRANSFORM_CONFIG_PATH") {
+            Ok(path) => load_transform_config(&path)?,
+            Err(_) => TransformConfig::default(),
+        };
+        let text_diff_threshold = match env::var("TEXT_DIFF_THRESHOLD") {
+            Ok(value) => value
+                .parse()
+                .map_err(|e| format!("Invalid TEXT_DIFF_THRESHOLD {}: {}", value, e))?,
+            Err(_) => DEFAULT_TEXT_DIFF_THRESHOLD,
+        };
+        let redact_patterns =
+            parse_redact_patterns(env::var("REDACT_KEY_PATTERNS").ok().as_deref());
         Ok(Self {
             client_id,
             client_secret,
             redirect_url,
             client_addr,
             server_addr,
+            history_path,
+            access_token,
+            admins,
+            drift,
+            notifications,
+            policy,
+            transforms,
+            text_diff_threshold,
+            redact_patterns,
         })
     }
 }
@@ -37,4 +118,12 @@ impl AppConfig {
 #[derive(Clone)]
 pub struct AppState {
     pub config: AppConfig,
+    pub jobs: Arc<Mutex<HashMap<String, JobEntry>>>,
+    /// History, backups, migration plans and other records that outlive the process.
+    pub history: HistoryStore,
+    /// Latest drift status per configured pair, keyed by pair name.
+    pub drift: Arc<Mutex<HashMap<String, DriftStatus>>>,
+    /// Masks credentials in everything returned to users, salted with a value kept in the
+    /// history database.
+    pub redactor: Redactor,
 }
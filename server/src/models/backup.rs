@@ -0,0 +1,30 @@
+use crate::services::Service;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// The destination values captured right before an apply, used to roll it back.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Backup {
+    pub id: String,
+    pub project_ref: String,
+    pub created_at: String,
+    pub services: Vec<ServiceBackup>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ServiceBackup {
+    pub service: Service,
+    /// Keys the apply was going to write.
+    pub keys: Vec<String>,
+    /// The destination config before the apply, limited to `keys` for Management API services.
+    pub value: Value,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BackupSummary {
+    pub id: String,
+    pub project_ref: String,
+    pub created_at: String,
+    pub services: Vec<Service>,
+}
@@ -0,0 +1,93 @@
+use crate::models::history::PreviewChange;
+use crate::models::migrate::ProjectConfig;
+use crate::services::Service;
+
+use serde::{Deserialize, Serialize};
+
+fn default_interval_minutes() -> u64 {
+    60
+}
+
+fn default_services() -> Vec<Service> {
+    Service::ALL.to_vec()
+}
+
+/// Project pairs the server watches for configuration drift, loaded from `DRIFT_CONFIG_PATH`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DriftConfig {
+    #[serde(default = "default_interval_minutes")]
+    pub interval_minutes: u64,
+    #[serde(default)]
+    pub pairs: Vec<DriftPair>,
+}
+
+impl Default for DriftConfig {
+    fn default() -> Self {
+        Self {
+            interval_minutes: default_interval_minutes(),
+            pairs: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DriftPair {
+    /// Unique name used in the drift endpoints, e.g. `staging-to-prod`.
+    pub name: String,
+    pub source: String,
+    pub dest: String,
+    #[serde(default = "default_services")]
+    pub services: Vec<Service>,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DriftState {
+    NotChecked,
+    InSync,
+    Drifted,
+    Error,
+}
+
+/// How the drift of a pair moved since the previous check.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DriftChange {
+    Unchanged,
+    Appeared,
+    Disappeared,
+    Changed,
+}
+
+/// The latest drift check of one pair.
+#[derive(Debug, Serialize, Clone)]
+pub struct DriftStatus {
+    pub pair: DriftPair,
+    pub state: DriftState,
+    pub change: DriftChange,
+    pub checked_at: Option<String>,
+    /// When the drift last appeared, disappeared or changed.
+    pub last_change_at: Option<String>,
+    pub diff_count: usize,
+    /// The current differences, kept from the last successful check when a check fails.
+    pub configs: Vec<ProjectConfig>,
+    /// Keys that changed since the previous check.
+    pub changes: Vec<PreviewChange>,
+    pub error: Option<String>,
+}
+
+impl DriftStatus {
+    pub fn not_checked(pair: DriftPair) -> Self {
+        Self {
+            pair,
+            state: DriftState::NotChecked,
+            change: DriftChange::Unchanged,
+            checked_at: None,
+            last_change_at: None,
+            diff_count: 0,
+            configs: Vec::new(),
+            changes: Vec::new(),
+            error: None,
+        }
+    }
+}
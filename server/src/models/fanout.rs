@@ -0,0 +1,76 @@
+use crate::models::migrate::ApplyOutcome;
+use crate::models::policy::PolicyHit;
+use crate::services::Service;
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+fn default_services() -> Vec<Service> {
+    Service::ALL.to_vec()
+}
+
+/// One source copied to several destinations, e.g. a template project to per-customer ones.
+#[derive(Debug, Deserialize)]
+pub struct FanOutRequest {
+    pub source_id: String,
+    pub dest_ids: Vec<String>,
+    #[serde(default = "default_services")]
+    pub services: Vec<Service>,
+    /// Key patterns to apply, checked against each destination's own diff.
+    #[serde(default)]
+    pub keys: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FanOutApplyQuery {
+    pub dry_run: Option<bool>,
+    pub background: Option<bool>,
+}
+
+/// How one destination compares with the source. Destinations that could not be read have
+/// an `error` and no cells in the matrix.
+#[derive(Debug, Serialize, Clone)]
+pub struct FanOutDestination {
+    pub dest_ref: String,
+    pub differences: usize,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub policy: Vec<PolicyHit>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct FanOutCell {
+    pub source_value: String,
+    pub dest_value: String,
+}
+
+/// A key that differs on at least one destination. Destinations missing from `dests` match
+/// the source for this key.
+#[derive(Debug, Serialize, Clone)]
+pub struct FanOutRow {
+    pub service: String,
+    pub key: String,
+    pub dests: BTreeMap<String, FanOutCell>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FanOutPreview {
+    pub source_ref: String,
+    pub destinations: Vec<FanOutDestination>,
+    pub matrix: Vec<FanOutRow>,
+}
+
+/// The apply for one destination. Failing destinations do not stop the others.
+#[derive(Debug, Serialize)]
+pub struct FanOutResult {
+    pub dest_ref: String,
+    #[serde(flatten)]
+    pub outcome: Option<ApplyOutcome>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FanOutApplyResponse {
+    pub source_ref: String,
+    pub results: Vec<FanOutResult>,
+}
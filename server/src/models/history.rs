@@ -0,0 +1,113 @@
+use crate::models::job::JobStatus;
+use crate::models::migrate::{DiffEntry, ProjectConfig};
+use crate::services::report::ReportFormat;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HistoryKind {
+    Preview,
+    DryRun,
+    Apply,
+    Rollback,
+}
+
+/// One recorded preview or apply in the audit log.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HistoryEntry {
+    pub id: String,
+    pub kind: HistoryKind,
+    /// Email of the user who made the request, when their profile could be read.
+    pub actor: Option<String>,
+    /// Who approved the plan an apply came from.
+    pub approved_by: Option<String>,
+    /// The migration plan an apply came from.
+    pub plan_id: Option<String>,
+    pub created_at: String,
+    pub source_ref: Option<String>,
+    pub dest_ref: String,
+    pub services: Vec<String>,
+    pub status: JobStatus,
+    /// The differences found by a preview, or resolved by an apply.
+    pub diffs: Vec<ProjectConfig>,
+    /// The apply report, or the planned operations for a dry run.
+    pub outcome: Option<Value>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HistorySummary {
+    pub id: String,
+    pub kind: HistoryKind,
+    pub actor: Option<String>,
+    pub created_at: String,
+    pub source_ref: Option<String>,
+    pub dest_ref: String,
+    pub services: Vec<String>,
+    pub status: JobStatus,
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct HistoryQuery {
+    pub kind: Option<HistoryKind>,
+    /// Matches entries with this project as source or destination.
+    pub project_ref: Option<String>,
+    pub actor: Option<String>,
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HistoryCompareQuery {
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HistoryReportQuery {
+    /// `markdown` (default), `html` or `json`.
+    pub format: Option<ReportFormat>,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PreviewChangeKind {
+    /// The key differs now but did not in the earlier preview.
+    Appeared,
+    /// The key differed in the earlier preview but no longer does.
+    Resolved,
+    /// The key differs in both previews, with different values.
+    Changed,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct PreviewChange {
+    pub service: String,
+    pub key: String,
+    pub change: PreviewChangeKind,
+    pub before: Option<DiffEntry>,
+    pub after: Option<DiffEntry>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PreviewComparison {
+    pub from: String,
+    pub to: String,
+    pub changes: Vec<PreviewChange>,
+}
+
+/// What an apply request is acting on, recorded with its history entry.
+#[derive(Debug, Clone)]
+pub struct ApplyOrigin {
+    pub kind: HistoryKind,
+    pub source_ref: Option<String>,
+    pub project_ref: String,
+    /// Set when the apply is of an approved migration plan.
+    pub approval: Option<PlanApproval>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PlanApproval {
+    pub plan_id: String,
+    pub approved_by: String,
+}
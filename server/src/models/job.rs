@@ -0,0 +1,83 @@
+use crate::models::migrate::{ApplyReport, ApplyResult};
+
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use tokio::sync::broadcast;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+impl JobStatus {
+    pub fn is_finished(self) -> bool {
+        matches!(
+            self,
+            JobStatus::Succeeded | JobStatus::Failed | JobStatus::Cancelled
+        )
+    }
+}
+
+/// One operation of a job's plan.
+#[derive(Debug, Serialize, Clone)]
+pub struct JobStep {
+    pub index: usize,
+    pub service: String,
+    pub keys: Vec<String>,
+    pub status: JobStatus,
+    pub results: Vec<ApplyResult>,
+}
+
+/// An apply running in the background. Steps are executed in order; `summary` is filled in
+/// once the job finishes.
+#[derive(Debug, Serialize, Clone)]
+pub struct Job {
+    pub id: String,
+    pub project_ref: String,
+    /// Who started the job. Only they or an admin may cancel it.
+    pub actor: Option<String>,
+    pub status: JobStatus,
+    pub created_at: String,
+    pub finished_at: Option<String>,
+    pub backup_id: Option<String>,
+    pub steps: Vec<JobStep>,
+    pub logs: Vec<String>,
+    pub summary: Option<ApplyReport>,
+}
+
+/// Progress updates streamed to clients following a job.
+#[derive(Debug, Serialize, Clone)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum JobEvent {
+    Step(JobStep),
+    Log {
+        message: String,
+    },
+    Finished {
+        status: JobStatus,
+        summary: Option<ApplyReport>,
+    },
+}
+
+impl JobEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            JobEvent::Step(_) => "step",
+            JobEvent::Log { .. } => "log",
+            JobEvent::Finished { .. } => "finished",
+        }
+    }
+}
+
+/// A job together with the channel its events are published on and its cancel flag.
+pub struct JobEntry {
+    pub job: Job,
+    pub events: broadcast::Sender<JobEvent>,
+    pub cancelled: Arc<AtomicBool>,
+}
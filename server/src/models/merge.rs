@@ -0,0 +1,48 @@
+use crate::models::migrate::PlannedService;
+use crate::models::policy::PolicyHit;
+
+use serde::{Deserialize, Serialize};
+
+/// Which side changed a key since the baseline both projects were last in sync at.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MergeChange {
+    /// Only the source changed the key, so the change is proposed for the destination.
+    ChangedInSource,
+    /// Only the destination changed the key, so it is left alone.
+    ChangedInDest,
+    /// Both sides changed the key to different values. The destination is left alone until
+    /// the conflict is resolved by hand.
+    Conflict,
+    /// Both sides made the same change.
+    Unchanged,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MergeEntry {
+    pub key: String,
+    pub change: MergeChange,
+    pub baseline_value: String,
+    pub source_value: String,
+    pub dest_value: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ServiceMerge {
+    pub name: String,
+    pub entries: Vec<MergeEntry>,
+}
+
+/// Result of a three-way comparison. Keys that match the baseline on both sides are left out.
+#[derive(Debug, Serialize)]
+pub struct MergeResponse {
+    pub source_ref: String,
+    pub dest_ref: String,
+    pub configs: Vec<ServiceMerge>,
+    pub conflicts: usize,
+    /// The writes that bring the source side changes to the destination.
+    pub operations: Vec<PlannedService>,
+    /// Policy rules the proposed changes hit, most severe first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub policy: Vec<PolicyHit>,
+}
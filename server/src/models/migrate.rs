@@ -1,4 +1,8 @@
+use crate::models::job::Job;
+use crate::models::policy::PolicyHit;
+
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ProjectConfig {
@@ -11,4 +15,135 @@ pub struct DiffEntry {
     pub key: String,
     pub source_value: String,
     pub dest_value: String,
-}
\ No newline at end of file
+    /// Line-level unified diff from the destination to the source value, for strings long
+    /// enough that the whole values are hard to compare by eye.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text_diff: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PreviewResponse {
+    pub configs: Vec<ProjectConfig>,
+    /// Policy rules the differences hit, most severe first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub policy: Vec<PolicyHit>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ApplyRequest {
+    pub source_id: String,
+    pub dest_id: String,
+    /// Key patterns to apply, such as `roles.*` or `!roles.postgres`. Without patterns every
+    /// difference is applied.
+    #[serde(default)]
+    pub keys: Vec<String>,
+    /// Hostnames rewritten in trigger definitions on top of the project hostnames. Only
+    /// accepted when applying triggers.
+    #[serde(default)]
+    pub host_rewrites: Vec<HostRewrite>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ApplyStep {
+    pub key: String,
+    pub statement: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ApplyResult {
+    pub key: String,
+    pub statement: String,
+    pub success: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ApplyResponse {
+    pub service: String,
+    pub results: Vec<ApplyResult>,
+}
+
+/// A Management API write covering one or more config keys.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ApiWrite {
+    pub keys: Vec<String>,
+    pub method: String,
+    pub path: String,
+    pub body: Value,
+}
+
+/// One unit of work in a reconciliation plan, executed in order against the destination.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Operation {
+    Sql(ApplyStep),
+    Api(ApiWrite),
+    Skip { key: String, reason: String },
+}
+
+impl Operation {
+    /// Keys this operation writes on the destination. Skipped operations write nothing.
+    pub fn written_keys(&self) -> Vec<&str> {
+        match self {
+            Operation::Sql(step) => vec![step.key.as_str()],
+            Operation::Api(write) => write.keys.iter().map(String::as_str).collect(),
+            Operation::Skip { .. } => Vec::new(),
+        }
+    }
+}
+
+/// How an apply endpoint runs its plan: previewed only, in the background, or inline.
+#[derive(Debug, Deserialize, Default)]
+pub struct ApplyOptions {
+    pub dry_run: Option<bool>,
+    pub background: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PlannedService {
+    pub service: String,
+    pub operations: Vec<Operation>,
+}
+
+/// The writes an apply would perform, in execution order, without any of them being made.
+#[derive(Debug, Serialize)]
+pub struct DryRunResponse {
+    pub project_ref: String,
+    pub dry_run: bool,
+    pub services: Vec<PlannedService>,
+    /// Policy rules the planned changes hit. Blocking hits would refuse the real apply.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub policy: Vec<PolicyHit>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ApplyReport {
+    pub project_ref: String,
+    /// Backup of the values overwritten by this apply, if anything was written.
+    pub backup_id: Option<String>,
+    pub services: Vec<ApplyResponse>,
+}
+
+/// What an apply request did, depending on its options.
+#[derive(Debug, Serialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum ApplyOutcome {
+    DryRun(DryRunResponse),
+    /// The apply was queued as a background job.
+    Queued(Job),
+    Applied(ApplyReport),
+}
+
+impl ApplyReport {
+    pub fn succeeded(&self) -> bool {
+        self.services
+            .iter()
+            .all(|service| service.results.iter().all(|result| result.success))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HostRewrite {
+    pub from: String,
+    pub to: String,
+}
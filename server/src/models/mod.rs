@@ -1,5 +1,17 @@
 pub mod app_config;
+pub mod backup;
+pub mod drift;
+pub mod fanout;
+pub mod history;
+pub mod job;
+pub mod merge;
 pub mod migrate;
+pub mod notification;
 pub mod oauth;
+pub mod plan;
+pub mod policy;
+pub mod provider;
+pub mod snapshot;
+pub mod transform;
 
 pub use app_config::{AppConfig, AppState};
@@ -0,0 +1,43 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SinkKind {
+    /// Receives the notification as JSON.
+    Json,
+    /// Slack-compatible incoming webhook.
+    Slack,
+}
+
+/// An outgoing webhook notifications are posted to.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct NotificationSink {
+    pub kind: SinkKind,
+    pub url: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationEvent {
+    MigrationSucceeded,
+    MigrationFailed,
+    MigrationCancelled,
+    DriftDetected,
+    DriftChanged,
+    DriftResolved,
+}
+
+/// The payload posted to generic JSON sinks. Slack sinks get the same content as a message.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Notification {
+    pub event: NotificationEvent,
+    pub title: String,
+    pub source_ref: Option<String>,
+    pub dest_ref: String,
+    pub services: Vec<String>,
+    /// One line summary, e.g. "3 differences across 2 services".
+    pub summary: String,
+    /// Short per-key lines, capped so messages stay readable.
+    pub details: Vec<String>,
+    pub created_at: String,
+}
@@ -17,3 +17,14 @@ pub struct TokenResponse {
     pub access_token: String,
     pub refresh_token: Option<String>,
 }
+
+/// The signed in user, as returned by the Management API `/profile` endpoint.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct Profile {
+    #[serde(default)]
+    pub gotrue_id: String,
+    #[serde(default)]
+    pub primary_email: String,
+    #[serde(default)]
+    pub username: Option<String>,
+}
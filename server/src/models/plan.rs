@@ -0,0 +1,78 @@
+use crate::models::migrate::{PlannedService, ProjectConfig};
+use crate::models::policy::PolicyHit;
+use crate::services::Service;
+use crate::services::reconcile::ServicePlan;
+
+use serde::{Deserialize, Serialize};
+
+fn default_services() -> Vec<Service> {
+    Service::ALL.to_vec()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PlanRequest {
+    pub source_id: String,
+    pub dest_id: String,
+    #[serde(default = "default_services")]
+    pub services: Vec<Service>,
+    /// Key patterns to apply, e.g. `EXTERNAL_GOOGLE_*` or `!SMTP_*`. Empty applies every key.
+    #[serde(default)]
+    pub keys: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PlanApplyQuery {
+    pub background: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PlanStatus {
+    PendingApproval,
+    Approved,
+    Applied,
+    /// The destination changed after the plan was made, so it no longer describes the apply.
+    Invalidated,
+}
+
+/// A signed in Supabase user, identified by their account id rather than their email.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct PlanActor {
+    pub id: String,
+    pub name: String,
+}
+
+/// A reviewed migration from one project to another. It is created from a preview, must be
+/// approved by a second user and is applied exactly as it was approved.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MigrationPlan {
+    pub id: String,
+    pub source_ref: String,
+    pub dest_ref: String,
+    pub services: Vec<Service>,
+    /// The key selection the plan was narrowed to, empty when it applies every key.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub keys: Vec<String>,
+    pub status: PlanStatus,
+    pub created_at: String,
+    pub created_by: PlanActor,
+    pub approved_at: Option<String>,
+    pub approved_by: Option<PlanActor>,
+    pub applied_at: Option<String>,
+    /// Hash of the live destination the plan was made against, checked again on approval
+    /// and apply.
+    pub dest_fingerprint: String,
+    pub invalidated_reason: Option<String>,
+    /// The differences the plan resolves, with sensitive values masked.
+    pub configs: Vec<ProjectConfig>,
+    /// The writes the apply will make, with sensitive values redacted.
+    pub operations: Vec<PlannedService>,
+    pub policy: Vec<PolicyHit>,
+}
+
+/// A stored plan with the unredacted operations it applies.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PlanEntry {
+    pub plan: MigrationPlan,
+    pub service_plans: Vec<ServicePlan>,
+}
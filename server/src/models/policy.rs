@@ -0,0 +1,67 @@
+use crate::services::Service;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Rules checked against every preview and apply, loaded from `POLICY_CONFIG_PATH`.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct PolicyConfig {
+    #[serde(default)]
+    pub rules: Vec<PolicyRule>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PolicyRule {
+    pub name: String,
+    /// Service the rule applies to. Rules without one apply to every service.
+    pub service: Option<Service>,
+    /// Diff key pattern, where `*` matches any run of characters, e.g. `*.verify_jwt`.
+    pub key: String,
+    /// Destination project refs the rule applies to. Empty applies to every destination.
+    #[serde(default)]
+    pub projects: Vec<String>,
+    #[serde(default)]
+    pub when: PolicyCondition,
+    pub severity: Severity,
+    /// Shown with every hit instead of the generated description.
+    pub message: Option<String>,
+}
+
+/// What a matching diff must do for the rule to hit. Values are compared with what the
+/// destination would get, i.e. the source side of the diff.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(tag = "condition", content = "value", rename_all = "snake_case")]
+pub enum PolicyCondition {
+    /// Any change to the key.
+    #[default]
+    Changed,
+    /// The new value equals this value.
+    Equals(Value),
+    /// The new value is a smaller number than the current one.
+    Decreases,
+    /// The new value is a larger number than the current one.
+    Increases,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// Reported in previews; applies go ahead.
+    Warn,
+    /// Applies are refused until the change is approved.
+    RequireApproval,
+    /// Applies are refused.
+    Block,
+}
+
+/// A diff matched by a policy rule.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PolicyHit {
+    pub rule: String,
+    pub severity: Severity,
+    pub service: String,
+    pub key: String,
+    pub source_value: String,
+    pub dest_value: String,
+    pub message: String,
+}
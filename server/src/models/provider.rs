@@ -0,0 +1,62 @@
+use crate::models::migrate::DiffEntry;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Deserialize)]
+pub struct ProviderQuery {
+    pub source_id: String,
+    pub dest_id: String,
+    pub dry_run: Option<bool>,
+    pub background: Option<bool>,
+}
+
+/// Providers to migrate and the client secret to use for each on the destination.
+#[derive(Debug, Deserialize, Default)]
+pub struct ProviderApplyRequest {
+    /// Providers to migrate, e.g. `google`. Defaults to every provider that differs.
+    #[serde(default)]
+    pub providers: Vec<String>,
+    #[serde(default)]
+    pub secrets: BTreeMap<String, String>,
+}
+
+/// One side's settings for a third-party OAuth provider. The secret itself is never shown.
+#[derive(Debug, Serialize, Clone, Default, PartialEq)]
+pub struct ProviderSettings {
+    pub enabled: bool,
+    pub client_id: Option<String>,
+    pub secret_present: bool,
+    pub redirect_uri: Option<String>,
+    /// Other `external_<provider>_*` settings, keyed by the part after the provider name.
+    pub extra: BTreeMap<String, Value>,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ProviderAction {
+    /// Enabled on the source but not on the destination.
+    Enable,
+    Disable,
+    Update,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ProviderDiff {
+    pub provider: String,
+    pub action: ProviderAction,
+    pub source: ProviderSettings,
+    pub dest: ProviderSettings,
+    /// Whether applying needs a client secret for the destination, because the provider is
+    /// being enabled, its client id changes or the destination has no secret.
+    pub secret_required: bool,
+    pub diffs: Vec<DiffEntry>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProviderPreview {
+    pub source_ref: String,
+    pub dest_ref: String,
+    pub providers: Vec<ProviderDiff>,
+}
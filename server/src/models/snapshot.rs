@@ -0,0 +1,27 @@
+use crate::services::Service;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Version of the snapshot document layout, bumped on incompatible changes.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// A project's declared configuration. Metadata fields are optional so hand written
+/// config files containing only `services` can be applied as well.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ProjectSnapshot {
+    #[serde(default = "default_snapshot_version")]
+    pub snapshot_version: u32,
+    #[serde(default)]
+    pub project_ref: String,
+    #[serde(default)]
+    pub created_at: String,
+    #[serde(default)]
+    pub tool_version: String,
+    pub services: BTreeMap<Service, Value>,
+}
+
+fn default_snapshot_version() -> u32 {
+    SNAPSHOT_VERSION
+}
@@ -0,0 +1,76 @@
+use crate::services::Service;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Rewrites of environment specific settings, loaded from `TRANSFORM_CONFIG_PATH`.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct TransformConfig {
+    #[serde(default)]
+    pub pairs: Vec<TransformPair>,
+}
+
+impl TransformConfig {
+    /// The rules for copying from `source_ref` to `dest_ref`, empty when the pair has none.
+    pub fn rules_for(&self, source_ref: &str, dest_ref: &str) -> &[TransformRule] {
+        self.pairs
+            .iter()
+            .find(|pair| pair.source == source_ref && pair.dest == dest_ref)
+            .map(|pair| pair.rules.as_slice())
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TransformPair {
+    pub source: String,
+    pub dest: String,
+    pub rules: Vec<TransformRule>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TransformRule {
+    /// Service the rule applies to. Rules without one apply to every service.
+    pub service: Option<Service>,
+    /// Key pattern as shown in previews, where `*` matches any run of characters.
+    pub key: String,
+    pub transform: Transform,
+}
+
+/// How a source value is rewritten before it is compared with or written to the destination.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Transform {
+    /// Replaces every occurrence of `from` in string values.
+    Replace { from: String, to: String },
+    /// Replaces every match of `pattern` in string values. `replacement` may refer to
+    /// capture groups as `$1` or `${name}`.
+    Regex {
+        pattern: TransformPattern,
+        replacement: String,
+    },
+    /// Uses this value whatever the source has.
+    Fixed { value: Value },
+    /// Uses the destination's current value, so the key never shows as a difference.
+    KeepDestination,
+}
+
+/// A regular expression compiled when the config is loaded, so invalid patterns fail early.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct TransformPattern(pub Regex);
+
+impl TryFrom<String> for TransformPattern {
+    type Error = regex::Error;
+
+    fn try_from(pattern: String) -> Result<Self, Self::Error> {
+        Regex::new(&pattern).map(TransformPattern)
+    }
+}
+
+impl From<TransformPattern> for String {
+    fn from(pattern: TransformPattern) -> Self {
+        pattern.0.as_str().to_string()
+    }
+}
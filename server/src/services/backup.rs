@@ -0,0 +1,283 @@
+use crate::models::backup::{Backup, ServiceBackup};
+use crate::models::migrate::{ApplyStep, Operation};
+use crate::services::database::{quote_ident, quote_qualified_name};
+use crate::services::reconcile::{ServicePlan, plan_service};
+use crate::services::{Service, now_rfc3339};
+
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+/// Captures the destination values the plans are about to overwrite. Returns `None` when
+/// the plans write nothing.
+pub fn create_backup(project_id: &str, plans: &[ServicePlan]) -> Option<Backup> {
+    let services: Vec<ServiceBackup> = plans
+        .iter()
+        .filter_map(|plan| {
+            let keys: Vec<String> = plan
+                .operations
+                .iter()
+                .flat_map(Operation::written_keys)
+                .map(str::to_string)
+                .collect();
+
+            if keys.is_empty() {
+                return None;
+            }
+
+            Some(ServiceBackup {
+                service: plan.service,
+                value: backup_value(plan.service, &plan.dest, &keys),
+                keys,
+            })
+        })
+        .collect();
+
+    if services.is_empty() {
+        return None;
+    }
+
+    Some(Backup {
+        id: Uuid::new_v4().to_string(),
+        project_ref: project_id.to_string(),
+        created_at: now_rfc3339(),
+        services,
+    })
+}
+
+// Management API configs are written key by key, so only the overwritten keys are kept.
+// Database backed services keep their whole config since their planners compare objects.
+fn backup_value(service: Service, dest: &Value, keys: &[String]) -> Value {
+    match service {
+        Service::Auth | Service::Postgrest | Service::Postgres => Value::Object(
+            keys.iter()
+                .map(|key| (key.clone(), dest.get(key).cloned().unwrap_or(Value::Null)))
+                .collect(),
+        ),
+        Service::EdgeFunctions => Value::Array(
+            dest.as_array()
+                .into_iter()
+                .flatten()
+                .filter(|function| {
+                    function
+                        .get("slug")
+                        .and_then(Value::as_str)
+                        .is_some_and(|slug| {
+                            keys.iter().any(|key| key.split('.').next() == Some(slug))
+                        })
+                })
+                .cloned()
+                .collect(),
+        ),
+        _ => dest.clone(),
+    }
+}
+
+/// Plans the operations that restore one backed up service on the live destination. Only
+/// keys written by the original apply are touched, and objects it created are dropped.
+pub fn plan_rollback(backup: &ServiceBackup, live: &Value, project_id: &str) -> Vec<Operation> {
+    let mut operations: Vec<Operation> =
+        plan_service(backup.service, &backup.value, live, "", project_id)
+            .into_iter()
+            .filter(|operation| match operation {
+                Operation::Sql(step) => backup.keys.contains(&step.key),
+                _ => true,
+            })
+            .collect();
+
+    operations.extend(drop_created(backup, live));
+    operations
+}
+
+// The planners only ever add database objects, so anything the apply created has to be
+// dropped explicitly.
+fn drop_created(backup: &ServiceBackup, live: &Value) -> Vec<Operation> {
+    let created = |key: &str, in_backup: bool, in_live: bool| {
+        backup.keys.iter().any(|k| k == key) && !in_backup && in_live
+    };
+    let empty = Map::new();
+    let mut steps = Vec::new();
+
+    match backup.service {
+        Service::Extensions => {
+            let backed_up = backup.value.get("installed").and_then(Value::as_object);
+            let installed = live
+                .get("installed")
+                .and_then(Value::as_object)
+                .unwrap_or(&empty);
+
+            for name in installed.keys() {
+                let key = format!("installed.{}", name);
+                if created(&key, backed_up.is_some_and(|b| b.contains_key(name)), true) {
+                    steps.push(Operation::Sql(ApplyStep {
+                        key,
+                        statement: format!("drop extension if exists {}", quote_ident(name)),
+                    }));
+                }
+            }
+        }
+        Service::Roles => {
+            let backed_up = backup.value.get("roles").and_then(Value::as_object);
+            let roles = live
+                .get("roles")
+                .and_then(Value::as_object)
+                .unwrap_or(&empty);
+
+            for name in roles.keys() {
+                let key = format!("roles.{}", name);
+                if created(&key, backed_up.is_some_and(|b| b.contains_key(name)), true) {
+                    steps.push(Operation::Sql(ApplyStep {
+                        key,
+                        statement: format!("drop role if exists {}", quote_ident(name)),
+                    }));
+                }
+            }
+        }
+        Service::Triggers => {
+            let tables = live.as_object().unwrap_or(&empty);
+
+            for (table, triggers) in tables {
+                for name in triggers.as_object().unwrap_or(&empty).keys() {
+                    let key = format!("{}.{}", table, name);
+                    let in_backup = backup.value.get(table).and_then(|t| t.get(name)).is_some();
+                    if !created(&key, in_backup, true) {
+                        continue;
+                    }
+                    let Some(table_name) = quote_qualified_name(table) else {
+                        steps.push(Operation::Skip {
+                            key,
+                            reason: format!("table name {:?} is not a valid identifier", table),
+                        });
+                        continue;
+                    };
+                    steps.push(Operation::Sql(ApplyStep {
+                        key,
+                        statement: format!(
+                            "drop trigger if exists {} on {}",
+                            quote_ident(name),
+                            table_name
+                        ),
+                    }));
+                }
+            }
+        }
+        Service::Realtime => {
+            let in_backup = backup
+                .value
+                .get("publication")
+                .is_some_and(|p| !p.is_null());
+            let in_live = live.get("publication").is_some_and(|p| !p.is_null());
+            if created("publication", in_backup, in_live) {
+                steps.push(Operation::Sql(ApplyStep {
+                    key: "publication".to_string(),
+                    statement: "drop publication if exists supabase_realtime".to_string(),
+                }));
+            }
+        }
+        _ => {}
+    }
+
+    steps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_backup_keeps_only_overwritten_auth_keys() {
+        let source = json!({"site_url": "https://new.example.com"});
+        let dest = json!({"site_url": "https://old.example.com", "jwt_exp": 3600});
+        let operations = plan_service(Service::Auth, &source, &dest, "", "dst");
+        let plans = vec![ServicePlan {
+            service: Service::Auth,
+            operations,
+            source,
+            dest,
+        }];
+
+        let backup = create_backup("dst", &plans).unwrap();
+
+        assert_eq!(backup.project_ref, "dst");
+        assert_eq!(backup.services.len(), 1);
+        assert_eq!(backup.services[0].keys, vec!["site_url"]);
+        assert_eq!(
+            backup.services[0].value,
+            json!({"site_url": "https://old.example.com"})
+        );
+
+        // After the apply, rolling back writes the old value again
+        let live = json!({"site_url": "https://new.example.com", "jwt_exp": 3600});
+        let rollback = plan_rollback(&backup.services[0], &live, "dst");
+        assert!(matches!(
+            &rollback[..],
+            [Operation::Api(write)] if write.body == json!({"site_url": "https://old.example.com"})
+        ));
+    }
+
+    #[test]
+    fn test_no_backup_without_writes() {
+        let plans = vec![ServicePlan {
+            service: Service::Postgrest,
+            operations: Vec::new(),
+            source: json!({"max_rows": 1000}),
+            dest: json!({"max_rows": 1000}),
+        }];
+
+        assert!(create_backup("dst", &plans).is_none());
+    }
+
+    #[test]
+    fn test_rollback_drops_created_objects() {
+        let backup = ServiceBackup {
+            service: Service::Extensions,
+            keys: vec!["installed.vector".to_string()],
+            value: json!({"installed": {"plpgsql": {"version": "1.0", "schema": "pg_catalog"}}}),
+        };
+        let live = json!({
+            "installed": {
+                "plpgsql": {"version": "1.0", "schema": "pg_catalog"},
+                "vector": {"version": "0.8.0", "schema": "extensions"},
+                "pg_net": {"version": "0.14.0", "schema": "extensions"}
+            }
+        });
+
+        let rollback = plan_rollback(&backup, &live, "dst");
+
+        // pg_net was installed by someone else and is left alone
+        assert_eq!(rollback.len(), 1);
+        assert!(matches!(
+            &rollback[0],
+            Operation::Sql(step) if step.statement == "drop extension if exists \"vector\""
+        ));
+    }
+
+    #[test]
+    fn test_rollback_quotes_trigger_tables() {
+        let backup = ServiceBackup {
+            service: Service::Triggers,
+            keys: vec![
+                "\"public\".\"Orders\".on_order".to_string(),
+                "public.todos; drop table x.on_todo".to_string(),
+            ],
+            value: json!({}),
+        };
+        let live = json!({
+            "\"public\".\"Orders\"": {"on_order": "create trigger on_order ..."},
+            "public.todos; drop table x": {"on_todo": "create trigger on_todo ..."}
+        });
+
+        let rollback = plan_rollback(&backup, &live, "dst");
+
+        assert_eq!(rollback.len(), 2);
+        assert!(rollback.iter().any(|op| matches!(
+            op,
+            Operation::Sql(step)
+                if step.statement == "drop trigger if exists \"on_order\" on \"public\".\"Orders\""
+        )));
+        assert!(rollback.iter().any(|op| matches!(
+            op,
+            Operation::Skip { key, .. } if key == "public.todos; drop table x.on_todo"
+        )));
+    }
+}
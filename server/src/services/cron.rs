@@ -0,0 +1,138 @@
+use crate::error::PreviewError;
+use crate::models::migrate::ApplyStep;
+use crate::services::database::{query_config, quote_literal, run_query};
+use crate::services::management_api::ManagementApi;
+
+use serde_json::{Map, Value};
+
+const CRON_INSTALLED_QUERY: &str = "select to_regclass('cron.job') is not null as installed";
+
+const CRON_JOBS_QUERY: &str = "select coalesce((
+    select json_object_agg(j.jobname, json_build_object(
+        'schedule', j.schedule,
+        'command', j.command,
+        'database', j.database,
+        'active', j.active
+    ) order by j.jobname)
+    from cron.job j
+    where j.jobname is not null
+), '{}'::json) as config";
+
+/// Returns the project's named pg_cron jobs, or an empty object when pg_cron is not enabled.
+pub async fn fetch_cron_jobs(api: &ManagementApi, project_id: &str) -> Result<Value, PreviewError> {
+    let rows = run_query(api, project_id, CRON_INSTALLED_QUERY).await?;
+    let installed = rows
+        .first()
+        .and_then(|row| row.get("installed"))
+        .and_then(Value::as_bool)
+        .unwrap_or(false);
+
+    if !installed {
+        return Ok(Value::Object(Map::new()));
+    }
+
+    query_config(api, project_id, CRON_JOBS_QUERY).await
+}
+
+/// Builds the statements that schedule, alter and unschedule jobs on the destination so
+/// that its named jobs match the source.
+pub fn plan_cron_jobs(source: &Value, dest: &Value) -> Vec<ApplyStep> {
+    let empty = Map::new();
+    let src_jobs = source.as_object().unwrap_or(&empty);
+    let dst_jobs = dest.as_object().unwrap_or(&empty);
+
+    let mut steps = Vec::new();
+
+    for (name, job) in src_jobs {
+        match dst_jobs.get(name) {
+            None => steps.push(ApplyStep {
+                key: name.clone(),
+                statement: format!(
+                    "select cron.schedule({name}, {schedule}, {command}); {alter}",
+                    name = quote_literal(name),
+                    schedule = literal_field(job, "schedule"),
+                    command = literal_field(job, "command"),
+                    alter = alter_job(name, job, &["database", "active"]),
+                ),
+            }),
+            Some(dst_job) if dst_job != job => {
+                let changed: Vec<&str> = ["schedule", "command", "database", "active"]
+                    .into_iter()
+                    .filter(|field| job.get(field) != dst_job.get(field))
+                    .collect();
+                steps.push(ApplyStep {
+                    key: name.clone(),
+                    statement: alter_job(name, job, &changed),
+                });
+            }
+            Some(_) => {}
+        }
+    }
+
+    for name in dst_jobs.keys() {
+        if !src_jobs.contains_key(name) {
+            steps.push(ApplyStep {
+                key: name.clone(),
+                statement: format!("select cron.unschedule({})", quote_literal(name)),
+            });
+        }
+    }
+
+    steps
+}
+
+fn literal_field(job: &Value, field: &str) -> String {
+    quote_literal(job.get(field).and_then(Value::as_str).unwrap_or_default())
+}
+
+fn alter_job(name: &str, job: &Value, fields: &[&str]) -> String {
+    let args: Vec<String> = fields
+        .iter()
+        .map(|field| match job.get(field) {
+            Some(Value::Bool(b)) => format!("{} := {}", field, b),
+            _ => format!("{} := {}", field, literal_field(job, field)),
+        })
+        .collect();
+
+    format!(
+        "select cron.alter_job(job_id := (select jobid from cron.job where jobname = {}), {})",
+        quote_literal(name),
+        args.join(", ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan_cron_jobs() {
+        let source: Value = serde_json::from_str(
+            r#"{
+                "cleanup": {"schedule": "0 3 * * *", "command": "delete from logs where created_at < now() - interval '7 days'", "database": "postgres", "active": true},
+                "refresh": {"schedule": "*/5 * * * *", "command": "refresh materialized view stats", "database": "postgres", "active": false}
+            }"#,
+        )
+        .unwrap();
+        let dest: Value = serde_json::from_str(
+            r#"{
+                "refresh": {"schedule": "*/10 * * * *", "command": "refresh materialized view stats", "database": "postgres", "active": true},
+                "legacy": {"schedule": "@daily", "command": "select 1", "database": "postgres", "active": true}
+            }"#,
+        )
+        .unwrap();
+
+        let steps = plan_cron_jobs(&source, &dest);
+
+        assert_eq!(steps.len(), 3);
+        assert_eq!(steps[0].key, "cleanup");
+        assert!(steps[0].statement.starts_with(
+            "select cron.schedule('cleanup', '0 3 * * *', 'delete from logs where created_at < now() - interval ''7 days''');"
+        ));
+        assert_eq!(steps[1].key, "refresh");
+        assert!(steps[1].statement.contains("schedule := '*/5 * * * *'"));
+        assert!(steps[1].statement.contains("active := false"));
+        assert!(!steps[1].statement.contains("command :="));
+        assert_eq!(steps[2].statement, "select cron.unschedule('legacy')");
+    }
+}
@@ -0,0 +1,157 @@
+use crate::error::PreviewError;
+use crate::services::management_api::ManagementApi;
+
+use serde_json::{Value, json};
+
+/// Runs a SQL query against a project through the Management API and returns the result rows.
+pub async fn run_query(
+    api: &ManagementApi,
+    project_id: &str,
+    query: &str,
+) -> Result<Vec<Value>, PreviewError> {
+    let body = json!({ "query": query });
+    let response = api
+        .post(format!("/projects/{}/database/query", project_id), &body)
+        .await?;
+
+    match serde_json::from_str(&response)? {
+        Value::Array(rows) => Ok(rows),
+        _ => Ok(Vec::new()),
+    }
+}
+
+/// Runs a query that returns a single `config` JSON column and extracts that value.
+pub async fn query_config(
+    api: &ManagementApi,
+    project_id: &str,
+    query: &str,
+) -> Result<Value, PreviewError> {
+    let rows = run_query(api, project_id, query).await?;
+
+    Ok(rows
+        .into_iter()
+        .next()
+        .and_then(|mut row| row.get_mut("config").map(Value::take))
+        .unwrap_or(Value::Null))
+}
+
+pub fn quote_ident(ident: &str) -> String {
+    format!("\"{}\"", ident.replace('"', "\"\""))
+}
+
+/// Renders a list of literals as a `text[]` array expression.
+pub fn sql_array(values: &[&str]) -> String {
+    let items: Vec<String> = values
+        .iter()
+        .map(|v| format!("'{}'", v.replace('\'', "''")))
+        .collect();
+    format!("array[{}]::text[]", items.join(", "))
+}
+
+pub fn quote_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// Parses a possibly schema qualified name such as `public.todos` or `"My Schema".items`
+/// and renders it with every part quoted. Unquoted parts are folded to lower case as
+/// Postgres does. Returns `None` for anything that is not a plain dotted name.
+pub fn quote_qualified_name(name: &str) -> Option<String> {
+    let mut parts = Vec::new();
+    let mut chars = name.trim().chars().peekable();
+
+    loop {
+        let mut part = String::new();
+        if chars.peek() == Some(&'"') {
+            chars.next();
+            loop {
+                match chars.next()? {
+                    '"' if chars.peek() == Some(&'"') => {
+                        chars.next();
+                        part.push('"');
+                    }
+                    '"' => break,
+                    c => part.push(c),
+                }
+            }
+        } else {
+            while let Some(&c) = chars.peek() {
+                let valid = c.is_ascii_alphabetic()
+                    || c == '_'
+                    || (!part.is_empty() && (c.is_ascii_digit() || c == '$'));
+                if !valid {
+                    break;
+                }
+                part.push(c.to_ascii_lowercase());
+                chars.next();
+            }
+        }
+
+        if part.is_empty() {
+            return None;
+        }
+        parts.push(quote_ident(&part));
+
+        match chars.next() {
+            None => break,
+            Some('.') => continue,
+            Some(_) => return None,
+        }
+    }
+
+    Some(parts.join("."))
+}
+
+/// Like [`quote_qualified_name`] for a function signature as rendered by
+/// `pg_get_function_identity_arguments`, e.g. `public.add(a integer, b integer)`. The
+/// argument list is kept as is but may only contain names, type names and separators.
+pub fn quote_function_signature(signature: &str) -> Option<String> {
+    let signature = signature.trim();
+    let open = signature.find('(')?;
+    let args = signature[open + 1..].strip_suffix(')')?;
+
+    let allowed = |c: char| c.is_ascii_alphanumeric() || " _,.\"[]$".contains(c);
+    if !args.chars().all(allowed) || args.matches('"').count() % 2 != 0 {
+        return None;
+    }
+
+    let name = quote_qualified_name(&signature[..open])?;
+    Some(format!("{}({})", name, args))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quote_qualified_name() {
+        assert_eq!(
+            quote_qualified_name("public.todos").as_deref(),
+            Some("\"public\".\"todos\"")
+        );
+        assert_eq!(
+            quote_qualified_name("\"My Schema\".Items").as_deref(),
+            Some("\"My Schema\".\"items\"")
+        );
+        assert_eq!(
+            quote_qualified_name("\"a\"\"b\"").as_deref(),
+            Some("\"a\"\"b\"")
+        );
+        assert!(quote_qualified_name("public.todos; drop table x").is_none());
+        assert!(quote_qualified_name("public.").is_none());
+        assert!(quote_qualified_name("\"unterminated").is_none());
+    }
+
+    #[test]
+    fn test_quote_function_signature() {
+        assert_eq!(
+            quote_function_signature("public.add(a integer, b integer)").as_deref(),
+            Some("\"public\".\"add\"(a integer, b integer)")
+        );
+        assert_eq!(
+            quote_function_signature("public.now()").as_deref(),
+            Some("\"public\".\"now\"()")
+        );
+        assert!(quote_function_signature("public.f(integer); drop role x; --)").is_none());
+        assert!(quote_function_signature("public.f").is_none());
+    }
+}
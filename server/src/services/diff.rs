@@ -0,0 +1,622 @@
+use crate::error::PreviewError;
+use crate::models::migrate::{DiffEntry, ProjectConfig};
+use crate::services::extensions::installed_versions;
+
+use serde_json::{Map, Value};
+use similar::TextDiff;
+use std::collections::HashMap;
+
+/// Length from which changed strings get a line-level diff, unless `TEXT_DIFF_THRESHOLD` says
+/// otherwise.
+pub const DEFAULT_TEXT_DIFF_THRESHOLD: usize = 200;
+
+/// Compares a source and destination config, returning `None` when they match.
+pub async fn json_diff(
+    config_type: String,
+    source_value: Value,
+    dest_value: Value,
+) -> Result<Option<ProjectConfig>, PreviewError> {
+    let diff_entries = calculate_diff(&config_type, &source_value, &dest_value)?;
+
+    if diff_entries.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(ProjectConfig {
+            name: config_type,
+            diffs: diff_entries,
+        }))
+    }
+}
+
+/// Flattens the differences between two config values into one entry per changed key path.
+/// Platform managed `SUPABASE_` secrets are ignored, and extensions are compared by their
+/// installed versions only. Values are kept as they are, so mask them with a `Redactor`
+/// before showing them.
+pub fn calculate_diff(
+    config_type: &str,
+    source: &Value,
+    dest: &Value,
+) -> Result<Vec<DiffEntry>, PreviewError> {
+    let mut diff_entries = Vec::new();
+
+    // Pre-filter arrays if this is Secrets config
+    if config_type == "Secrets" {
+        if let (Value::Array(src_arr), Value::Array(dst_arr)) = (source, dest) {
+            // Filter out SUPABASE_ secrets before diffing
+            let filtered_src: Vec<Value> = src_arr
+                .iter()
+                .filter(|v| !is_supabase_secret(v))
+                .cloned()
+                .collect();
+            let filtered_dst: Vec<Value> = dst_arr
+                .iter()
+                .filter(|v| !is_supabase_secret(v))
+                .cloned()
+                .collect();
+
+            let filtered_src_value = Value::Array(filtered_src);
+            let filtered_dst_value = Value::Array(filtered_dst);
+            diff_values(
+                "",
+                &filtered_src_value,
+                &filtered_dst_value,
+                &mut diff_entries,
+            );
+        } else {
+            diff_values("", source, dest, &mut diff_entries);
+        }
+    } else if config_type == "Extensions" {
+        diff_values(
+            "",
+            &installed_versions(source),
+            &installed_versions(dest),
+            &mut diff_entries,
+        );
+    } else {
+        diff_values("", source, dest, &mut diff_entries);
+    }
+
+    Ok(diff_entries)
+}
+
+fn is_supabase_secret(value: &Value) -> bool {
+    if let Value::Object(obj) = value
+        && let Some(Value::String(name)) = obj.get("name")
+    {
+        return name.starts_with("SUPABASE_");
+    }
+    false
+}
+
+pub fn diff_values(path: &str, source: &Value, dest: &Value, diffs: &mut Vec<DiffEntry>) {
+    use Value::*;
+
+    match (source, dest) {
+        (Array(src), Array(dst)) => diff_arrays(path, src, dst, diffs),
+        (Object(src), Object(dst)) => diff_objects(path, src, dst, diffs),
+        _ if source != dest => {
+            diffs.push(DiffEntry {
+                key: if path.is_empty() { "root" } else { path }.to_string(),
+                source_value: format_value(source),
+                dest_value: format_value(dest),
+                text_diff: None,
+            });
+        }
+        _ => {} // Values are equal
+    }
+}
+
+fn diff_arrays(path: &str, src: &[Value], dst: &[Value], diffs: &mut Vec<DiffEntry>) {
+    let src_map = to_id_map(src);
+    let dst_map = to_id_map(dst);
+
+    match (src_map, dst_map) {
+        (Some(src_ids), Some(mut dst_ids)) => {
+            diff_by_id(path, &src_ids, &mut dst_ids, diffs);
+        }
+        (Some(src_ids), None) => {
+            for (id, val) in src_ids {
+                diffs.push(DiffEntry {
+                    key: format!(
+                        "{}{}id:{}",
+                        path,
+                        if path.is_empty() { "" } else { "." },
+                        id
+                    ),
+                    source_value: format_value(val),
+                    dest_value: "null".to_string(),
+                    text_diff: None,
+                });
+            }
+        }
+        (None, Some(dst_ids)) => {
+            for (id, val) in dst_ids {
+                diffs.push(DiffEntry {
+                    key: format!(
+                        "{}{}id:{}",
+                        path,
+                        if path.is_empty() { "" } else { "." },
+                        id
+                    ),
+                    source_value: "null".to_string(),
+                    dest_value: format_value(val),
+                    text_diff: None,
+                });
+            }
+        }
+        (None, None) => {
+            diff_by_index(path, src, dst, diffs);
+        }
+    }
+}
+
+fn to_id_map(arr: &[Value]) -> Option<HashMap<String, &Value>> {
+    let mut map = HashMap::new();
+    let mut has_ids = false;
+
+    for item in arr {
+        if let Value::Object(obj) = item
+            && let Some(Value::String(id)) = obj.get("id")
+        {
+            map.insert(id.clone(), item);
+            has_ids = true;
+        }
+    }
+
+    if has_ids { Some(map) } else { None }
+}
+
+fn diff_by_id(
+    path: &str,
+    src_map: &HashMap<String, &Value>,
+    dst_map: &mut HashMap<String, &Value>,
+    diffs: &mut Vec<DiffEntry>,
+) {
+    for (id, src_val) in src_map {
+        let item_path = format!(
+            "{}{}id:{}",
+            path,
+            if path.is_empty() { "" } else { "." },
+            id
+        );
+
+        if let Some(dst_val) = dst_map.remove(id) {
+            diff_values(&item_path, src_val, dst_val, diffs);
+        } else {
+            diffs.push(DiffEntry {
+                key: item_path,
+                source_value: format_value(src_val),
+                dest_value: "null".to_string(),
+                text_diff: None,
+            });
+        }
+    }
+
+    for (id, dst_val) in dst_map.iter() {
+        diffs.push(DiffEntry {
+            key: format!(
+                "{}{}id:{}",
+                path,
+                if path.is_empty() { "" } else { "." },
+                id
+            ),
+            source_value: "null".to_string(),
+            dest_value: format_value(dst_val),
+            text_diff: None,
+        });
+    }
+}
+
+fn diff_by_index(path: &str, src: &[Value], dst: &[Value], diffs: &mut Vec<DiffEntry>) {
+    let max_len = src.len().max(dst.len());
+
+    for i in 0..max_len {
+        let item_path = format!("{}[{}]", path, i);
+
+        match (src.get(i), dst.get(i)) {
+            (Some(s), Some(d)) => {
+                if s.is_object() && d.is_object() && s != d {
+                    diffs.push(DiffEntry {
+                        key: item_path,
+                        source_value: format_value(s),
+                        dest_value: format_value(d),
+                        text_diff: None,
+                    });
+                } else if !s.is_object() || !d.is_object() {
+                    diff_values(&item_path, s, d, diffs);
+                }
+            }
+            (Some(s), None) => diffs.push(DiffEntry {
+                key: item_path,
+                source_value: format_value(s),
+                dest_value: "null".to_string(),
+                text_diff: None,
+            }),
+            (None, Some(d)) => diffs.push(DiffEntry {
+                key: item_path,
+                source_value: "null".to_string(),
+                dest_value: format_value(d),
+                text_diff: None,
+            }),
+            _ => {}
+        }
+    }
+}
+
+fn diff_objects(
+    path: &str,
+    src: &Map<String, Value>,
+    dst: &Map<String, Value>,
+    diffs: &mut Vec<DiffEntry>,
+) {
+    for (key, src_val) in src {
+        let field_path = if path.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", path, key)
+        };
+
+        match dst.get(key) {
+            Some(dst_val) => diff_values(&field_path, src_val, dst_val, diffs),
+            None => diffs.push(DiffEntry {
+                key: field_path,
+                source_value: format_value(src_val),
+                dest_value: "null".to_string(),
+                text_diff: None,
+            }),
+        }
+    }
+
+    for (key, dst_val) in dst {
+        if !src.contains_key(key) {
+            let field_path = if path.is_empty() {
+                key.clone()
+            } else {
+                format!("{}.{}", path, key)
+            };
+            diffs.push(DiffEntry {
+                key: field_path,
+                source_value: "null".to_string(),
+                dest_value: format_value(dst_val),
+                text_diff: None,
+            });
+        }
+    }
+}
+
+/// Renders a value the way diff entries show it: strings unquoted, everything else as JSON.
+pub fn format_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => "null".to_string(),
+        Value::Number(n) => n.to_string(),
+        Value::Bool(b) => b.to_string(),
+        Value::Array(_) | Value::Object(_) => value.to_string(),
+    }
+}
+
+/// Attaches a line-level unified diff to every changed string of at least `threshold`
+/// characters, such as Auth email templates. Added or removed values and JSON values are left
+/// as they are.
+pub fn add_text_diffs(configs: &mut [ProjectConfig], threshold: usize) {
+    for diff in configs
+        .iter_mut()
+        .flat_map(|config| config.diffs.iter_mut())
+    {
+        let long = diff
+            .source_value
+            .chars()
+            .count()
+            .max(diff.dest_value.chars().count());
+        if long >= threshold && is_text(&diff.source_value) && is_text(&diff.dest_value) {
+            diff.text_diff = Some(text_diff(&diff.dest_value, &diff.source_value));
+        }
+    }
+}
+
+fn is_text(value: &str) -> bool {
+    value != "null"
+        && !matches!(
+            serde_json::from_str::<Value>(value),
+            Ok(Value::Object(_) | Value::Array(_))
+        )
+}
+
+/// Unified diff reading from the destination value to the source value, i.e. what applying
+/// would change.
+fn text_diff(dest: &str, source: &str) -> String {
+    TextDiff::from_lines(dest, source)
+        .unified_diff()
+        .context_radius(3)
+        .header("destination", "source")
+        .missing_newline_hint(false)
+        .to_string()
+}
+
+/// Matches `value` against a pattern where `*` stands for any run of characters, including
+/// none. Everything else matches literally.
+pub fn glob_match(pattern: &str, value: &str) -> bool {
+    let mut parts = pattern.split('*');
+    // A pattern always yields at least one part
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = value.strip_prefix(first) else {
+        return false;
+    };
+
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_object_diff() {
+        let source: Value = serde_json::from_str(r#"{"a": 1, "b": 2}"#).unwrap();
+        let dest: Value = serde_json::from_str(r#"{"a": 1, "b": 3, "c": 4}"#).unwrap();
+
+        let result = json_diff("test".to_string(), source, dest).await.unwrap();
+        let config = result.unwrap();
+
+        assert_eq!(config.diffs.len(), 2); // b changed, c added
+        assert!(
+            config
+                .diffs
+                .iter()
+                .any(|d| d.key == "b" && d.dest_value == "3")
+        );
+        assert!(
+            config
+                .diffs
+                .iter()
+                .any(|d| d.key == "c" && d.source_value == "null")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_edge_functions_diff() {
+        let source = r#"[
+            {"id": "func1", "version": 1},
+            {"id": "func2", "version": 1}
+        ]"#;
+        let dest = r#"[]"#;
+
+        let source_value: Value = serde_json::from_str(source).unwrap();
+        let dest_value: Value = serde_json::from_str(dest).unwrap();
+
+        let result = json_diff("test".to_string(), source_value, dest_value)
+            .await
+            .unwrap();
+        let config = result.unwrap();
+
+        assert!(!config.diffs.iter().any(|d| d.key == "length"));
+        assert!(config.diffs.iter().any(|d| d.key == "id:func1"));
+        assert!(config.diffs.iter().any(|d| d.key == "id:func2"));
+    }
+
+    #[tokio::test]
+    async fn test_no_diff() {
+        let source = r#"{"a": 1, "b": "test", "c": true}"#;
+        let dest = r#"{"a": 1, "b": "test", "c": true}"#;
+
+        let source_value: Value = serde_json::from_str(source).unwrap();
+        let dest_value: Value = serde_json::from_str(dest).unwrap();
+
+        let result = json_diff("test".to_string(), source_value, dest_value)
+            .await
+            .unwrap();
+        assert!(result.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_nested_object_diff() {
+        let source = r#"{
+            "user": {
+                "name": "John",
+                "age": 30,
+                "address": {
+                    "street": "123 Main St",
+                    "city": "Boston"
+                }
+            }
+        }"#;
+        let dest = r#"{
+            "user": {
+                "name": "John",
+                "age": 31,
+                "address": {
+                    "street": "123 Main St",
+                    "city": "New York",
+                    "zip": "10001"
+                }
+            }
+        }"#;
+
+        let source_value: Value = serde_json::from_str(source).unwrap();
+        let dest_value: Value = serde_json::from_str(dest).unwrap();
+
+        let result = json_diff("test".to_string(), source_value, dest_value)
+            .await
+            .unwrap();
+        let config = result.unwrap();
+
+        assert_eq!(config.diffs.len(), 3);
+        assert!(
+            config
+                .diffs
+                .iter()
+                .any(|d| d.key == "user.age" && d.dest_value == "31")
+        );
+        assert!(
+            config
+                .diffs
+                .iter()
+                .any(|d| d.key == "user.address.city" && d.dest_value == "New York")
+        );
+        assert!(
+            config
+                .diffs
+                .iter()
+                .any(|d| d.key == "user.address.zip" && d.source_value == "null")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_array_of_primitives() {
+        let source = r#"[1, 2, 3, 4]"#;
+        let dest = r#"[1, 2, 5]"#;
+
+        let source_value: Value = serde_json::from_str(source).unwrap();
+        let dest_value: Value = serde_json::from_str(dest).unwrap();
+
+        let result = json_diff("test".to_string(), source_value, dest_value)
+            .await
+            .unwrap();
+        let config = result.unwrap();
+
+        // No length diff
+        assert!(!config.diffs.iter().any(|d| d.key == "length"));
+        assert!(
+            config
+                .diffs
+                .iter()
+                .any(|d| d.key == "[2]" && d.source_value == "3" && d.dest_value == "5")
+        );
+        assert!(
+            config
+                .diffs
+                .iter()
+                .any(|d| d.key == "[3]" && d.source_value == "4" && d.dest_value == "null")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_secrets_with_supabase_filter() {
+        let source = r#"[
+            {"name": "MY_SECRET", "updated_at": "2025-01-01T00:00:00Z", "value": "secret1"},
+            {"name": "SUPABASE_URL", "updated_at": "2025-01-01T00:00:00Z", "value": "old_url"},
+            {"name": "ANOTHER_SECRET", "updated_at": "2025-01-01T00:00:00Z", "value": "secret2"}
+        ]"#;
+        let dest = r#"[
+            {"name": "MY_SECRET", "updated_at": "2025-01-02T00:00:00Z", "value": "secret1_new"},
+            {"name": "SUPABASE_URL", "updated_at": "2025-01-02T00:00:00Z", "value": "new_url"},
+            {"name": "SUPABASE_ANON_KEY", "updated_at": "2025-01-02T00:00:00Z", "value": "anon_key"}
+        ]"#;
+
+        let source_value: Value = serde_json::from_str(source).unwrap();
+        let dest_value: Value = serde_json::from_str(dest).unwrap();
+
+        let result = json_diff("Secrets".to_string(), source_value, dest_value)
+            .await
+            .unwrap();
+        let config = result.unwrap();
+
+        // After filtering SUPABASE_ secrets:
+        // Source has: MY_SECRET, ANOTHER_SECRET
+        // Dest has: MY_SECRET
+        // So we should see:
+        // - [0] changed (MY_SECRET value changed)
+        // - [1] removed (ANOTHER_SECRET)
+        assert_eq!(config.diffs.len(), 2);
+        assert!(config.diffs.iter().any(|d| d.key == "[0]")); // MY_SECRET changed
+        assert!(
+            config
+                .diffs
+                .iter()
+                .any(|d| d.key == "[1]" && d.source_value.contains("ANOTHER_SECRET"))
+        ); // ANOTHER_SECRET removed
+
+        // Should not have any SUPABASE_ related diffs
+        for diff in &config.diffs {
+            assert!(!diff.source_value.contains("SUPABASE_"));
+            assert!(!diff.dest_value.contains("SUPABASE_"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_array_object_diff_whole_object() {
+        let source = r#"[
+            {"name": "item1", "value": 100, "active": true}
+        ]"#;
+        let dest = r#"[
+            {"name": "item1", "value": 200, "active": true}
+        ]"#;
+
+        let source_value: Value = serde_json::from_str(source).unwrap();
+        let dest_value: Value = serde_json::from_str(dest).unwrap();
+
+        let result = json_diff("test".to_string(), source_value, dest_value)
+            .await
+            .unwrap();
+        let config = result.unwrap();
+
+        // Should report the whole object as changed
+        assert_eq!(config.diffs.len(), 1);
+        assert!(config.diffs.iter().any(|d| d.key == "[0]"));
+        assert!(config.diffs[0].source_value.contains("\"value\":100"));
+        assert!(config.diffs[0].dest_value.contains("\"value\":200"));
+    }
+
+    #[tokio::test]
+    async fn test_text_diffs_for_long_strings() {
+        let template = |line: &str| {
+            format!(
+                "<h2>Confirm your signup</h2>\n<p>{}</p>\n<p><a href=\"{{{{ .ConfirmationURL }}}}\">Confirm</a></p>\n",
+                line
+            )
+        };
+        let source = serde_json::json!({
+            "mailer_templates_confirmation_content": template("Follow this link to confirm your account:"),
+            "site_url": "https://staging.example.com",
+            "uri_allow_list": ["https://a.example.com"]
+        });
+        let dest = serde_json::json!({
+            "mailer_templates_confirmation_content": template("Follow this link to confirm your user:"),
+            "site_url": "https://example.com",
+            "uri_allow_list": ["https://b.example.com"]
+        });
+        let mut configs = vec![
+            json_diff("Auth".to_string(), source, dest)
+                .await
+                .unwrap()
+                .unwrap(),
+        ];
+
+        add_text_diffs(&mut configs, 100);
+
+        let diffs = &configs[0].diffs;
+        let template_diff = diffs
+            .iter()
+            .find(|d| d.key == "mailer_templates_confirmation_content")
+            .and_then(|d| d.text_diff.as_deref())
+            .unwrap();
+        assert!(template_diff.starts_with("--- destination\n+++ source\n@@"));
+        assert!(template_diff.contains("\n-<p>Follow this link to confirm your user:</p>\n"));
+        assert!(template_diff.contains("\n+<p>Follow this link to confirm your account:</p>\n"));
+        assert!(template_diff.contains("\n <h2>Confirm your signup</h2>\n"));
+        // Short strings and JSON values keep the whole-value comparison only
+        assert!(diffs.iter().filter(|d| d.text_diff.is_some()).count() == 1);
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("mailer_autoconfirm", "mailer_autoconfirm"));
+        assert!(!glob_match("mailer_autoconfirm", "mailer_autoconfirm_x"));
+        assert!(glob_match("*.verify_jwt", "id:abc.verify_jwt"));
+        assert!(!glob_match("*.verify_jwt", "id:abc.name"));
+        assert!(glob_match("smtp_*", "smtp_host"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("a*b*c", "abbc"));
+        assert!(!glob_match("ab*ba", "aba"));
+    }
+}
@@ -0,0 +1,257 @@
+use crate::error::PreviewError;
+use crate::models::AppState;
+use crate::models::drift::{DriftChange, DriftConfig, DriftPair, DriftState, DriftStatus};
+use crate::models::migrate::ProjectConfig;
+use crate::services::history::compare_previews;
+use crate::services::management_api::ManagementApi;
+use crate::services::notify::{drift_notification, notify};
+use crate::services::{PreviewSide, load_config_file, now_rfc3339, preview_services};
+
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+/// Reads the drift configuration, accepting either JSON or YAML. Pair names must be unique.
+pub fn load_drift_config(path: &str) -> Result<DriftConfig, String> {
+    let config: DriftConfig = load_config_file(path, "drift config")?;
+
+    if config.interval_minutes == 0 {
+        return Err("Drift interval_minutes must be at least 1".to_string());
+    }
+    let mut names = HashSet::new();
+    for pair in &config.pairs {
+        if !names.insert(pair.name.as_str()) {
+            return Err(format!("Duplicate drift pair name: {}", pair.name));
+        }
+    }
+
+    Ok(config)
+}
+
+/// One not yet checked status per configured pair, keyed by pair name.
+pub fn initial_statuses(config: &DriftConfig) -> HashMap<String, DriftStatus> {
+    config
+        .pairs
+        .iter()
+        .map(|pair| (pair.name.clone(), DriftStatus::not_checked(pair.clone())))
+        .collect()
+}
+
+/// Starts the background scheduler that checks every configured pair on the configured
+/// interval. Needs `SUPABASE_ACCESS_TOKEN`, since there is no user session to borrow.
+pub fn spawn_drift_scheduler(app_state: AppState) {
+    let config = &app_state.config.drift;
+    if config.pairs.is_empty() {
+        return;
+    }
+    let Some(token) = app_state.config.access_token.clone() else {
+        eprintln!("Drift pairs configured but SUPABASE_ACCESS_TOKEN is not set, not scheduling");
+        return;
+    };
+
+    let api = ManagementApi::new(token);
+    let period = Duration::from_secs(config.interval_minutes * 60);
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(period);
+        loop {
+            interval.tick().await;
+            for pair in &app_state.config.drift.pairs {
+                if let Err(e) = check_pair(&app_state, &api, &pair.name).await {
+                    eprintln!("Drift check for {} failed: {:?}", pair.name, e);
+                }
+            }
+        }
+    });
+}
+
+/// Runs the preview diff for one pair and records how its drift moved since the last check.
+pub async fn check_pair(
+    app_state: &AppState,
+    api: &ManagementApi,
+    pair_name: &str,
+) -> Result<DriftStatus, PreviewError> {
+    let pair = find_pair(&app_state.config.drift, pair_name)?;
+    let outcome = preview_services(
+        api,
+        &pair.services,
+        PreviewSide::Project(&pair.source),
+        PreviewSide::Project(&pair.dest),
+        &app_state.config.transforms,
+    )
+    .await;
+
+    let mut statuses = app_state
+        .drift
+        .lock()
+        .map_err(|e| PreviewError::ApiError(format!("Failed to update drift status: {:?}", e)))?;
+    let previous = statuses
+        .remove(pair_name)
+        .unwrap_or_else(|| DriftStatus::not_checked(pair.clone()));
+    let status = next_status(previous, outcome, now_rfc3339());
+
+    if let Some(notification) = drift_notification(&status) {
+        eprintln!(
+            "Drift {:?} for {}: {} differences",
+            status.change, pair_name, status.diff_count
+        );
+        notify(&app_state.config.notifications, notification);
+    }
+    statuses.insert(pair_name.to_string(), status.clone());
+
+    Ok(status)
+}
+
+pub fn find_pair(config: &DriftConfig, pair_name: &str) -> Result<DriftPair, PreviewError> {
+    config
+        .pairs
+        .iter()
+        .find(|pair| pair.name == pair_name)
+        .cloned()
+        .ok_or_else(|| PreviewError::NotFound(format!("Drift pair {} not found", pair_name)))
+}
+
+/// Folds the outcome of a check into the previous status. A failed check keeps the last known
+/// differences so a flaky API call does not read as drift disappearing.
+fn next_status(
+    previous: DriftStatus,
+    outcome: Result<Vec<ProjectConfig>, PreviewError>,
+    now: String,
+) -> DriftStatus {
+    let configs = match outcome {
+        Ok(configs) => configs,
+        Err(e) => {
+            return DriftStatus {
+                state: DriftState::Error,
+                change: DriftChange::Unchanged,
+                checked_at: Some(now),
+                changes: Vec::new(),
+                error: Some(e.to_string()),
+                ..previous
+            };
+        }
+    };
+
+    let changes = compare_previews(&previous.configs, &configs);
+    let drifted = !configs.is_empty();
+    let was_drifted = !previous.configs.is_empty();
+    let change = match (was_drifted, drifted) {
+        (false, true) => DriftChange::Appeared,
+        (true, false) => DriftChange::Disappeared,
+        (true, true) if !changes.is_empty() => DriftChange::Changed,
+        _ => DriftChange::Unchanged,
+    };
+
+    DriftStatus {
+        pair: previous.pair,
+        state: if drifted {
+            DriftState::Drifted
+        } else {
+            DriftState::InSync
+        },
+        change,
+        last_change_at: if change == DriftChange::Unchanged {
+            previous.last_change_at
+        } else {
+            Some(now.clone())
+        },
+        checked_at: Some(now),
+        diff_count: configs.iter().map(|config| config.diffs.len()).sum(),
+        configs,
+        changes,
+        error: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::migrate::DiffEntry;
+    use crate::services::Service;
+
+    fn pair() -> DriftPair {
+        DriftPair {
+            name: "staging-to-prod".to_string(),
+            source: "stg".to_string(),
+            dest: "prd".to_string(),
+            services: vec![Service::Auth],
+        }
+    }
+
+    fn auth_diff(dest_value: &str) -> Vec<ProjectConfig> {
+        vec![ProjectConfig {
+            name: "Auth".to_string(),
+            diffs: vec![DiffEntry {
+                key: "site_url".to_string(),
+                source_value: "https://stg.example.com".to_string(),
+                dest_value: dest_value.to_string(),
+                text_diff: None,
+            }],
+        }]
+    }
+
+    #[test]
+    fn test_drift_transitions() {
+        let status = DriftStatus::not_checked(pair());
+
+        let status = next_status(status, Ok(Vec::new()), "t1".to_string());
+        assert_eq!(status.state, DriftState::InSync);
+        assert_eq!(status.change, DriftChange::Unchanged);
+        assert!(status.last_change_at.is_none());
+
+        let status = next_status(
+            status,
+            Ok(auth_diff("https://a.example.com")),
+            "t2".to_string(),
+        );
+        assert_eq!(status.state, DriftState::Drifted);
+        assert_eq!(status.change, DriftChange::Appeared);
+        assert_eq!(status.diff_count, 1);
+
+        let status = next_status(
+            status,
+            Ok(auth_diff("https://a.example.com")),
+            "t3".to_string(),
+        );
+        assert_eq!(status.change, DriftChange::Unchanged);
+        assert_eq!(status.last_change_at.as_deref(), Some("t2"));
+
+        let status = next_status(
+            status,
+            Ok(auth_diff("https://b.example.com")),
+            "t4".to_string(),
+        );
+        assert_eq!(status.change, DriftChange::Changed);
+        assert_eq!(status.changes.len(), 1);
+
+        let status = next_status(status, Ok(Vec::new()), "t5".to_string());
+        assert_eq!(status.state, DriftState::InSync);
+        assert_eq!(status.change, DriftChange::Disappeared);
+    }
+
+    #[test]
+    fn test_failed_check_keeps_last_diff() {
+        let drifted = next_status(
+            DriftStatus::not_checked(pair()),
+            Ok(auth_diff("https://a.example.com")),
+            "t1".to_string(),
+        );
+
+        let status = next_status(
+            drifted,
+            Err(PreviewError::ApiError("timeout".to_string())),
+            "t2".to_string(),
+        );
+
+        assert_eq!(status.state, DriftState::Error);
+        assert_eq!(status.error.as_deref(), Some("timeout"));
+        assert_eq!(status.diff_count, 1);
+        assert_eq!(status.checked_at.as_deref(), Some("t2"));
+
+        // Recovering with the same diff is not reported as new drift
+        let status = next_status(
+            status,
+            Ok(auth_diff("https://a.example.com")),
+            "t3".to_string(),
+        );
+        assert_eq!(status.change, DriftChange::Unchanged);
+    }
+}
@@ -0,0 +1,204 @@
+use crate::error::PreviewError;
+use crate::models::migrate::{ApplyStep, Operation};
+use crate::services::database::{query_config, quote_ident};
+use crate::services::management_api::ManagementApi;
+
+use serde_json::{Map, Value};
+
+// Installed extensions keyed by name with their version and schema, plus every
+// extension the project could enable with its default version.
+const EXTENSIONS_QUERY: &str = "select json_build_object(
+    'installed', coalesce((
+        select json_object_agg(
+            e.extname,
+            json_build_object('version', e.extversion, 'schema', n.nspname)
+            order by e.extname
+        )
+        from pg_extension e
+        join pg_namespace n on n.oid = e.extnamespace
+    ), '{}'::json),
+    'available', coalesce((
+        select json_object_agg(a.name, a.default_version order by a.name)
+        from pg_available_extensions a
+    ), '{}'::json)
+) as config";
+
+pub async fn fetch_extensions(
+    api: &ManagementApi,
+    project_id: &str,
+) -> Result<Value, PreviewError> {
+    query_config(api, project_id, EXTENSIONS_QUERY).await
+}
+
+/// The part of an extensions config that is compared: the installed extensions and their
+/// versions. The catalog of available extensions changes with every platform release, so
+/// comparing it would only add noise.
+pub fn installed_versions(config: &Value) -> Value {
+    let installed: Map<String, Value> = config
+        .get("installed")
+        .and_then(Value::as_object)
+        .into_iter()
+        .flatten()
+        .map(|(name, details)| {
+            let version = details.get("version").cloned().unwrap_or(Value::Null);
+            (name.clone(), version)
+        })
+        .collect();
+
+    let mut view = Map::new();
+    view.insert("installed".to_string(), Value::Object(installed));
+    Value::Object(view)
+}
+
+/// Builds the statements that enable every extension installed on the source but missing
+/// on the destination, in the same schema the source uses. Extensions the destination does
+/// not offer are skipped, since enabling them would fail.
+pub fn plan_extensions(source: &Value, dest: &Value) -> Vec<Operation> {
+    let mut operations = Vec::new();
+
+    let Some(src_installed) = source.get("installed").and_then(Value::as_object) else {
+        return operations;
+    };
+    let dst_installed = dest.get("installed").and_then(Value::as_object);
+    // Configs without the catalog, such as backups, are not checked
+    let dst_available = dest.get("available").and_then(Value::as_object);
+
+    for (name, details) in src_installed {
+        if dst_installed.is_some_and(|installed| installed.contains_key(name)) {
+            continue;
+        }
+
+        let key = format!("installed.{}", name);
+        if dst_available.is_some_and(|available| !available.contains_key(name)) {
+            operations.push(Operation::Skip {
+                key,
+                reason: format!("{} is not available on the destination", name),
+            });
+            continue;
+        }
+
+        let schema = details
+            .get("schema")
+            .and_then(Value::as_str)
+            .unwrap_or("extensions");
+
+        operations.push(Operation::Sql(ApplyStep {
+            key,
+            statement: format!(
+                "create schema if not exists {schema}; create extension if not exists {name} with schema {schema} cascade",
+                schema = quote_ident(schema),
+                name = quote_ident(name),
+            ),
+        }));
+    }
+
+    operations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sql_steps(operations: &[Operation]) -> Vec<&ApplyStep> {
+        operations
+            .iter()
+            .filter_map(|op| match op {
+                Operation::Sql(step) => Some(step),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_plan_missing_extensions() {
+        let source: Value = serde_json::from_str(
+            r#"{
+                "installed": {
+                    "pg_net": {"version": "0.14.0", "schema": "extensions"},
+                    "vector": {"version": "0.8.0", "schema": "public"},
+                    "plpgsql": {"version": "1.0", "schema": "pg_catalog"}
+                },
+                "available": {}
+            }"#,
+        )
+        .unwrap();
+        let dest: Value = serde_json::from_str(
+            r#"{
+                "installed": {
+                    "plpgsql": {"version": "1.0", "schema": "pg_catalog"}
+                },
+                "available": {"pg_net": "0.14.0", "vector": "0.8.0", "plpgsql": "1.0"}
+            }"#,
+        )
+        .unwrap();
+
+        let operations = plan_extensions(&source, &dest);
+        let steps = sql_steps(&operations);
+
+        assert_eq!(operations.len(), 2);
+        assert_eq!(steps.len(), 2);
+        assert!(steps.iter().any(|s| {
+            s.key == "installed.pg_net"
+                && s.statement.contains(
+                    "create extension if not exists \"pg_net\" with schema \"extensions\"",
+                )
+        }));
+        assert!(
+            steps
+                .iter()
+                .any(|s| s.key == "installed.vector"
+                    && s.statement.contains("with schema \"public\""))
+        );
+    }
+
+    #[test]
+    fn test_plan_skips_extensions_the_destination_does_not_offer() {
+        let source: Value = serde_json::from_str(
+            r#"{"installed": {"postgis": {"version": "3.3.2", "schema": "extensions"}}}"#,
+        )
+        .unwrap();
+        let dest: Value =
+            serde_json::from_str(r#"{"installed": {}, "available": {"vector": "0.8.0"}}"#).unwrap();
+
+        let operations = plan_extensions(&source, &dest);
+
+        assert_eq!(operations.len(), 1);
+        match &operations[0] {
+            Operation::Skip { key, reason } => {
+                assert_eq!(key, "installed.postgis");
+                assert!(reason.contains("not available"));
+            }
+            other => panic!("expected a skip, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_plan_ignores_version_only_differences() {
+        let source: Value = serde_json::from_str(
+            r#"{"installed": {"vector": {"version": "0.8.0", "schema": "extensions"}}}"#,
+        )
+        .unwrap();
+        let dest: Value = serde_json::from_str(
+            r#"{"installed": {"vector": {"version": "0.7.0", "schema": "extensions"}}}"#,
+        )
+        .unwrap();
+
+        assert!(plan_extensions(&source, &dest).is_empty());
+    }
+
+    #[test]
+    fn test_installed_versions_drops_the_catalog() {
+        let config: Value = serde_json::from_str(
+            r#"{
+                "installed": {"vector": {"version": "0.8.0", "schema": "extensions"}},
+                "available": {"vector": "0.8.0", "postgis": "3.3.2"}
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            installed_versions(&config),
+            serde_json::json!({"installed": {"vector": "0.8.0"}})
+        );
+    }
+}
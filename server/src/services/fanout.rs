@@ -0,0 +1,179 @@
+use crate::error::PreviewError;
+use crate::models::fanout::{FanOutCell, FanOutRequest, FanOutRow};
+use crate::models::migrate::ProjectConfig;
+use crate::models::transform::TransformConfig;
+use crate::services::management_api::ManagementApi;
+use crate::services::{PreviewSide, fetch_service_config, preview_services};
+
+use futures_util::stream::{self, StreamExt};
+use std::collections::BTreeMap;
+
+/// Destinations compared or applied at the same time, to stay clear of Management API rate
+/// limits.
+pub const MAX_CONCURRENT_DESTINATIONS: usize = 4;
+
+/// The differences of one destination, or why it could not be compared.
+pub type DestinationDiff = (String, Result<Vec<ProjectConfig>, PreviewError>);
+
+pub fn validate_fan_out(request: &FanOutRequest) -> Result<(), PreviewError> {
+    if request.dest_ids.is_empty() {
+        return Err(PreviewError::BadRequest(
+            "At least one destination is required".to_string(),
+        ));
+    }
+    if request.services.is_empty() {
+        return Err(PreviewError::BadRequest(
+            "At least one service is required".to_string(),
+        ));
+    }
+    for (index, dest_id) in request.dest_ids.iter().enumerate() {
+        if *dest_id == request.source_id {
+            return Err(PreviewError::BadRequest(format!(
+                "{} is both the source and a destination",
+                dest_id
+            )));
+        }
+        if request.dest_ids[..index].contains(dest_id) {
+            return Err(PreviewError::BadRequest(format!(
+                "Duplicate destination: {}",
+                dest_id
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Diffs the source against every destination, reading the source once and the
+/// destinations concurrently. Results follow the order of `dest_ids`.
+pub async fn preview_fan_out(
+    api: &ManagementApi,
+    request: &FanOutRequest,
+    transforms: &TransformConfig,
+) -> Result<Vec<DestinationDiff>, PreviewError> {
+    let mut sources = BTreeMap::new();
+    for service in &request.services {
+        sources.insert(
+            *service,
+            fetch_service_config(api, *service, &request.source_id).await?,
+        );
+    }
+
+    let source = PreviewSide::Fetched {
+        project_id: &request.source_id,
+        configs: &sources,
+    };
+    Ok(stream::iter(request.dest_ids.clone())
+        .map(|dest_id| async move {
+            let configs = preview_services(
+                api,
+                &request.services,
+                source,
+                PreviewSide::Project(&dest_id),
+                transforms,
+            )
+            .await;
+            (dest_id, configs)
+        })
+        .buffered(MAX_CONCURRENT_DESTINATIONS)
+        .collect()
+        .await)
+}
+
+/// Pivots the per-destination differences into one row per service and key.
+pub fn fan_out_matrix(results: &[DestinationDiff]) -> Vec<FanOutRow> {
+    let mut rows: BTreeMap<(&str, &str), BTreeMap<String, FanOutCell>> = BTreeMap::new();
+
+    for (dest_ref, configs) in results {
+        let Ok(configs) = configs else { continue };
+        for config in configs {
+            for diff in &config.diffs {
+                rows.entry((&config.name, &diff.key)).or_default().insert(
+                    dest_ref.clone(),
+                    FanOutCell {
+                        source_value: diff.source_value.clone(),
+                        dest_value: diff.dest_value.clone(),
+                    },
+                );
+            }
+        }
+    }
+
+    rows.into_iter()
+        .map(|((service, key), dests)| FanOutRow {
+            service: service.to_string(),
+            key: key.to_string(),
+            dests,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::migrate::DiffEntry;
+    use crate::services::Service;
+
+    fn auth_diff(key: &str, source_value: &str, dest_value: &str) -> ProjectConfig {
+        ProjectConfig {
+            name: "Auth".to_string(),
+            diffs: vec![DiffEntry {
+                key: key.to_string(),
+                source_value: source_value.to_string(),
+                dest_value: dest_value.to_string(),
+                text_diff: None,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_matrix_rows_per_key() {
+        let results = vec![
+            (
+                "customer-a".to_string(),
+                Ok(vec![auth_diff("jwt_exp", "3600", "7200")]),
+            ),
+            (
+                "customer-b".to_string(),
+                Ok(vec![
+                    auth_diff("jwt_exp", "3600", "1800"),
+                    auth_diff("mfa_max_enrolled_factors", "10", "5"),
+                ]),
+            ),
+            ("customer-c".to_string(), Ok(Vec::new())),
+            (
+                "customer-d".to_string(),
+                Err(PreviewError::ApiError("forbidden".to_string())),
+            ),
+        ];
+
+        let matrix = fan_out_matrix(&results);
+
+        assert_eq!(matrix.len(), 2);
+        assert_eq!(matrix[0].key, "jwt_exp");
+        assert_eq!(
+            matrix[0].dests.keys().collect::<Vec<_>>(),
+            vec!["customer-a", "customer-b"]
+        );
+        assert_eq!(matrix[0].dests["customer-b"].dest_value, "1800");
+        assert_eq!(matrix[1].key, "mfa_max_enrolled_factors");
+        assert_eq!(
+            matrix[1].dests.keys().collect::<Vec<_>>(),
+            vec!["customer-b"]
+        );
+    }
+
+    #[test]
+    fn test_validate_destinations() {
+        let request = |dest_ids: &[&str]| FanOutRequest {
+            source_id: "template".to_string(),
+            dest_ids: dest_ids.iter().map(|id| id.to_string()).collect(),
+            services: vec![Service::Auth],
+            keys: Vec::new(),
+        };
+
+        assert!(validate_fan_out(&request(&["a", "b"])).is_ok());
+        assert!(validate_fan_out(&request(&[])).is_err());
+        assert!(validate_fan_out(&request(&["a", "template"])).is_err());
+        assert!(validate_fan_out(&request(&["a", "b", "a"])).is_err());
+    }
+}
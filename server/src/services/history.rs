@@ -0,0 +1,793 @@
+use crate::error::PreviewError;
+use crate::models::backup::{Backup, BackupSummary, ServiceBackup};
+use crate::models::history::{
+    ApplyOrigin, HistoryEntry, HistoryQuery, HistorySummary, PreviewChange, PreviewChangeKind,
+};
+use crate::models::job::JobStatus;
+use crate::models::migrate::{DiffEntry, ProjectConfig};
+use crate::models::plan::{MigrationPlan, PlanEntry};
+use crate::services::now_rfc3339;
+use crate::services::reconcile::{ServicePlan, plan_diffs};
+use crate::services::redact::Redactor;
+
+use rusqlite::types::Type;
+use rusqlite::{Connection, OptionalExtension, Row, params};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use uuid::Uuid;
+
+const DEFAULT_LIMIT: u32 = 50;
+
+const SCHEMA: &str = "
+create table if not exists history (
+    id text primary key,
+    kind text not null,
+    actor text,
+    approved_by text,
+    created_at text not null,
+    source_ref text,
+    dest_ref text not null,
+    services text not null,
+    status text not null,
+    diffs text not null,
+    outcome text
+);
+create index if not exists history_created_at on history (created_at);
+create table if not exists backups (
+    id text primary key,
+    project_ref text not null,
+    created_at text not null,
+    services text not null
+);
+create table if not exists plans (
+    id text primary key,
+    source_ref text not null,
+    dest_ref text not null,
+    created_at text not null,
+    plan text not null,
+    service_plans text not null
+);
+create table if not exists settings (
+    key text primary key,
+    value text not null
+);
+";
+
+const SUMMARY_COLUMNS: &str = "id, kind, actor, created_at, source_ref, dest_ref, services, status";
+
+/// Audit log of every preview and apply, the backups taken before applies and migration
+/// plans, kept in a local SQLite database.
+#[derive(Clone)]
+pub struct HistoryStore {
+    connection: Arc<Mutex<Connection>>,
+}
+
+impl HistoryStore {
+    pub fn open(path: &str) -> Result<Self, rusqlite::Error> {
+        Self::init(Connection::open(path)?)
+    }
+
+    #[cfg(test)]
+    pub fn open_in_memory() -> Result<Self, rusqlite::Error> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(connection: Connection) -> Result<Self, rusqlite::Error> {
+        connection.execute_batch(SCHEMA)?;
+        // Databases created before plan approvals lack the column
+        if connection
+            .prepare("select approved_by from history limit 0")
+            .is_err()
+        {
+            connection.execute_batch("alter table history add column approved_by text")?;
+        }
+        if connection
+            .prepare("select plan_id from history limit 0")
+            .is_err()
+        {
+            connection.execute_batch("alter table history add column plan_id text")?;
+        }
+        Ok(Self {
+            connection: Arc::new(Mutex::new(connection)),
+        })
+    }
+
+    pub fn record(&self, entry: &HistoryEntry) -> Result<(), PreviewError> {
+        let outcome = entry.outcome.as_ref().map(Value::to_string);
+        self.with_connection(|connection| {
+            connection.execute(
+                "insert into history
+                 (id, kind, actor, created_at, source_ref, dest_ref, services, status, diffs, outcome,
+                  approved_by, plan_id)
+                 values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+                params![
+                    entry.id,
+                    to_text(&entry.kind),
+                    entry.actor,
+                    entry.created_at,
+                    entry.source_ref,
+                    entry.dest_ref,
+                    to_text(&entry.services),
+                    to_text(&entry.status),
+                    to_text(&entry.diffs),
+                    outcome,
+                    entry.approved_by,
+                    entry.plan_id,
+                ],
+            )
+        })
+        .map(|_| ())
+    }
+
+    /// Records the final status and report of an entry that was recorded before it finished,
+    /// such as a background apply.
+    pub fn finish(&self, id: &str, status: JobStatus, outcome: &Value) -> Result<(), PreviewError> {
+        self.with_connection(|connection| {
+            connection.execute(
+                "update history set status = ?2, outcome = ?3 where id = ?1",
+                params![id, to_text(&status), outcome.to_string()],
+            )
+        })
+        .map(|_| ())
+    }
+
+    /// Lists entries newest first, filtered by the query and limited to entries whose live
+    /// projects are all in `projects`. Uploaded snapshots, recorded as `snapshot:<ref>`, are
+    /// not live projects and need no access.
+    pub fn list(
+        &self,
+        query: &HistoryQuery,
+        projects: &[String],
+    ) -> Result<Vec<HistorySummary>, PreviewError> {
+        let kind = query.kind.as_ref().map(to_text);
+        let limit = query.limit.unwrap_or(DEFAULT_LIMIT);
+        let projects = to_text(&projects);
+
+        self.with_connection(|connection| {
+            let mut statement = connection.prepare(&format!(
+                "select {} from history
+                 where (?1 is null or kind = ?1)
+                   and (?2 is null or source_ref = ?2 or dest_ref = ?2)
+                   and (?3 is null or actor = ?3)
+                   and (dest_ref like 'snapshot:%' or dest_ref in (select value from json_each(?5)))
+                   and (source_ref is null or source_ref like 'snapshot:%'
+                        or source_ref in (select value from json_each(?5)))
+                 order by created_at desc, rowid desc
+                 limit ?4",
+                SUMMARY_COLUMNS
+            ))?;
+            let rows = statement.query_map(
+                params![kind, query.project_ref, query.actor, limit, projects],
+                summary_from_row,
+            )?;
+            rows.collect()
+        })
+    }
+
+    pub fn get(&self, id: &str) -> Result<Option<HistoryEntry>, PreviewError> {
+        self.with_connection(|connection| {
+            connection
+                .query_row(
+                    &format!(
+                        "select {}, diffs, outcome, approved_by, plan_id from history where id = ?1",
+                        SUMMARY_COLUMNS
+                    ),
+                    params![id],
+                    |row| {
+                        let summary = summary_from_row(row)?;
+                        let outcome: Option<String> = row.get(9)?;
+                        Ok(HistoryEntry {
+                            id: summary.id,
+                            kind: summary.kind,
+                            actor: summary.actor,
+                            approved_by: row.get(10)?,
+                            plan_id: row.get(11)?,
+                            created_at: summary.created_at,
+                            source_ref: summary.source_ref,
+                            dest_ref: summary.dest_ref,
+                            services: summary.services,
+                            status: summary.status,
+                            diffs: from_text(row, 8)?,
+                            outcome: outcome
+                                .map(|outcome| serde_json::from_str(&outcome))
+                                .transpose()
+                                .map_err(|e| {
+                                    rusqlite::Error::FromSqlConversionFailure(
+                                        9,
+                                        Type::Text,
+                                        Box::new(e),
+                                    )
+                                })?,
+                        })
+                    },
+                )
+                .optional()
+        })
+    }
+
+    pub fn record_backup(&self, backup: &Backup) -> Result<(), PreviewError> {
+        self.with_connection(|connection| {
+            connection.execute(
+                "insert into backups (id, project_ref, created_at, services)
+                 values (?1, ?2, ?3, ?4)",
+                params![
+                    backup.id,
+                    backup.project_ref,
+                    backup.created_at,
+                    to_text(&backup.services),
+                ],
+            )
+        })
+        .map(|_| ())
+    }
+
+    /// Lists the backups of the given projects, newest first.
+    pub fn list_backups(&self, projects: &[String]) -> Result<Vec<BackupSummary>, PreviewError> {
+        let projects = to_text(&projects);
+        self.with_connection(|connection| {
+            let mut statement = connection.prepare(
+                "select id, project_ref, created_at, services from backups
+                 where project_ref in (select value from json_each(?1))
+                 order by created_at desc, rowid desc",
+            )?;
+            let rows = statement.query_map(params![projects], |row| {
+                let services: Vec<ServiceBackup> = from_text(row, 3)?;
+                Ok(BackupSummary {
+                    id: row.get(0)?,
+                    project_ref: row.get(1)?,
+                    created_at: row.get(2)?,
+                    services: services.iter().map(|s| s.service).collect(),
+                })
+            })?;
+            rows.collect()
+        })
+    }
+
+    pub fn get_backup(&self, id: &str) -> Result<Option<Backup>, PreviewError> {
+        self.with_connection(|connection| {
+            connection
+                .query_row(
+                    "select id, project_ref, created_at, services from backups where id = ?1",
+                    params![id],
+                    |row| {
+                        Ok(Backup {
+                            id: row.get(0)?,
+                            project_ref: row.get(1)?,
+                            created_at: row.get(2)?,
+                            services: from_text(row, 3)?,
+                        })
+                    },
+                )
+                .optional()
+        })
+    }
+
+    pub fn record_plan(&self, entry: &PlanEntry) -> Result<(), PreviewError> {
+        self.with_connection(|connection| {
+            connection.execute(
+                "insert into plans (id, source_ref, dest_ref, created_at, plan, service_plans)
+                 values (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    entry.plan.id,
+                    entry.plan.source_ref,
+                    entry.plan.dest_ref,
+                    entry.plan.created_at,
+                    to_text(&entry.plan),
+                    to_text(&entry.service_plans),
+                ],
+            )
+        })
+        .map(|_| ())
+    }
+
+    /// Lists the plans whose source and destination are both in `projects`, newest first.
+    pub fn list_plans(&self, projects: &[String]) -> Result<Vec<MigrationPlan>, PreviewError> {
+        let projects = to_text(&projects);
+        self.with_connection(|connection| {
+            let mut statement = connection.prepare(
+                "select plan from plans
+                 where source_ref in (select value from json_each(?1))
+                   and dest_ref in (select value from json_each(?1))
+                 order by created_at desc, rowid desc",
+            )?;
+            let rows = statement.query_map(params![projects], |row| from_text(row, 0))?;
+            rows.collect()
+        })
+    }
+
+    pub fn get_plan(&self, id: &str) -> Result<Option<PlanEntry>, PreviewError> {
+        self.with_connection(|connection| read_plan(connection, id))
+    }
+
+    /// Applies a change to a stored plan and writes it back, holding the database lock in
+    /// between so concurrent approvals and applies see each other's changes. Returns the
+    /// updated plan, or `None` when there is no such plan.
+    pub fn update_plan(
+        &self,
+        id: &str,
+        change: impl FnOnce(&mut PlanEntry) -> Result<(), PreviewError>,
+    ) -> Result<Option<MigrationPlan>, PreviewError> {
+        let connection = self.lock()?;
+        let Some(mut entry) = read_plan(&connection, id).map_err(query_error)? else {
+            return Ok(None);
+        };
+
+        change(&mut entry)?;
+        connection
+            .execute(
+                "update plans set plan = ?2 where id = ?1",
+                params![id, to_text(&entry.plan)],
+            )
+            .map_err(query_error)?;
+        Ok(Some(entry.plan))
+    }
+
+    /// The salt masked values are hashed with, created on first use and kept so masks stay
+    /// comparable across restarts.
+    pub fn redaction_salt(&self) -> Result<String, PreviewError> {
+        self.with_connection(|connection| {
+            connection.execute(
+                "insert or ignore into settings (key, value) values ('redaction_salt', ?1)",
+                params![Uuid::new_v4().to_string()],
+            )?;
+            connection.query_row(
+                "select value from settings where key = 'redaction_salt'",
+                [],
+                |row| row.get(0),
+            )
+        })
+    }
+
+    fn with_connection<T>(
+        &self,
+        action: impl FnOnce(&Connection) -> Result<T, rusqlite::Error>,
+    ) -> Result<T, PreviewError> {
+        let connection = self.lock()?;
+        action(&connection).map_err(query_error)
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, Connection>, PreviewError> {
+        self.connection
+            .lock()
+            .map_err(|e| PreviewError::ApiError(format!("Failed to open history: {:?}", e)))
+    }
+}
+
+/// Records an entry, logging instead of failing the request when the history cannot be
+/// written.
+pub fn record_entry(store: &HistoryStore, entry: &HistoryEntry) {
+    if let Err(e) = store.record(entry) {
+        eprintln!("Failed to record history entry {}: {:?}", entry.id, e);
+    }
+}
+
+/// Starts the history entry of an apply of `plans`, with the masked differences it resolves.
+/// Every apply gets its own id, so retrying an approved plan records a new entry under the
+/// same `plan_id`.
+pub fn apply_entry(
+    actor: Option<String>,
+    origin: &ApplyOrigin,
+    plans: &[ServicePlan],
+    redactor: &Redactor,
+) -> Result<HistoryEntry, PreviewError> {
+    let mut diffs = plan_diffs(plans)?;
+    redactor.redact_configs(&mut diffs);
+
+    Ok(HistoryEntry {
+        id: Uuid::new_v4().to_string(),
+        kind: origin.kind,
+        actor,
+        approved_by: origin
+            .approval
+            .as_ref()
+            .map(|approval| approval.approved_by.clone()),
+        plan_id: origin
+            .approval
+            .as_ref()
+            .map(|approval| approval.plan_id.clone()),
+        created_at: now_rfc3339(),
+        source_ref: origin.source_ref.clone(),
+        dest_ref: origin.project_ref.clone(),
+        services: plans
+            .iter()
+            .map(|plan| plan.service.name().to_string())
+            .collect(),
+        status: JobStatus::Succeeded,
+        diffs,
+        outcome: None,
+    })
+}
+
+fn query_error(e: rusqlite::Error) -> PreviewError {
+    PreviewError::ApiError(format!("History query failed: {:?}", e))
+}
+
+fn read_plan(connection: &Connection, id: &str) -> Result<Option<PlanEntry>, rusqlite::Error> {
+    connection
+        .query_row(
+            "select plan, service_plans from plans where id = ?1",
+            params![id],
+            |row| {
+                Ok(PlanEntry {
+                    plan: from_text(row, 0)?,
+                    service_plans: from_text(row, 1)?,
+                })
+            },
+        )
+        .optional()
+}
+
+fn summary_from_row(row: &Row) -> Result<HistorySummary, rusqlite::Error> {
+    Ok(HistorySummary {
+        id: row.get(0)?,
+        kind: from_text(row, 1)?,
+        actor: row.get(2)?,
+        created_at: row.get(3)?,
+        source_ref: row.get(4)?,
+        dest_ref: row.get(5)?,
+        services: from_text(row, 6)?,
+        status: from_text(row, 7)?,
+    })
+}
+
+// Enums and lists are stored as their JSON form, with the quotes of plain strings dropped so
+// the columns stay readable and filterable.
+fn to_text<T: Serialize>(value: &T) -> String {
+    match serde_json::to_value(value) {
+        Ok(Value::String(text)) => text,
+        Ok(other) => other.to_string(),
+        Err(_) => String::new(),
+    }
+}
+
+fn from_text<T: DeserializeOwned>(row: &Row, index: usize) -> Result<T, rusqlite::Error> {
+    let text: String = row.get(index)?;
+    serde_json::from_str(&text)
+        .or_else(|_| serde_json::from_value(Value::String(text)))
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(index, Type::Text, Box::new(e)))
+}
+
+/// What changed between the diffs of two previews: keys that started or stopped differing,
+/// and keys whose values moved.
+pub fn compare_previews(before: &[ProjectConfig], after: &[ProjectConfig]) -> Vec<PreviewChange> {
+    let index = |configs: &[ProjectConfig]| -> BTreeMap<(String, String), DiffEntry> {
+        configs
+            .iter()
+            .flat_map(|config| {
+                config
+                    .diffs
+                    .iter()
+                    .map(move |diff| ((config.name.clone(), diff.key.clone()), diff.clone()))
+            })
+            .collect()
+    };
+    let mut before = index(before);
+    let after = index(after);
+    let mut changes = Vec::new();
+
+    for ((service, key), after_diff) in after {
+        let before_diff = before.remove(&(service.clone(), key.clone()));
+        let change = match &before_diff {
+            None => PreviewChangeKind::Appeared,
+            Some(diff)
+                if diff.source_value != after_diff.source_value
+                    || diff.dest_value != after_diff.dest_value =>
+            {
+                PreviewChangeKind::Changed
+            }
+            Some(_) => continue,
+        };
+        changes.push(PreviewChange {
+            service,
+            key,
+            change,
+            before: before_diff,
+            after: Some(after_diff),
+        });
+    }
+
+    changes.extend(
+        before
+            .into_iter()
+            .map(|((service, key), before_diff)| PreviewChange {
+                service,
+                key,
+                change: PreviewChangeKind::Resolved,
+                before: Some(before_diff),
+                after: None,
+            }),
+    );
+    changes.sort_by(|a, b| (&a.service, &a.key).cmp(&(&b.service, &b.key)));
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::history::{HistoryKind, PlanApproval};
+    use crate::models::plan::PlanStatus;
+    use crate::services::Service;
+    use serde_json::json;
+
+    fn diff(key: &str, source: &str, dest: &str) -> DiffEntry {
+        DiffEntry {
+            key: key.to_string(),
+            source_value: source.to_string(),
+            dest_value: dest.to_string(),
+            text_diff: None,
+        }
+    }
+
+    fn entry(id: &str, kind: HistoryKind, created_at: &str) -> HistoryEntry {
+        HistoryEntry {
+            id: id.to_string(),
+            kind,
+            actor: Some("ops@example.com".to_string()),
+            approved_by: None,
+            plan_id: None,
+            created_at: created_at.to_string(),
+            source_ref: Some("src".to_string()),
+            dest_ref: "dst".to_string(),
+            services: vec!["Auth".to_string()],
+            status: JobStatus::Succeeded,
+            diffs: vec![ProjectConfig {
+                name: "Auth".to_string(),
+                diffs: vec![diff("site_url", "\"a\"", "\"b\"")],
+            }],
+            outcome: None,
+        }
+    }
+
+    #[test]
+    fn test_record_list_and_get() {
+        let store = HistoryStore::open_in_memory().unwrap();
+        store
+            .record(&entry("1", HistoryKind::Preview, "2025-01-01T00:00:00Z"))
+            .unwrap();
+        store
+            .record(&entry("2", HistoryKind::Apply, "2025-01-02T00:00:00Z"))
+            .unwrap();
+        store
+            .finish("2", JobStatus::Failed, &json!({"project_ref": "dst"}))
+            .unwrap();
+
+        let projects = vec!["src".to_string(), "dst".to_string()];
+        let all = store.list(&HistoryQuery::default(), &projects).unwrap();
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[0].id, "2");
+        assert_eq!(all[0].status, JobStatus::Failed);
+
+        let previews = store
+            .list(
+                &HistoryQuery {
+                    kind: Some(HistoryKind::Preview),
+                    project_ref: Some("dst".to_string()),
+                    ..Default::default()
+                },
+                &projects,
+            )
+            .unwrap();
+        assert_eq!(previews.len(), 1);
+        assert_eq!(previews[0].id, "1");
+
+        // Entries are only listed when both of their projects are accessible
+        let mut snapshot = entry("4", HistoryKind::Preview, "2025-01-04T00:00:00Z");
+        snapshot.source_ref = Some("snapshot:src".to_string());
+        store.record(&snapshot).unwrap();
+        let dest_only = store
+            .list(&HistoryQuery::default(), &["dst".to_string()])
+            .unwrap();
+        assert_eq!(dest_only.len(), 1);
+        assert_eq!(dest_only[0].id, "4");
+
+        let mut approved = entry("3", HistoryKind::Apply, "2025-01-03T00:00:00Z");
+        approved.approved_by = Some("lead@example.com".to_string());
+        store.record(&approved).unwrap();
+        assert_eq!(
+            store.get("3").unwrap().unwrap().approved_by.as_deref(),
+            Some("lead@example.com")
+        );
+
+        let apply = store.get("2").unwrap().unwrap();
+        assert_eq!(apply.outcome, Some(json!({"project_ref": "dst"})));
+        assert_eq!(apply.diffs[0].diffs[0].key, "site_url");
+        assert!(store.get("missing").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_backups_are_listed_per_project() {
+        let store = HistoryStore::open_in_memory().unwrap();
+        for (id, project_ref) in [("b1", "dst"), ("b2", "other")] {
+            store
+                .record_backup(&Backup {
+                    id: id.to_string(),
+                    project_ref: project_ref.to_string(),
+                    created_at: "2025-01-01T00:00:00Z".to_string(),
+                    services: vec![ServiceBackup {
+                        service: Service::Auth,
+                        keys: vec!["site_url".to_string()],
+                        value: json!({"site_url": "https://old.example.com"}),
+                    }],
+                })
+                .unwrap();
+        }
+
+        let listed = store.list_backups(&["dst".to_string()]).unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].id, "b1");
+        assert_eq!(listed[0].services, vec![Service::Auth]);
+
+        let backup = store.get_backup("b2").unwrap().unwrap();
+        assert_eq!(backup.project_ref, "other");
+        assert_eq!(
+            backup.services[0].value["site_url"],
+            "https://old.example.com"
+        );
+        assert!(store.get_backup("missing").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_plans_survive_in_the_store() {
+        let store = HistoryStore::open_in_memory().unwrap();
+        let plan: MigrationPlan = serde_json::from_value(json!({
+            "id": "plan",
+            "source_ref": "stg",
+            "dest_ref": "prd",
+            "services": ["auth"],
+            "status": "pending_approval",
+            "created_at": "2025-01-01T00:00:00Z",
+            "created_by": {"id": "author", "name": "author@example.com"},
+            "approved_at": null,
+            "approved_by": null,
+            "applied_at": null,
+            "dest_fingerprint": "abc",
+            "invalidated_reason": null,
+            "configs": [],
+            "operations": [],
+            "policy": []
+        }))
+        .unwrap();
+        store
+            .record_plan(&PlanEntry {
+                plan,
+                service_plans: Vec::new(),
+            })
+            .unwrap();
+
+        let projects = vec!["stg".to_string(), "prd".to_string()];
+        assert_eq!(store.list_plans(&projects).unwrap().len(), 1);
+        assert!(store.list_plans(&["prd".to_string()]).unwrap().is_empty());
+
+        let updated = store
+            .update_plan("plan", |entry| {
+                entry.plan.status = PlanStatus::Approved;
+                Ok(())
+            })
+            .unwrap()
+            .unwrap();
+        assert_eq!(updated.status, PlanStatus::Approved);
+        assert_eq!(
+            store.get_plan("plan").unwrap().unwrap().plan.status,
+            PlanStatus::Approved
+        );
+
+        // A refused change leaves the stored plan as it was
+        let refused = store.update_plan("plan", |entry| {
+            entry.plan.status = PlanStatus::Applied;
+            Err(PreviewError::Conflict("no".to_string()))
+        });
+        assert!(refused.is_err());
+        assert_eq!(
+            store.get_plan("plan").unwrap().unwrap().plan.status,
+            PlanStatus::Approved
+        );
+        assert!(store.update_plan("missing", |_| Ok(())).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_redaction_salt_is_kept() {
+        let store = HistoryStore::open_in_memory().unwrap();
+
+        let salt = store.redaction_salt().unwrap();
+        assert!(!salt.is_empty());
+        assert_eq!(store.redaction_salt().unwrap(), salt);
+    }
+
+    #[test]
+    fn test_applies_record_masked_diffs() {
+        let store = HistoryStore::open_in_memory().unwrap();
+        let origin = ApplyOrigin {
+            kind: HistoryKind::Apply,
+            source_ref: Some("src".to_string()),
+            project_ref: "dst".to_string(),
+            approval: None,
+        };
+        let plans = vec![ServicePlan {
+            service: Service::Auth,
+            operations: Vec::new(),
+            source: json!({"site_url": "https://new.example.com", "smtp_pass": "hunter2"}),
+            dest: json!({"site_url": "https://old.example.com", "smtp_pass": "old"}),
+        }];
+
+        let entry = apply_entry(None, &origin, &plans, &Redactor::default()).unwrap();
+        store.record(&entry).unwrap();
+
+        let recorded = store.get(&entry.id).unwrap().unwrap();
+        assert_eq!(recorded.diffs[0].name, "Auth");
+        assert_eq!(recorded.diffs[0].diffs.len(), 2);
+        assert!(
+            recorded.diffs[0]
+                .diffs
+                .iter()
+                .all(|diff| !diff.source_value.contains("hunter2"))
+        );
+    }
+
+    #[test]
+    fn test_retried_plan_applies_get_their_own_entries() {
+        let store = HistoryStore::open_in_memory().unwrap();
+        let origin = ApplyOrigin {
+            kind: HistoryKind::Apply,
+            source_ref: Some("src".to_string()),
+            project_ref: "dst".to_string(),
+            approval: Some(PlanApproval {
+                plan_id: "plan-1".to_string(),
+                approved_by: "lead@example.com".to_string(),
+            }),
+        };
+        let plans = vec![ServicePlan {
+            service: Service::Auth,
+            operations: Vec::new(),
+            source: json!({"site_url": "https://new.example.com"}),
+            dest: json!({"site_url": "https://old.example.com"}),
+        }];
+
+        let first = apply_entry(None, &origin, &plans, &Redactor::default()).unwrap();
+        let retry = apply_entry(None, &origin, &plans, &Redactor::default()).unwrap();
+        store.record(&first).unwrap();
+        store.record(&retry).unwrap();
+
+        assert_ne!(first.id, retry.id);
+        let recorded = store.get(&retry.id).unwrap().unwrap();
+        assert_eq!(recorded.plan_id.as_deref(), Some("plan-1"));
+        assert_eq!(recorded.approved_by.as_deref(), Some("lead@example.com"));
+    }
+
+    #[test]
+    fn test_compare_previews() {
+        let before = vec![ProjectConfig {
+            name: "Auth".to_string(),
+            diffs: vec![
+                diff("site_url", "\"a\"", "\"b\""),
+                diff("jwt_exp", "3600", "600"),
+                diff("disable_signup", "true", "false"),
+            ],
+        }];
+        let after = vec![ProjectConfig {
+            name: "Auth".to_string(),
+            diffs: vec![
+                diff("site_url", "\"a\"", "\"c\""),
+                diff("jwt_exp", "3600", "600"),
+                diff("smtp_host", "\"smtp\"", "null"),
+            ],
+        }];
+
+        let changes = compare_previews(&before, &after);
+
+        let kinds: Vec<(&str, PreviewChangeKind)> = changes
+            .iter()
+            .map(|change| (change.key.as_str(), change.change))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ("disable_signup", PreviewChangeKind::Resolved),
+                ("site_url", PreviewChangeKind::Changed),
+                ("smtp_host", PreviewChangeKind::Appeared),
+            ]
+        );
+    }
+}
@@ -0,0 +1,453 @@
+use crate::error::PreviewError;
+use crate::models::AppState;
+use crate::models::history::HistoryEntry;
+use crate::models::job::{Job, JobEntry, JobEvent, JobStatus, JobStep};
+use crate::models::migrate::{ApplyReport, ApplyResponse, ApplyResult, Operation};
+use crate::services::history::record_entry;
+use crate::services::management_api::ManagementApi;
+use crate::services::notify::{migration_notification, notify};
+use crate::services::now_rfc3339;
+use crate::services::reconcile::{ServicePlan, execute_operation, store_backup};
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::sync::broadcast;
+
+// Events buffered per job for slow subscribers. Subscribers that fall further behind skip
+// ahead and can re-read the job for the full state.
+const EVENT_BUFFER: usize = 256;
+
+// Finished jobs kept in memory for clients to read back. Their outcome stays in the history
+// once they are evicted.
+const RETAINED_FINISHED_JOBS: usize = 100;
+
+/// Stores the backup for the plans, registers a queued job for them and starts executing it
+/// in the background. The job takes the id of its history entry, which is recorded as queued
+/// and completed when the job finishes. Returns the job as queued.
+pub fn start_job(
+    app_state: &AppState,
+    api: ManagementApi,
+    plans: Vec<ServicePlan>,
+    history: HistoryEntry,
+) -> Result<Job, PreviewError> {
+    let project_id = history.dest_ref.clone();
+    let backup_id = store_backup(app_state, &project_id, &plans)?;
+
+    let steps = job_steps(&plans);
+    let operations: Vec<Operation> = plans.into_iter().flat_map(|plan| plan.operations).collect();
+
+    let job = Job {
+        id: history.id.clone(),
+        project_ref: project_id.clone(),
+        actor: history.actor.clone(),
+        status: JobStatus::Queued,
+        created_at: now_rfc3339(),
+        finished_at: None,
+        backup_id,
+        steps,
+        logs: Vec::new(),
+        summary: None,
+    };
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let (events, _) = broadcast::channel(EVENT_BUFFER);
+
+    {
+        let mut jobs = app_state
+            .jobs
+            .lock()
+            .map_err(|e| PreviewError::ApiError(format!("Failed to store job: {:?}", e)))?;
+        evict_finished_jobs(&mut jobs);
+        jobs.insert(
+            job.id.clone(),
+            JobEntry {
+                job: job.clone(),
+                events,
+                cancelled: cancelled.clone(),
+            },
+        );
+    }
+
+    let history = HistoryEntry {
+        status: JobStatus::Queued,
+        ..history
+    };
+    record_entry(&app_state.history, &history);
+
+    tokio::spawn(run_job(
+        app_state.clone(),
+        api,
+        history,
+        operations,
+        cancelled,
+    ));
+
+    Ok(job)
+}
+
+// Drops the oldest finished jobs beyond the retention limit. Running jobs are always kept.
+fn evict_finished_jobs(jobs: &mut HashMap<String, JobEntry>) {
+    let mut finished: Vec<(String, String)> = jobs
+        .values()
+        .filter(|entry| entry.job.status.is_finished())
+        .map(|entry| (entry.job.created_at.clone(), entry.job.id.clone()))
+        .collect();
+    if finished.len() <= RETAINED_FINISHED_JOBS {
+        return;
+    }
+
+    finished.sort();
+    let excess = finished.len() - RETAINED_FINISHED_JOBS;
+    for (_, id) in finished.into_iter().take(excess) {
+        jobs.remove(&id);
+    }
+}
+
+/// Requests cancellation of a job. The step currently executing is finished first; the
+/// remaining steps are marked cancelled.
+pub fn cancel_job(app_state: &AppState, job_id: &str) -> Result<Job, PreviewError> {
+    update_job(app_state, job_id, |entry| {
+        if entry.job.status.is_finished() {
+            return Vec::new();
+        }
+        entry.cancelled.store(true, Ordering::SeqCst);
+        vec![JobEvent::Log {
+            message: "Cancellation requested".to_string(),
+        }]
+    })?
+    .ok_or_else(|| PreviewError::NotFound(format!("Job {} not found", job_id)))
+}
+
+async fn run_job(
+    app_state: AppState,
+    api: ManagementApi,
+    history: HistoryEntry,
+    operations: Vec<Operation>,
+    cancelled: Arc<AtomicBool>,
+) {
+    let job_id = &history.id;
+    let project_id = &history.dest_ref;
+    let total = operations.len();
+    log_update_error(update_job(&app_state, job_id, |entry| {
+        entry.job.status = JobStatus::Running;
+        vec![JobEvent::Log {
+            message: format!("Applying {} steps to {}", total, project_id),
+        }]
+    }));
+
+    for (index, operation) in operations.into_iter().enumerate() {
+        if cancelled.load(Ordering::SeqCst) {
+            break;
+        }
+
+        log_update_error(update_job(&app_state, job_id, |entry| {
+            let step = &mut entry.job.steps[index];
+            step.status = JobStatus::Running;
+            vec![JobEvent::Step(step.clone())]
+        }));
+
+        let results = execute_operation(&api, project_id, operation).await;
+
+        log_update_error(update_job(&app_state, job_id, |entry| {
+            let step = &mut entry.job.steps[index];
+            step.status = step_status(&results);
+            step.results = results;
+
+            let mut events = vec![JobEvent::Step(step.clone())];
+            events.extend(step.results.iter().map(|result| JobEvent::Log {
+                message: match &result.error {
+                    None => format!("{}: applied {}", step.service, result.key),
+                    Some(error) => format!("{}: failed {}: {}", step.service, result.key, error),
+                },
+            }));
+            events
+        }));
+    }
+
+    let finished = update_job(&app_state, job_id, |entry| {
+        let job = &mut entry.job;
+        if cancelled.load(Ordering::SeqCst) {
+            for step in &mut job.steps {
+                if step.status == JobStatus::Queued {
+                    step.status = JobStatus::Cancelled;
+                }
+            }
+        }
+        job.status = final_status(&job.steps);
+        job.finished_at = Some(now_rfc3339());
+        job.summary = Some(summarize(job));
+
+        match serde_json::to_value(&job.summary) {
+            Ok(outcome) => {
+                if let Err(e) = app_state.history.finish(&job.id, job.status, &outcome) {
+                    eprintln!("Failed to record job {} in history: {:?}", job.id, e);
+                }
+            }
+            Err(e) => eprintln!("Failed to serialize job summary: {:?}", e),
+        }
+
+        vec![JobEvent::Finished {
+            status: job.status,
+            summary: job.summary.clone(),
+        }]
+    });
+
+    if let Ok(Some(Job {
+        status,
+        summary: Some(report),
+        ..
+    })) = &finished
+    {
+        notify(
+            &app_state.config.notifications,
+            migration_notification(&history, *status, report),
+        );
+    }
+    log_update_error(finished);
+}
+
+/// Applies a change to a job under the lock and publishes the events it returns. Log events
+/// are also appended to the job's log. Returns the updated job, or `None` if it is unknown.
+fn update_job(
+    app_state: &AppState,
+    job_id: &str,
+    change: impl FnOnce(&mut JobEntry) -> Vec<JobEvent>,
+) -> Result<Option<Job>, PreviewError> {
+    let mut jobs = app_state
+        .jobs
+        .lock()
+        .map_err(|e| PreviewError::ApiError(format!("Failed to update job: {:?}", e)))?;
+    let Some(entry) = jobs.get_mut(job_id) else {
+        return Ok(None);
+    };
+
+    for event in change(entry) {
+        if let JobEvent::Log { message } = &event {
+            entry.job.logs.push(message.clone());
+        }
+        // Sending only fails when nobody is following the job
+        let _ = entry.events.send(event);
+    }
+
+    Ok(Some(entry.job.clone()))
+}
+
+fn log_update_error(outcome: Result<Option<Job>, PreviewError>) {
+    if let Err(e) = outcome {
+        eprintln!("Failed to update job: {:?}", e);
+    }
+}
+
+/// One queued step per planned operation, in execution order.
+fn job_steps(plans: &[ServicePlan]) -> Vec<JobStep> {
+    plans
+        .iter()
+        .flat_map(|plan| {
+            plan.operations
+                .iter()
+                .map(move |operation| (plan.service.name(), operation))
+        })
+        .enumerate()
+        .map(|(index, (service, operation))| JobStep {
+            index,
+            service: service.to_string(),
+            keys: match operation {
+                Operation::Skip { key, .. } => vec![key.clone()],
+                _ => operation
+                    .written_keys()
+                    .into_iter()
+                    .map(str::to_string)
+                    .collect(),
+            },
+            status: JobStatus::Queued,
+            results: Vec::new(),
+        })
+        .collect()
+}
+
+fn step_status(results: &[ApplyResult]) -> JobStatus {
+    if results.iter().all(|result| result.success) {
+        JobStatus::Succeeded
+    } else {
+        JobStatus::Failed
+    }
+}
+
+fn final_status(steps: &[JobStep]) -> JobStatus {
+    if steps.iter().any(|step| step.status == JobStatus::Cancelled) {
+        JobStatus::Cancelled
+    } else if steps.iter().any(|step| step.status == JobStatus::Failed) {
+        JobStatus::Failed
+    } else {
+        JobStatus::Succeeded
+    }
+}
+
+/// Builds the same report a synchronous apply returns from the executed steps.
+fn summarize(job: &Job) -> ApplyReport {
+    let mut services: Vec<ApplyResponse> = Vec::new();
+
+    for step in &job.steps {
+        match services.last_mut() {
+            Some(last) if last.service == step.service => {
+                last.results.extend(step.results.iter().cloned())
+            }
+            _ => services.push(ApplyResponse {
+                service: step.service.clone(),
+                results: step.results.clone(),
+            }),
+        }
+    }
+
+    ApplyReport {
+        project_ref: job.project_ref.clone(),
+        backup_id: job.backup_id.clone(),
+        services,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::migrate::ApplyStep;
+    use crate::services::Service;
+    use serde_json::json;
+
+    fn sql(key: &str) -> Operation {
+        Operation::Sql(ApplyStep {
+            key: key.to_string(),
+            statement: format!("select '{}'", key),
+        })
+    }
+
+    fn result(key: &str, success: bool) -> ApplyResult {
+        ApplyResult {
+            key: key.to_string(),
+            statement: String::new(),
+            success,
+            error: (!success).then(|| "boom".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_job_steps_follow_plan_order() {
+        let plans = vec![
+            ServicePlan {
+                service: Service::Extensions,
+                operations: vec![sql("installed.vector")],
+                source: json!({}),
+                dest: json!({}),
+            },
+            ServicePlan {
+                service: Service::Roles,
+                operations: vec![
+                    sql("roles.app"),
+                    Operation::Skip {
+                        key: "roles.x".to_string(),
+                        reason: "unsupported".to_string(),
+                    },
+                ],
+                source: json!({}),
+                dest: json!({}),
+            },
+        ];
+
+        let steps = job_steps(&plans);
+
+        assert_eq!(steps.len(), 3);
+        assert_eq!(steps[0].service, "Extensions");
+        assert_eq!(steps[1].keys, vec!["roles.app"]);
+        assert_eq!(steps[2].index, 2);
+        assert_eq!(steps[2].keys, vec!["roles.x"]);
+        assert!(steps.iter().all(|step| step.status == JobStatus::Queued));
+    }
+
+    #[test]
+    fn test_final_status_and_summary() {
+        let step =
+            |index: usize, service: &str, status: JobStatus, results: Vec<ApplyResult>| JobStep {
+                index,
+                service: service.to_string(),
+                keys: Vec::new(),
+                status,
+                results,
+            };
+        let mut job = Job {
+            id: "job".to_string(),
+            project_ref: "dst".to_string(),
+            actor: None,
+            status: JobStatus::Running,
+            created_at: String::new(),
+            finished_at: None,
+            backup_id: Some("backup".to_string()),
+            steps: vec![
+                step(
+                    0,
+                    "Roles",
+                    JobStatus::Succeeded,
+                    vec![result("roles.a", true)],
+                ),
+                step(
+                    1,
+                    "Roles",
+                    JobStatus::Failed,
+                    vec![result("roles.b", false)],
+                ),
+                step(
+                    2,
+                    "Cron",
+                    JobStatus::Succeeded,
+                    vec![result("nightly", true)],
+                ),
+            ],
+            logs: Vec::new(),
+            summary: None,
+        };
+
+        assert_eq!(final_status(&job.steps), JobStatus::Failed);
+
+        let summary = summarize(&job);
+        assert_eq!(summary.backup_id.as_deref(), Some("backup"));
+        assert_eq!(summary.services.len(), 2);
+        assert_eq!(summary.services[0].results.len(), 2);
+        assert_eq!(summary.services[1].service, "Cron");
+
+        job.steps[2].status = JobStatus::Cancelled;
+        assert_eq!(final_status(&job.steps), JobStatus::Cancelled);
+    }
+
+    #[test]
+    fn test_evict_finished_jobs_keeps_running_and_newest() {
+        let entry = |id: usize, status: JobStatus| {
+            let (events, _) = broadcast::channel(1);
+            JobEntry {
+                job: Job {
+                    id: id.to_string(),
+                    project_ref: "dst".to_string(),
+                    actor: None,
+                    status,
+                    created_at: format!("2025-01-01T00:{:02}:{:02}Z", id / 60, id % 60),
+                    finished_at: None,
+                    backup_id: None,
+                    steps: Vec::new(),
+                    logs: Vec::new(),
+                    summary: None,
+                },
+                events,
+                cancelled: Arc::new(AtomicBool::new(false)),
+            }
+        };
+        let mut jobs: HashMap<String, JobEntry> = (0..RETAINED_FINISHED_JOBS + 5)
+            .map(|id| (id.to_string(), entry(id, JobStatus::Succeeded)))
+            .collect();
+        jobs.insert("0".to_string(), entry(0, JobStatus::Running));
+
+        evict_finished_jobs(&mut jobs);
+
+        assert_eq!(jobs.len(), RETAINED_FINISHED_JOBS + 1);
+        assert!(jobs.contains_key("0"));
+        assert!(!jobs.contains_key("1"));
+        assert!(!jobs.contains_key("4"));
+        assert!(jobs.contains_key("5"));
+    }
+}
@@ -0,0 +1,91 @@
+use crate::error::PreviewError;
+
+use reqwest::header::{ACCEPT, AUTHORIZATION};
+use serde_json::Value;
+use tower_sessions::Session;
+
+const BASE_URL: &str = "https://api.supabase.com/v1";
+
+/// Authenticated Supabase Management API client. Holds the access token itself so it can
+/// outlive the request that created it, e.g. in background jobs.
+#[derive(Clone)]
+pub struct ManagementApi {
+    token: String,
+    client: reqwest::Client,
+}
+
+impl ManagementApi {
+    pub fn new(token: String) -> Self {
+        Self {
+            token,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Builds a client from the access token stored in the session by the OAuth callback.
+    pub async fn from_session(session: &Session) -> Result<Self, PreviewError> {
+        let token_option: Option<String> =
+            session.get("supabase_access_token").await.map_err(|e| {
+                PreviewError::SessionError(format!("Failed to get token from session: {:?}", e))
+            })?;
+
+        let token = token_option.ok_or(PreviewError::Unauthorized)?;
+        Ok(Self::new(token))
+    }
+
+    pub async fn get(&self, url: String) -> Result<String, PreviewError> {
+        self.request(reqwest::Method::GET, url, None).await
+    }
+
+    pub async fn post(&self, url: String, body: &Value) -> Result<String, PreviewError> {
+        self.request(reqwest::Method::POST, url, Some(body)).await
+    }
+
+    pub async fn send(
+        &self,
+        method: reqwest::Method,
+        url: String,
+        body: &Value,
+    ) -> Result<String, PreviewError> {
+        self.request(method, url, Some(body)).await
+    }
+
+    async fn request(
+        &self,
+        method: reqwest::Method,
+        url: String,
+        body: Option<&Value>,
+    ) -> Result<String, PreviewError> {
+        let constructed_url = format!("{}{}", BASE_URL, url);
+
+        let mut request = self
+            .client
+            .request(method, &constructed_url)
+            .header(AUTHORIZATION, format!("Bearer {}", self.token))
+            .header(ACCEPT, "application/json");
+        if let Some(body) = body {
+            request = request.json(body);
+        }
+
+        let api_response = request
+            .send()
+            .await
+            .map_err(|e| PreviewError::ApiError(format!("Request failed: {:?}", e)))?;
+
+        if api_response.status().is_success() {
+            api_response.text().await.map_err(|e| {
+                PreviewError::ApiError(format!("Error reading response body as text: {:?}", e))
+            })
+        } else {
+            let status_code = api_response.status().as_u16();
+            let error_text = api_response
+                .text()
+                .await
+                .unwrap_or_else(|e| format!("Error reading response body: {}", e));
+            Err(PreviewError::ApiError(format!(
+                "HTTP request failed with status {}: {}",
+                status_code, error_text
+            )))
+        }
+    }
+}
@@ -0,0 +1,273 @@
+use crate::error::PreviewError;
+use crate::models::merge::{MergeChange, MergeEntry, ServiceMerge};
+use crate::models::snapshot::ProjectSnapshot;
+use crate::models::transform::TransformConfig;
+use crate::services::diff::format_value;
+use crate::services::management_api::ManagementApi;
+use crate::services::reconcile::{ServicePlan, plan_service};
+use crate::services::snapshot::snapshot_config;
+use crate::services::transform::transform_source;
+use crate::services::{Service, fetch_service_config};
+
+use serde_json::{Map, Value};
+use std::collections::BTreeSet;
+
+/// Compares both live projects with the baseline they were last in sync at, for every service
+/// in the baseline, and plans only the changes made on the source side. The baseline should
+/// hold destination values, e.g. a snapshot of the destination taken right after the last
+/// sync, since the source is transformed for the destination before it is compared.
+pub async fn plan_merge(
+    api: &ManagementApi,
+    baseline: &ProjectSnapshot,
+    source_id: &str,
+    dest_id: &str,
+    transforms: &TransformConfig,
+) -> Result<(Vec<ServiceMerge>, Vec<ServicePlan>), PreviewError> {
+    let rules = transforms.rules_for(source_id, dest_id);
+    let mut merges = Vec::new();
+    let mut plans = Vec::new();
+
+    for (service, base) in &baseline.services {
+        let source = live_config(api, *service, source_id).await?;
+        let dest = live_config(api, *service, dest_id).await?;
+        let source = transform_source(rules, *service, source, &dest);
+
+        let (merged, entries) = merge_values(base, &source, &dest);
+        if !entries.is_empty() {
+            merges.push(ServiceMerge {
+                name: service.name().to_string(),
+                entries,
+            });
+        }
+        plans.push(ServicePlan {
+            service: *service,
+            operations: plan_service(*service, &merged, &dest, source_id, dest_id),
+            source: merged,
+            dest,
+        });
+    }
+
+    Ok((merges, plans))
+}
+
+/// Number of keys both sides changed differently.
+pub fn conflict_count(merges: &[ServiceMerge]) -> usize {
+    merges
+        .iter()
+        .flat_map(|merge| &merge.entries)
+        .filter(|entry| entry.change == MergeChange::Conflict)
+        .count()
+}
+
+async fn live_config(
+    api: &ManagementApi,
+    service: Service,
+    project_id: &str,
+) -> Result<Value, PreviewError> {
+    let live = fetch_service_config(api, service, project_id)
+        .await
+        .map_err(|e| {
+            PreviewError::ApiError(format!("Failed to get {} config: {:?}", service, e))
+        })?;
+    Ok(snapshot_config(service, live))
+}
+
+/// Classifies every key that changed since `base` and returns the destination with the
+/// source side changes applied. Keys both sides changed differently keep the destination
+/// value.
+pub fn merge_values(base: &Value, source: &Value, dest: &Value) -> (Value, Vec<MergeEntry>) {
+    let mut entries = Vec::new();
+    let merged = merge_value("", Some(base), Some(source), Some(dest), &mut entries)
+        .unwrap_or_else(|| dest.clone());
+    (merged, entries)
+}
+
+/// Merges one key, where `None` means the side does not have it. Objects and arrays of
+/// items with an `id` are merged member by member so keys match those of a preview.
+fn merge_value(
+    path: &str,
+    base: Option<&Value>,
+    source: Option<&Value>,
+    dest: Option<&Value>,
+    entries: &mut Vec<MergeEntry>,
+) -> Option<Value> {
+    if source == base && dest == base {
+        return dest.cloned();
+    }
+
+    match (base, source, dest) {
+        (None | Some(Value::Object(_)), Some(Value::Object(src)), Some(Value::Object(dst))) => {
+            let base = base.and_then(Value::as_object);
+            let keys: BTreeSet<&String> = src
+                .keys()
+                .chain(dst.keys())
+                .chain(base.into_iter().flat_map(Map::keys))
+                .collect();
+
+            let mut merged = Map::new();
+            for key in keys {
+                if let Some(value) = merge_value(
+                    &join(path, key),
+                    base.and_then(|base| base.get(key)),
+                    src.get(key),
+                    dst.get(key),
+                    entries,
+                ) {
+                    merged.insert(key.clone(), value);
+                }
+            }
+            return Some(Value::Object(merged));
+        }
+        (_, Some(Value::Array(src)), Some(Value::Array(dst))) => {
+            let base = base.and_then(Value::as_array);
+            if let (Some(src_ids), Some(dst_ids), Some(base_ids)) = (
+                item_ids(src),
+                item_ids(dst),
+                base.map_or(Some(Vec::new()), |b| item_ids(b)),
+            ) {
+                // Destination order first, then items new to the source
+                let mut ids: Vec<&str> = Vec::new();
+                for id in dst_ids.into_iter().chain(src_ids).chain(base_ids) {
+                    if !ids.contains(&id) {
+                        ids.push(id);
+                    }
+                }
+
+                let merged = ids
+                    .into_iter()
+                    .filter_map(|id| {
+                        merge_value(
+                            &join(path, &format!("id:{}", id)),
+                            base.and_then(|base| find_by_id(base, id)),
+                            find_by_id(src, id),
+                            find_by_id(dst, id),
+                            entries,
+                        )
+                    })
+                    .collect();
+                return Some(Value::Array(merged));
+            }
+        }
+        _ => {}
+    }
+
+    let (change, merged) = if source == dest {
+        (MergeChange::Unchanged, dest)
+    } else if source == base {
+        (MergeChange::ChangedInDest, dest)
+    } else if dest == base {
+        (MergeChange::ChangedInSource, source)
+    } else {
+        (MergeChange::Conflict, dest)
+    };
+    entries.push(MergeEntry {
+        key: if path.is_empty() { "root" } else { path }.to_string(),
+        change,
+        baseline_value: display(base),
+        source_value: display(source),
+        dest_value: display(dest),
+    });
+    merged.cloned()
+}
+
+/// The `id` of every item, or `None` unless every item has one.
+fn item_ids(items: &[Value]) -> Option<Vec<&str>> {
+    items
+        .iter()
+        .map(|item| item.get("id").and_then(Value::as_str))
+        .collect()
+}
+
+fn find_by_id<'a>(items: &'a [Value], id: &str) -> Option<&'a Value> {
+    items
+        .iter()
+        .find(|item| item.get("id").and_then(Value::as_str) == Some(id))
+}
+
+fn display(value: Option<&Value>) -> String {
+    value.map_or_else(|| "null".to_string(), format_value)
+}
+
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn changes(entries: &[MergeEntry]) -> Vec<(&str, MergeChange)> {
+        entries
+            .iter()
+            .map(|entry| (entry.key.as_str(), entry.change))
+            .collect()
+    }
+
+    #[test]
+    fn test_classifies_each_side() {
+        let base = json!({"jwt_exp": 3600, "site_url": "https://a.example.com", "mfa": false, "smtp": "x", "otp": 60});
+        let source = json!({"jwt_exp": 7200, "site_url": "https://a.example.com", "mfa": true, "smtp": "y", "otp": 60});
+        let dest = json!({"jwt_exp": 3600, "site_url": "https://b.example.com", "mfa": true, "smtp": "z", "otp": 60});
+
+        let (merged, entries) = merge_values(&base, &source, &dest);
+
+        assert_eq!(
+            changes(&entries),
+            vec![
+                ("jwt_exp", MergeChange::ChangedInSource),
+                ("mfa", MergeChange::Unchanged),
+                ("site_url", MergeChange::ChangedInDest),
+                ("smtp", MergeChange::Conflict),
+            ]
+        );
+        // Only the source side change reaches the destination
+        assert_eq!(
+            merged,
+            json!({"jwt_exp": 7200, "site_url": "https://b.example.com", "mfa": true, "smtp": "z", "otp": 60})
+        );
+        assert_eq!(entries[3].baseline_value, "x");
+        assert_eq!(entries[3].source_value, "y");
+        assert_eq!(entries[3].dest_value, "z");
+    }
+
+    #[test]
+    fn test_merges_items_by_id() {
+        let base = json!([{"id": "a", "verify_jwt": true}, {"id": "b", "verify_jwt": true}]);
+        let source = json!([{"id": "a", "verify_jwt": false}, {"id": "c", "verify_jwt": true}]);
+        let dest = json!([{"id": "b", "verify_jwt": true}, {"id": "a", "verify_jwt": true}, {"id": "d", "verify_jwt": true}]);
+
+        let (merged, entries) = merge_values(&base, &source, &dest);
+
+        assert_eq!(
+            changes(&entries),
+            vec![
+                ("id:b", MergeChange::ChangedInSource),
+                ("id:a.verify_jwt", MergeChange::ChangedInSource),
+                ("id:d", MergeChange::ChangedInDest),
+                ("id:c", MergeChange::ChangedInSource),
+            ]
+        );
+        assert_eq!(
+            merged,
+            json!([{"id": "a", "verify_jwt": false}, {"id": "d", "verify_jwt": true}, {"id": "c", "verify_jwt": true}])
+        );
+    }
+
+    #[test]
+    fn test_source_removal_of_changed_key_conflicts() {
+        let base = json!({"hook": {"enabled": true, "uri": "a"}});
+        let source = json!({});
+        let dest = json!({"hook": {"enabled": true, "uri": "b"}});
+
+        let (merged, entries) = merge_values(&base, &source, &dest);
+
+        assert_eq!(changes(&entries), vec![("hook", MergeChange::Conflict)]);
+        assert_eq!(entries[0].source_value, "null");
+        assert_eq!(merged, dest);
+    }
+}
@@ -0,0 +1,250 @@
+pub mod backup;
+pub mod cron;
+pub mod database;
+pub mod diff;
+pub mod drift;
+pub mod extensions;
+pub mod fanout;
+pub mod history;
+pub mod jobs;
+pub mod management_api;
+pub mod merge;
+pub mod notify;
+pub mod plans;
+pub mod policy;
+pub mod profile;
+pub mod providers;
+pub mod realtime;
+pub mod reconcile;
+pub mod redact;
+pub mod report;
+pub mod roles;
+pub mod selection;
+pub mod snapshot;
+pub mod transform;
+pub mod triggers;
+
+use crate::error::PreviewError;
+use crate::models::migrate::ProjectConfig;
+use crate::models::snapshot::ProjectSnapshot;
+use crate::models::transform::TransformConfig;
+use crate::services::diff::json_diff;
+use crate::services::management_api::ManagementApi;
+use crate::services::transform::transform_source;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+
+/// Every project configuration area the tool can read, compare and snapshot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Service {
+    Auth,
+    Postgrest,
+    EdgeFunctions,
+    Secrets,
+    Postgres,
+    Extensions,
+    Roles,
+    Triggers,
+    Cron,
+    Realtime,
+}
+
+impl Service {
+    pub const ALL: [Service; 10] = [
+        Service::Auth,
+        Service::Postgrest,
+        Service::EdgeFunctions,
+        Service::Secrets,
+        Service::Postgres,
+        Service::Extensions,
+        Service::Roles,
+        Service::Triggers,
+        Service::Cron,
+        Service::Realtime,
+    ];
+
+    /// Name used for `ProjectConfig.name` in preview responses.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Service::Auth => "Auth",
+            Service::Postgrest => "Postgrest",
+            Service::EdgeFunctions => "EdgeFunctions",
+            Service::Secrets => "Secrets",
+            Service::Postgres => "Postgres",
+            Service::Extensions => "Extensions",
+            Service::Roles => "Roles",
+            Service::Triggers => "Triggers",
+            Service::Cron => "Cron",
+            Service::Realtime => "Realtime",
+        }
+    }
+
+    /// Key used for query parameters and snapshot documents.
+    pub fn key(&self) -> &'static str {
+        match self {
+            Service::Auth => "auth",
+            Service::Postgrest => "postgrest",
+            Service::EdgeFunctions => "edge_functions",
+            Service::Secrets => "secrets",
+            Service::Postgres => "postgres",
+            Service::Extensions => "extensions",
+            Service::Roles => "roles",
+            Service::Triggers => "triggers",
+            Service::Cron => "cron",
+            Service::Realtime => "realtime",
+        }
+    }
+}
+
+impl fmt::Display for Service {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.key())
+    }
+}
+
+/// Reads the current configuration of one service for a project.
+pub async fn fetch_service_config(
+    api: &ManagementApi,
+    service: Service,
+    project_id: &str,
+) -> Result<Value, PreviewError> {
+    let api_path = match service {
+        Service::Auth => "config/auth",
+        Service::Postgrest => "postgrest",
+        Service::EdgeFunctions => "functions",
+        Service::Secrets => "secrets",
+        Service::Postgres => "config/database/postgres",
+        Service::Extensions => return extensions::fetch_extensions(api, project_id).await,
+        Service::Roles => return roles::fetch_roles(api, project_id).await,
+        Service::Triggers => return triggers::fetch_triggers(api, project_id).await,
+        Service::Cron => return cron::fetch_cron_jobs(api, project_id).await,
+        Service::Realtime => return realtime::fetch_realtime(api, project_id).await,
+    };
+
+    let response = api
+        .get(format!("/projects/{}/{}", project_id, api_path))
+        .await?;
+    Ok(serde_json::from_str(&response)?)
+}
+
+/// One side of a preview.
+#[derive(Clone, Copy)]
+pub enum PreviewSide<'a> {
+    /// A live project, read through the Management API.
+    Project(&'a str),
+    /// Live configs of a project that were read already, such as a fan-out source shared by
+    /// every destination. Services missing from `configs` are read from the project.
+    Fetched {
+        project_id: &'a str,
+        configs: &'a BTreeMap<Service, Value>,
+    },
+    /// An uploaded snapshot. The live side is normalized to the snapshot form before the
+    /// two are compared, and services the snapshot lacks are left out, as are credentials
+    /// it does not carry.
+    Snapshot(&'a ProjectSnapshot),
+}
+
+impl PreviewSide<'_> {
+    /// The project the side stands for, used to pick transform rules.
+    pub fn project_ref(&self) -> &str {
+        match self {
+            PreviewSide::Project(project_id) | PreviewSide::Fetched { project_id, .. } => {
+                project_id
+            }
+            PreviewSide::Snapshot(snapshot) => &snapshot.project_ref,
+        }
+    }
+
+    async fn read(
+        &self,
+        api: &ManagementApi,
+        service: Service,
+    ) -> Result<Option<Value>, PreviewError> {
+        let project_id = match self {
+            PreviewSide::Snapshot(snapshot) => return Ok(snapshot.services.get(&service).cloned()),
+            PreviewSide::Fetched { configs, .. } if configs.contains_key(&service) => {
+                return Ok(configs.get(&service).cloned());
+            }
+            PreviewSide::Project(project_id) | PreviewSide::Fetched { project_id, .. } => {
+                project_id
+            }
+        };
+
+        fetch_service_config(api, service, project_id)
+            .await
+            .map(Some)
+            .map_err(|e| {
+                PreviewError::ApiError(format!("Failed to get {} config: {:?}", service, e))
+            })
+    }
+}
+
+/// Diffs the selected services of two sides after applying the pair's transform rules,
+/// leaving out services that match. Every preview, whether of live projects, a snapshot
+/// or a fan-out destination, goes through here.
+pub async fn preview_services(
+    api: &ManagementApi,
+    services: &[Service],
+    source: PreviewSide<'_>,
+    dest: PreviewSide<'_>,
+    transforms: &TransformConfig,
+) -> Result<Vec<ProjectConfig>, PreviewError> {
+    let mut configs = Vec::new();
+    let rules = transforms.rules_for(source.project_ref(), dest.project_ref());
+    let snapshot_source = matches!(source, PreviewSide::Snapshot(_));
+    let snapshot_dest = matches!(dest, PreviewSide::Snapshot(_));
+
+    for service in services {
+        let (Some(source), Some(dest)) = (
+            source.read(api, *service).await?,
+            dest.read(api, *service).await?,
+        ) else {
+            continue;
+        };
+        let (mut source, mut dest) = if snapshot_source || snapshot_dest {
+            (
+                snapshot::snapshot_config(*service, source),
+                snapshot::snapshot_config(*service, dest),
+            )
+        } else {
+            (source, dest)
+        };
+        if snapshot_source {
+            snapshot::drop_unset_secrets(*service, &mut source, &mut dest);
+        } else if snapshot_dest {
+            snapshot::drop_unset_secrets(*service, &mut dest, &mut source);
+        }
+
+        let source = transform_source(rules, *service, source, &dest);
+        if let Some(config) = json_diff(service.name().to_string(), source, dest).await? {
+            configs.push(config);
+        }
+    }
+
+    Ok(configs)
+}
+
+/// Reads a JSON or YAML configuration file. `what` names the file in error messages.
+pub fn load_config_file<T: DeserializeOwned>(path: &str, what: &str) -> Result<T, String> {
+    let body = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {} {}: {}", what, path, e))?;
+    match serde_json::from_str(&body) {
+        Ok(config) => Ok(config),
+        Err(_) => {
+            serde_yaml::from_str(&body).map_err(|e| format!("Invalid {} {}: {}", what, path, e))
+        }
+    }
+}
+
+pub fn now_rfc3339() -> String {
+    OffsetDateTime::now_utc()
+        .format(&Rfc3339)
+        .unwrap_or_default()
+}
@@ -0,0 +1,340 @@
+use crate::error::PreviewError;
+use crate::models::drift::{DriftChange, DriftStatus};
+use crate::models::history::{HistoryEntry, HistoryKind, PreviewChangeKind};
+use crate::models::job::JobStatus;
+use crate::models::migrate::ApplyReport;
+use crate::models::notification::{Notification, NotificationEvent, NotificationSink, SinkKind};
+use crate::services::now_rfc3339;
+
+use serde_json::{Value, json};
+use std::time::Duration;
+
+// Longest list of per-key lines included in a notification
+const MAX_DETAILS: usize = 10;
+
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Reads the sinks from comma separated URL lists, one list per sink kind.
+pub fn parse_sinks(json_urls: Option<&str>, slack_urls: Option<&str>) -> Vec<NotificationSink> {
+    let sinks = |urls: Option<&str>, kind: SinkKind| {
+        urls.unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|url| !url.is_empty())
+            .map(move |url| NotificationSink {
+                kind,
+                url: url.to_string(),
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let mut all = sinks(json_urls, SinkKind::Json);
+    all.extend(sinks(slack_urls, SinkKind::Slack));
+    all
+}
+
+/// Posts a notification to every sink in the background. Failures are logged and never
+/// affect the migration or check that triggered them.
+pub fn notify(sinks: &[NotificationSink], notification: Notification) {
+    if sinks.is_empty() {
+        return;
+    }
+
+    let sinks = sinks.to_vec();
+    tokio::spawn(async move {
+        let client = reqwest::Client::new();
+        for sink in &sinks {
+            if let Err(e) = deliver(&client, sink, &notification).await {
+                eprintln!("Failed to notify {}: {}", sink.url, e);
+            }
+        }
+    });
+}
+
+pub async fn deliver(
+    client: &reqwest::Client,
+    sink: &NotificationSink,
+    notification: &Notification,
+) -> Result<(), PreviewError> {
+    let body = match sink.kind {
+        SinkKind::Json => serde_json::to_value(notification)?,
+        SinkKind::Slack => slack_payload(notification),
+    };
+
+    let response = client
+        .post(&sink.url)
+        .timeout(DELIVERY_TIMEOUT)
+        .json(&body)
+        .send()
+        .await
+        .map_err(|e| PreviewError::ApiError(format!("Request failed: {:?}", e)))?;
+
+    if !response.status().is_success() {
+        return Err(PreviewError::ApiError(format!(
+            "Webhook responded with status {}",
+            response.status().as_u16()
+        )));
+    }
+    Ok(())
+}
+
+/// Describes a finished apply. `entry` is its history entry, which carries the project refs
+/// and services.
+pub fn migration_notification(
+    entry: &HistoryEntry,
+    status: JobStatus,
+    report: &ApplyReport,
+) -> Notification {
+    let event = match status {
+        JobStatus::Cancelled => NotificationEvent::MigrationCancelled,
+        JobStatus::Succeeded => NotificationEvent::MigrationSucceeded,
+        _ => NotificationEvent::MigrationFailed,
+    };
+    let action = match entry.kind {
+        HistoryKind::Rollback => "Rollback",
+        _ => "Migration",
+    };
+    let outcome = match event {
+        NotificationEvent::MigrationSucceeded => "succeeded",
+        NotificationEvent::MigrationCancelled => "was cancelled",
+        _ => "failed",
+    };
+
+    let results: Vec<_> = report
+        .services
+        .iter()
+        .flat_map(|service| {
+            service
+                .results
+                .iter()
+                .map(move |result| (service.service.as_str(), result))
+        })
+        .collect();
+    let failed = results.iter().filter(|(_, result)| !result.success).count();
+
+    // Failures first, since they are what the reader has to act on
+    let mut details: Vec<String> = results
+        .iter()
+        .filter(|(_, result)| !result.success)
+        .map(|(service, result)| {
+            format!(
+                "{}: {} failed: {}",
+                service,
+                result.key,
+                result.error.as_deref().unwrap_or("unknown error")
+            )
+        })
+        .chain(
+            results
+                .iter()
+                .filter(|(_, result)| result.success)
+                .map(|(service, result)| format!("{}: {} applied", service, result.key)),
+        )
+        .collect();
+    truncate_details(&mut details);
+
+    Notification {
+        event,
+        title: format!("{} to {} {}", action, entry.dest_ref, outcome),
+        source_ref: entry.source_ref.clone(),
+        dest_ref: entry.dest_ref.clone(),
+        services: entry.services.clone(),
+        summary: format!(
+            "{} of {} keys applied, {} failed",
+            results.len() - failed,
+            results.len(),
+            failed
+        ),
+        details,
+        created_at: now_rfc3339(),
+    }
+}
+
+/// Describes a change in a pair's drift, or `None` when nothing moved.
+pub fn drift_notification(status: &DriftStatus) -> Option<Notification> {
+    let (event, verb) = match status.change {
+        DriftChange::Unchanged => return None,
+        DriftChange::Appeared => (NotificationEvent::DriftDetected, "detected"),
+        DriftChange::Changed => (NotificationEvent::DriftChanged, "changed"),
+        DriftChange::Disappeared => (NotificationEvent::DriftResolved, "resolved"),
+    };
+
+    let mut details: Vec<String> = status
+        .changes
+        .iter()
+        .map(|change| {
+            let label = match change.change {
+                PreviewChangeKind::Appeared => "now differs",
+                PreviewChangeKind::Changed => "differs differently",
+                PreviewChangeKind::Resolved => "matches again",
+            };
+            format!("{}: {} {}", change.service, change.key, label)
+        })
+        .collect();
+    truncate_details(&mut details);
+
+    Some(Notification {
+        event,
+        title: format!("Drift {} for {}", verb, status.pair.name),
+        source_ref: Some(status.pair.source.clone()),
+        dest_ref: status.pair.dest.clone(),
+        services: status
+            .pair
+            .services
+            .iter()
+            .map(|service| service.name().to_string())
+            .collect(),
+        summary: format!(
+            "{} differences across {} services",
+            status.diff_count,
+            status.configs.len()
+        ),
+        details,
+        created_at: now_rfc3339(),
+    })
+}
+
+fn truncate_details(details: &mut Vec<String>) {
+    if details.len() > MAX_DETAILS {
+        let hidden = details.len() - MAX_DETAILS;
+        details.truncate(MAX_DETAILS);
+        details.push(format!("…and {} more", hidden));
+    }
+}
+
+/// Formats a notification as a Slack incoming webhook message.
+fn slack_payload(notification: &Notification) -> Value {
+    let projects = match &notification.source_ref {
+        Some(source_ref) => format!("`{}` → `{}`", source_ref, notification.dest_ref),
+        None => format!("`{}`", notification.dest_ref),
+    };
+    let mut text = format!(
+        "*{}*\n{} · {}\n{}",
+        notification.title,
+        projects,
+        notification.services.join(", "),
+        notification.summary
+    );
+    for line in &notification.details {
+        text.push_str("\n• ");
+        text.push_str(line);
+    }
+
+    json!({
+        "text": notification.title,
+        "blocks": [
+            {"type": "section", "text": {"type": "mrkdwn", "text": text}}
+        ]
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::migrate::{ApplyResponse, ApplyResult};
+    use axum::{Json, Router, extract::State, routing::post};
+    use tokio::sync::mpsc;
+
+    fn entry() -> HistoryEntry {
+        HistoryEntry {
+            id: "job".to_string(),
+            kind: HistoryKind::Apply,
+            actor: None,
+            approved_by: None,
+            plan_id: None,
+            created_at: String::new(),
+            source_ref: Some("stg".to_string()),
+            dest_ref: "prd".to_string(),
+            services: vec!["Auth".to_string()],
+            status: JobStatus::Running,
+            diffs: Vec::new(),
+            outcome: None,
+        }
+    }
+
+    fn report() -> ApplyReport {
+        let result = |key: &str, success: bool| ApplyResult {
+            key: key.to_string(),
+            statement: "PATCH /projects/prd/config/auth".to_string(),
+            success,
+            error: (!success).then(|| "HTTP 400".to_string()),
+        };
+        ApplyReport {
+            project_ref: "prd".to_string(),
+            backup_id: None,
+            services: vec![ApplyResponse {
+                service: "Auth".to_string(),
+                results: vec![result("site_url", true), result("smtp_host", false)],
+            }],
+        }
+    }
+
+    #[test]
+    fn test_parse_sinks() {
+        let sinks = parse_sinks(
+            Some("https://a.example.com/hook, ,https://b.example.com/hook"),
+            Some("https://hooks.slack.com/services/x"),
+        );
+
+        assert_eq!(sinks.len(), 3);
+        assert_eq!(sinks[1].url, "https://b.example.com/hook");
+        assert_eq!(sinks[2].kind, SinkKind::Slack);
+        assert!(parse_sinks(None, None).is_empty());
+    }
+
+    #[test]
+    fn test_migration_notification_lists_failures_first() {
+        let notification = migration_notification(&entry(), JobStatus::Failed, &report());
+
+        assert_eq!(notification.event, NotificationEvent::MigrationFailed);
+        assert_eq!(notification.title, "Migration to prd failed");
+        assert_eq!(notification.summary, "1 of 2 keys applied, 1 failed");
+        assert_eq!(
+            notification.details,
+            vec!["Auth: smtp_host failed: HTTP 400", "Auth: site_url applied"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_deliver_to_local_receiver() {
+        let (sender, mut received) = mpsc::unbounded_channel::<Value>();
+        let receiver = Router::new()
+            .route(
+                "/hook",
+                post(
+                    |State(sender): State<mpsc::UnboundedSender<Value>>,
+                     Json(body): Json<Value>| async move {
+                        sender.send(body).unwrap();
+                    },
+                ),
+            )
+            .with_state(sender);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, receiver).await.unwrap() });
+
+        let client = reqwest::Client::new();
+        let notification = migration_notification(&entry(), JobStatus::Failed, &report());
+
+        let json_sink = NotificationSink {
+            kind: SinkKind::Json,
+            url: url.clone(),
+        };
+        deliver(&client, &json_sink, &notification).await.unwrap();
+        let body = received.recv().await.unwrap();
+        assert_eq!(body["event"], "migration_failed");
+        assert_eq!(body["source_ref"], "stg");
+        assert_eq!(body["services"], json!(["Auth"]));
+
+        let slack_sink = NotificationSink {
+            kind: SinkKind::Slack,
+            url,
+        };
+        deliver(&client, &slack_sink, &notification).await.unwrap();
+        let body = received.recv().await.unwrap();
+        assert_eq!(body["text"], "Migration to prd failed");
+        let text = body["blocks"][0]["text"]["text"].as_str().unwrap();
+        assert!(text.contains("`stg` → `prd`"));
+        assert!(text.contains("• Auth: smtp_host failed: HTTP 400"));
+    }
+}
@@ -0,0 +1,333 @@
+use crate::error::PreviewError;
+use crate::models::AppState;
+use crate::models::plan::{MigrationPlan, PlanActor, PlanEntry, PlanRequest, PlanStatus};
+use crate::services::management_api::ManagementApi;
+use crate::services::policy::{enforce, evaluate};
+use crate::services::reconcile::{ServicePlan, describe_plans, plan_config, plan_diffs};
+use crate::services::selection::select_plans;
+use crate::services::snapshot::{build_partial_snapshot, snapshot_config};
+use crate::services::{Service, fetch_service_config, now_rfc3339};
+
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use uuid::Uuid;
+
+/// Plans the selected services of the source onto the destination and stores the plan
+/// pending approval. Plans that change nothing or that the policy blocks are refused.
+pub async fn create_plan(
+    app_state: &AppState,
+    api: &ManagementApi,
+    request: PlanRequest,
+    created_by: PlanActor,
+) -> Result<MigrationPlan, PreviewError> {
+    if request.services.is_empty() {
+        return Err(PreviewError::BadRequest(
+            "A plan needs at least one service".to_string(),
+        ));
+    }
+
+    let declared = build_partial_snapshot(api, &request.source_id, &request.services).await?;
+    let service_plans = plan_config(
+        api,
+        &request.dest_id,
+        &declared,
+        &app_state.config.transforms,
+    )
+    .await?;
+    let service_plans = select_plans(
+        &request.keys,
+        service_plans,
+        &declared.project_ref,
+        &request.dest_id,
+    )?;
+
+    let mut configs = plan_diffs(&service_plans)?;
+    if configs.is_empty() {
+        return Err(PreviewError::BadRequest(format!(
+            "{} already matches {} for the selected services",
+            request.dest_id, request.source_id
+        )));
+    }
+    let policy = evaluate(
+        &app_state.config.policy,
+        &configs,
+        &request.dest_id,
+        &app_state.redactor,
+    );
+    // Approval lifts require-approval rules, so only blocking rules can refuse the plan
+    enforce(&policy, true)?;
+    // The stored plan is what reviewers read, so it only keeps masked values
+    app_state.redactor.redact_configs(&mut configs);
+
+    let plan = MigrationPlan {
+        id: Uuid::new_v4().to_string(),
+        source_ref: request.source_id,
+        dest_ref: request.dest_id,
+        services: declared.services.keys().copied().collect(),
+        keys: request.keys,
+        status: PlanStatus::PendingApproval,
+        created_at: now_rfc3339(),
+        created_by,
+        approved_at: None,
+        approved_by: None,
+        applied_at: None,
+        dest_fingerprint: fingerprint(service_plans.iter().map(|plan| (plan.service, &plan.dest))),
+        invalidated_reason: None,
+        configs,
+        operations: describe_plans(service_plans.clone(), &app_state.redactor),
+        policy,
+    };
+
+    app_state.history.record_plan(&PlanEntry {
+        plan: plan.clone(),
+        service_plans,
+    })?;
+
+    Ok(plan)
+}
+
+/// Approves a pending plan on behalf of someone other than its author, after checking the
+/// destination still matches what the plan was made against.
+pub async fn approve_plan(
+    app_state: &AppState,
+    api: &ManagementApi,
+    plan_id: &str,
+    approver: PlanActor,
+) -> Result<MigrationPlan, PreviewError> {
+    let plan = find_plan(app_state, plan_id)?;
+    ensure_status(&plan, PlanStatus::PendingApproval)?;
+    ensure_second_person(&plan, &approver)?;
+    verify_destination(app_state, api, &plan).await?;
+
+    update_plan(app_state, plan_id, |entry| {
+        ensure_status(&entry.plan, PlanStatus::PendingApproval)?;
+        entry.plan.status = PlanStatus::Approved;
+        entry.plan.approved_at = Some(now_rfc3339());
+        entry.plan.approved_by = Some(approver);
+        Ok(())
+    })
+}
+
+/// Marks an approved plan as applied and returns the operations to run, after checking the
+/// destination has not changed since the plan was made. Claiming first means a plan can
+/// only ever be applied once.
+pub async fn claim_plan(
+    app_state: &AppState,
+    api: &ManagementApi,
+    plan_id: &str,
+) -> Result<(MigrationPlan, Vec<ServicePlan>), PreviewError> {
+    let plan = find_plan(app_state, plan_id)?;
+    ensure_status(&plan, PlanStatus::Approved)?;
+    verify_destination(app_state, api, &plan).await?;
+
+    let mut service_plans = Vec::new();
+    let plan = update_plan(app_state, plan_id, |entry| {
+        ensure_status(&entry.plan, PlanStatus::Approved)?;
+        entry.plan.status = PlanStatus::Applied;
+        entry.plan.applied_at = Some(now_rfc3339());
+        service_plans = entry.service_plans.clone();
+        Ok(())
+    })?;
+
+    Ok((plan, service_plans))
+}
+
+/// Returns a claimed plan to approved when its apply was refused before writing anything.
+pub fn release_plan(app_state: &AppState, plan_id: &str) {
+    let released = update_plan(app_state, plan_id, |entry| {
+        entry.plan.status = PlanStatus::Approved;
+        entry.plan.applied_at = None;
+        Ok(())
+    });
+    if let Err(e) = released {
+        eprintln!("Failed to release plan {}: {:?}", plan_id, e);
+    }
+}
+
+pub fn find_plan(app_state: &AppState, plan_id: &str) -> Result<MigrationPlan, PreviewError> {
+    app_state
+        .history
+        .get_plan(plan_id)?
+        .map(|entry| entry.plan)
+        .ok_or_else(|| PreviewError::NotFound(format!("Plan {} not found", plan_id)))
+}
+
+/// Applies a change to a stored plan and returns the updated plan.
+fn update_plan(
+    app_state: &AppState,
+    plan_id: &str,
+    change: impl FnOnce(&mut PlanEntry) -> Result<(), PreviewError>,
+) -> Result<MigrationPlan, PreviewError> {
+    app_state
+        .history
+        .update_plan(plan_id, change)?
+        .ok_or_else(|| PreviewError::NotFound(format!("Plan {} not found", plan_id)))
+}
+
+/// Invalidates the plan when the live destination no longer matches its fingerprint.
+async fn verify_destination(
+    app_state: &AppState,
+    api: &ManagementApi,
+    plan: &MigrationPlan,
+) -> Result<(), PreviewError> {
+    let live = live_fingerprint(api, &plan.dest_ref, &plan.services).await?;
+    if live == plan.dest_fingerprint {
+        return Ok(());
+    }
+
+    let reason = format!(
+        "{} changed after the plan was created, create a new plan",
+        plan.dest_ref
+    );
+    update_plan(app_state, &plan.id, |entry| {
+        entry.plan.status = PlanStatus::Invalidated;
+        entry.plan.invalidated_reason = Some(reason.clone());
+        Ok(())
+    })?;
+    Err(PreviewError::Conflict(format!(
+        "Plan {} is invalidated: {}",
+        plan.id, reason
+    )))
+}
+
+async fn live_fingerprint(
+    api: &ManagementApi,
+    project_id: &str,
+    services: &[Service],
+) -> Result<String, PreviewError> {
+    let mut dests = Vec::new();
+    // Same order as the plans, which follow the snapshot's sorted services
+    for service in services.iter().copied().collect::<BTreeSet<_>>() {
+        let live = fetch_service_config(api, service, project_id)
+            .await
+            .map_err(|e| {
+                PreviewError::ApiError(format!("Failed to get {} config: {:?}", service, e))
+            })?;
+        dests.push((service, snapshot_config(service, live)));
+    }
+
+    Ok(fingerprint(
+        dests.iter().map(|(service, dest)| (*service, dest)),
+    ))
+}
+
+/// SHA-256 over the destination config of each service, in the order given.
+fn fingerprint<'a>(dests: impl IntoIterator<Item = (Service, &'a Value)>) -> String {
+    let mut hasher = Sha256::new();
+    for (service, dest) in dests {
+        hasher.update(service.key().as_bytes());
+        hasher.update(b"\n");
+        hasher.update(dest.to_string().as_bytes());
+        hasher.update(b"\n");
+    }
+
+    hasher
+        .finalize()
+        .iter()
+        .fold(String::with_capacity(64), |mut hex, byte| {
+            let _ = write!(hex, "{:02x}", byte);
+            hex
+        })
+}
+
+fn ensure_status(plan: &MigrationPlan, expected: PlanStatus) -> Result<(), PreviewError> {
+    if plan.status == expected {
+        return Ok(());
+    }
+
+    let action = match expected {
+        PlanStatus::PendingApproval => "approved",
+        _ => "applied",
+    };
+    let detail = match &plan.invalidated_reason {
+        Some(reason) => format!(": {}", reason),
+        None => String::new(),
+    };
+    Err(PreviewError::Conflict(format!(
+        "Plan {} is {:?} and cannot be {}{}",
+        plan.id, plan.status, action, detail
+    )))
+}
+
+fn ensure_second_person(plan: &MigrationPlan, approver: &PlanActor) -> Result<(), PreviewError> {
+    if approver.id == plan.created_by.id {
+        return Err(PreviewError::Forbidden(
+            "A plan must be approved by someone other than its author".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn actor(id: &str) -> PlanActor {
+        PlanActor {
+            id: id.to_string(),
+            name: format!("{}@example.com", id),
+        }
+    }
+
+    fn plan(status: PlanStatus) -> MigrationPlan {
+        MigrationPlan {
+            id: "plan".to_string(),
+            source_ref: "stg".to_string(),
+            dest_ref: "prd".to_string(),
+            services: vec![Service::Auth],
+            keys: Vec::new(),
+            status,
+            created_at: String::new(),
+            created_by: actor("author"),
+            approved_at: None,
+            approved_by: None,
+            applied_at: None,
+            dest_fingerprint: String::new(),
+            invalidated_reason: None,
+            configs: Vec::new(),
+            operations: Vec::new(),
+            policy: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_fingerprint_tracks_destination_values() {
+        let auth = json!({"site_url": "https://a.example.com", "jwt_exp": 3600});
+        let same = json!({"jwt_exp": 3600, "site_url": "https://a.example.com"});
+        let changed = json!({"site_url": "https://b.example.com", "jwt_exp": 3600});
+
+        let original = fingerprint([(Service::Auth, &auth)]);
+        assert_eq!(original.len(), 64);
+        assert_eq!(original, fingerprint([(Service::Auth, &same)]));
+        assert_ne!(original, fingerprint([(Service::Auth, &changed)]));
+        assert_ne!(original, fingerprint([(Service::Postgrest, &auth)]));
+    }
+
+    #[test]
+    fn test_author_cannot_approve() {
+        let plan = plan(PlanStatus::PendingApproval);
+
+        assert!(matches!(
+            ensure_second_person(&plan, &actor("author")),
+            Err(PreviewError::Forbidden(_))
+        ));
+        assert!(ensure_second_person(&plan, &actor("reviewer")).is_ok());
+    }
+
+    #[test]
+    fn test_status_checks() {
+        assert!(ensure_status(&plan(PlanStatus::Approved), PlanStatus::Approved).is_ok());
+
+        let mut invalidated = plan(PlanStatus::Invalidated);
+        invalidated.invalidated_reason = Some("prd changed".to_string());
+        let error = ensure_status(&invalidated, PlanStatus::Approved).unwrap_err();
+        assert!(matches!(error, PreviewError::Conflict(_)));
+        assert_eq!(
+            error.to_string(),
+            "Plan plan is Invalidated and cannot be applied: prd changed"
+        );
+    }
+}
@@ -0,0 +1,310 @@
+use crate::error::PreviewError;
+use crate::models::migrate::{DiffEntry, ProjectConfig};
+use crate::models::policy::{PolicyCondition, PolicyConfig, PolicyHit, PolicyRule, Severity};
+use crate::services::diff::{format_value, glob_match};
+use crate::services::load_config_file;
+use crate::services::reconcile::{ServicePlan, plan_diffs};
+use crate::services::redact::Redactor;
+
+use std::collections::HashSet;
+
+/// Reads the policy rules, accepting either JSON or YAML. Rule names must be unique.
+pub fn load_policy_config(path: &str) -> Result<PolicyConfig, String> {
+    let config: PolicyConfig = load_config_file(path, "policy config")?;
+
+    let mut names = HashSet::new();
+    for rule in &config.rules {
+        if !names.insert(rule.name.as_str()) {
+            return Err(format!("Duplicate policy rule name: {}", rule.name));
+        }
+        if rule.key.is_empty() {
+            return Err(format!("Policy rule {} has an empty key", rule.name));
+        }
+    }
+
+    Ok(config)
+}
+
+/// Checks a preview against every rule, returning the hits ordered by severity, most severe
+/// first. Conditions are checked on the real values; the hits show them masked.
+pub fn evaluate(
+    policy: &PolicyConfig,
+    configs: &[ProjectConfig],
+    dest_ref: &str,
+    redactor: &Redactor,
+) -> Vec<PolicyHit> {
+    let mut hits = Vec::new();
+
+    for rule in &policy.rules {
+        if !rule.projects.is_empty() && !rule.projects.iter().any(|project| project == dest_ref) {
+            continue;
+        }
+        for config in configs {
+            if rule
+                .service
+                .is_some_and(|service| service.name() != config.name)
+            {
+                continue;
+            }
+            for diff in &config.diffs {
+                if glob_match(&rule.key, &diff.key) && condition_holds(&rule.when, diff) {
+                    let mut shown = diff.clone();
+                    redactor.redact_diff(&config.name, &mut shown);
+                    hits.push(hit(rule, &config.name, &shown));
+                }
+            }
+        }
+    }
+
+    hits.sort_by_key(|hit| std::cmp::Reverse(hit.severity));
+    hits
+}
+
+/// Checks the changes a set of plans would make on `dest_ref`.
+pub fn evaluate_plans(
+    policy: &PolicyConfig,
+    plans: &[ServicePlan],
+    dest_ref: &str,
+    redactor: &Redactor,
+) -> Result<Vec<PolicyHit>, PreviewError> {
+    if policy.rules.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    Ok(evaluate(policy, &plan_diffs(plans)?, dest_ref, redactor))
+}
+
+/// Refuses an apply when any hit blocks, or requires approval and the apply is not of an
+/// approved plan. Warnings never refuse.
+pub fn enforce(hits: &[PolicyHit], approved: bool) -> Result<(), PreviewError> {
+    let refused = |severity: Severity| -> Vec<String> {
+        hits.iter()
+            .filter(|hit| hit.severity == severity)
+            .map(|hit| {
+                format!(
+                    "{} ({} {}): {}",
+                    hit.rule, hit.service, hit.key, hit.message
+                )
+            })
+            .collect()
+    };
+
+    let blocked = refused(Severity::Block);
+    if !blocked.is_empty() {
+        return Err(PreviewError::Forbidden(format!(
+            "Blocked by policy: {}",
+            blocked.join("; ")
+        )));
+    }
+    let unapproved = refused(Severity::RequireApproval);
+    if !approved && !unapproved.is_empty() {
+        return Err(PreviewError::Forbidden(format!(
+            "Approval required by policy, apply an approved plan instead: {}",
+            unapproved.join("; ")
+        )));
+    }
+    Ok(())
+}
+
+fn condition_holds(condition: &PolicyCondition, diff: &DiffEntry) -> bool {
+    let numbers = || {
+        Some((
+            diff.source_value.parse::<f64>().ok()?,
+            diff.dest_value.parse::<f64>().ok()?,
+        ))
+    };
+
+    match condition {
+        PolicyCondition::Changed => true,
+        PolicyCondition::Equals(value) => diff.source_value == format_value(value),
+        PolicyCondition::Decreases => numbers().is_some_and(|(new, current)| new < current),
+        PolicyCondition::Increases => numbers().is_some_and(|(new, current)| new > current),
+    }
+}
+
+fn hit(rule: &PolicyRule, service: &str, diff: &DiffEntry) -> PolicyHit {
+    let message = rule.message.clone().unwrap_or_else(|| match &rule.when {
+        PolicyCondition::Changed => format!("{} changes", diff.key),
+        PolicyCondition::Equals(_) => format!("{} becomes {}", diff.key, diff.source_value),
+        PolicyCondition::Decreases => format!(
+            "{} decreases from {} to {}",
+            diff.key, diff.dest_value, diff.source_value
+        ),
+        PolicyCondition::Increases => format!(
+            "{} increases from {} to {}",
+            diff.key, diff.dest_value, diff.source_value
+        ),
+    });
+
+    PolicyHit {
+        rule: rule.name.clone(),
+        severity: rule.severity,
+        service: service.to_string(),
+        key: diff.key.clone(),
+        source_value: diff.source_value.clone(),
+        dest_value: diff.dest_value.clone(),
+        message,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::Service;
+    use serde_json::json;
+
+    fn rule(name: &str, key: &str, when: PolicyCondition, severity: Severity) -> PolicyRule {
+        PolicyRule {
+            name: name.to_string(),
+            service: None,
+            key: key.to_string(),
+            projects: Vec::new(),
+            when,
+            severity,
+            message: None,
+        }
+    }
+
+    fn diff(key: &str, source_value: &str, dest_value: &str) -> DiffEntry {
+        DiffEntry {
+            key: key.to_string(),
+            source_value: source_value.to_string(),
+            dest_value: dest_value.to_string(),
+            text_diff: None,
+        }
+    }
+
+    #[test]
+    fn test_evaluate_conditions() {
+        let policy = PolicyConfig {
+            rules: vec![
+                rule(
+                    "autoconfirm",
+                    "mailer_autoconfirm",
+                    PolicyCondition::Equals(json!(true)),
+                    Severity::Block,
+                ),
+                rule(
+                    "password-length",
+                    "password_min_length",
+                    PolicyCondition::Decreases,
+                    Severity::RequireApproval,
+                ),
+                rule(
+                    "jwt",
+                    "*.verify_jwt",
+                    PolicyCondition::Equals(json!(false)),
+                    Severity::Warn,
+                ),
+            ],
+        };
+        let configs = vec![
+            ProjectConfig {
+                name: "Auth".to_string(),
+                diffs: vec![
+                    diff("mailer_autoconfirm", "false", "true"),
+                    diff("password_min_length", "6", "12"),
+                ],
+            },
+            ProjectConfig {
+                name: "EdgeFunctions".to_string(),
+                diffs: vec![diff("id:abc.verify_jwt", "false", "true")],
+            },
+        ];
+
+        let hits = evaluate(&policy, &configs, "prd", &Redactor::default());
+
+        // Re-enabling confirmation is not a hit, only the two weakening changes are
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].rule, "password-length");
+        assert_eq!(
+            hits[0].message,
+            "password_min_length decreases from 12 to 6"
+        );
+        assert_eq!(hits[1].severity, Severity::Warn);
+        assert_eq!(hits[1].service, "EdgeFunctions");
+    }
+
+    #[test]
+    fn test_rules_scoped_by_service_and_project() {
+        let mut signups = rule(
+            "prod-signups",
+            "disable_signup",
+            PolicyCondition::Equals(json!(false)),
+            Severity::Block,
+        );
+        signups.service = Some(Service::Auth);
+        signups.projects = vec!["prd".to_string()];
+        let policy = PolicyConfig {
+            rules: vec![signups],
+        };
+        let configs = |name: &str| {
+            vec![ProjectConfig {
+                name: name.to_string(),
+                diffs: vec![diff("disable_signup", "false", "true")],
+            }]
+        };
+
+        let redactor = Redactor::default();
+        assert_eq!(
+            evaluate(&policy, &configs("Auth"), "prd", &redactor).len(),
+            1
+        );
+        assert!(evaluate(&policy, &configs("Auth"), "stg", &redactor).is_empty());
+        assert!(evaluate(&policy, &configs("Postgrest"), "prd", &redactor).is_empty());
+    }
+
+    #[test]
+    fn test_conditions_see_real_secret_values() {
+        let policy = PolicyConfig {
+            rules: vec![rule(
+                "default-password",
+                "smtp_pass",
+                PolicyCondition::Equals(json!("changeme")),
+                Severity::Block,
+            )],
+        };
+        let configs = vec![ProjectConfig {
+            name: "Auth".to_string(),
+            diffs: vec![diff("smtp_pass", "changeme", "hunter2")],
+        }];
+
+        let hits = evaluate(&policy, &configs, "prd", &Redactor::new(Vec::new(), "salt"));
+
+        assert_eq!(hits.len(), 1);
+        assert!(hits[0].source_value.starts_with("[REDACTED "));
+        assert!(!hits[0].message.contains("changeme"));
+    }
+
+    #[test]
+    fn test_enforce() {
+        let policy = PolicyConfig {
+            rules: vec![rule(
+                "any",
+                "*",
+                PolicyCondition::Changed,
+                Severity::RequireApproval,
+            )],
+        };
+        let configs = vec![ProjectConfig {
+            name: "Auth".to_string(),
+            diffs: vec![diff("site_url", "a", "b")],
+        }];
+
+        let mut hits = evaluate(&policy, &configs, "prd", &Redactor::default());
+        let error = enforce(&hits, false).unwrap_err();
+        assert!(matches!(error, PreviewError::Forbidden(_)));
+        assert!(
+            error
+                .to_string()
+                .ends_with(": any (Auth site_url): site_url changes")
+        );
+        assert!(enforce(&hits, true).is_ok());
+
+        hits[0].severity = Severity::Block;
+        assert!(enforce(&hits, true).is_err());
+
+        hits[0].severity = Severity::Warn;
+        assert!(enforce(&hits, false).is_ok());
+    }
+}
@@ -0,0 +1,105 @@
+use crate::error::PreviewError;
+use crate::models::oauth::Profile;
+use crate::models::plan::PlanActor;
+use crate::services::management_api::ManagementApi;
+
+use serde_json::Value;
+use tower_sessions::Session;
+
+const PROFILE_SESSION_KEY: &str = "supabase_profile";
+
+/// Returns the signed in user's profile, fetching it once per session.
+pub async fn session_profile(
+    session: &Session,
+    api: &ManagementApi,
+) -> Result<Profile, PreviewError> {
+    let cached: Option<Profile> = session.get(PROFILE_SESSION_KEY).await.map_err(|e| {
+        PreviewError::SessionError(format!("Failed to get profile from session: {:?}", e))
+    })?;
+    if let Some(profile) = cached {
+        return Ok(profile);
+    }
+
+    let response = api.get("/profile".to_string()).await?;
+    let profile: Profile = serde_json::from_str(&response)?;
+
+    if let Err(e) = session.insert(PROFILE_SESSION_KEY, profile.clone()).await {
+        eprintln!("Failed to store profile in session: {:?}", e);
+    }
+    Ok(profile)
+}
+
+/// Forgets the cached profile, e.g. when the session's token changes.
+pub async fn clear_session_profile(session: &Session) {
+    session.remove::<Profile>(PROFILE_SESSION_KEY).await.ok();
+}
+
+/// Who is making the request, for the audit trail. Falls back to `None` when the profile
+/// cannot be read rather than failing the request.
+pub async fn current_actor(session: &Session, api: &ManagementApi) -> Option<String> {
+    match session_profile(session, api).await {
+        Ok(profile) => Some(display_name(profile)),
+        Err(e) => {
+            eprintln!("Failed to read profile: {:?}", e);
+            None
+        }
+    }
+}
+
+/// The signed in user for decisions that depend on who they are, such as approving a plan.
+/// Unlike `current_actor`, fails when the profile cannot be read.
+pub async fn current_identity(
+    session: &Session,
+    api: &ManagementApi,
+) -> Result<PlanActor, PreviewError> {
+    let profile = session_profile(session, api).await?;
+    if profile.gotrue_id.is_empty() {
+        return Err(PreviewError::ApiError(
+            "The Supabase profile has no account id".to_string(),
+        ));
+    }
+
+    Ok(PlanActor {
+        id: profile.gotrue_id.clone(),
+        name: display_name(profile),
+    })
+}
+
+/// Refs of the projects the signed in user can access. Stored backups, jobs, plans and
+/// history are only shown for these.
+pub async fn accessible_projects(api: &ManagementApi) -> Result<Vec<String>, PreviewError> {
+    let response = api.get("/projects".to_string()).await?;
+    let projects: Vec<Value> = serde_json::from_str(&response)?;
+
+    Ok(projects
+        .iter()
+        .filter_map(|project| project.get("id").and_then(Value::as_str))
+        .map(str::to_string)
+        .collect())
+}
+
+/// Fails with `NotFound` unless the signed in user can access the project, so stored
+/// records of other projects look the same as missing ones.
+pub async fn require_project_access(
+    api: &ManagementApi,
+    project_ref: &str,
+    not_found: impl FnOnce() -> String,
+) -> Result<(), PreviewError> {
+    if accessible_projects(api)
+        .await?
+        .iter()
+        .any(|project| project == project_ref)
+    {
+        Ok(())
+    } else {
+        Err(PreviewError::NotFound(not_found()))
+    }
+}
+
+fn display_name(profile: Profile) -> String {
+    if !profile.primary_email.is_empty() {
+        profile.primary_email
+    } else {
+        profile.username.unwrap_or(profile.gotrue_id)
+    }
+}
@@ -0,0 +1,400 @@
+use crate::error::PreviewError;
+use crate::models::provider::{
+    ProviderAction, ProviderApplyRequest, ProviderDiff, ProviderSettings,
+};
+use crate::models::transform::TransformConfig;
+use crate::services::diff::calculate_diff;
+use crate::services::management_api::ManagementApi;
+use crate::services::reconcile::{ServicePlan, plan_service};
+use crate::services::snapshot::snapshot_config;
+use crate::services::transform::transform_source;
+use crate::services::{Service, fetch_service_config};
+
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, BTreeSet};
+
+const PROVIDER_PREFIX: &str = "external_";
+
+/// Fetches the Auth config of both projects, with the source transformed for the destination.
+pub async fn fetch_auth_configs(
+    api: &ManagementApi,
+    source_id: &str,
+    dest_id: &str,
+    transforms: &TransformConfig,
+) -> Result<(Value, Value), PreviewError> {
+    let mut configs = Vec::new();
+    for project_id in [source_id, dest_id] {
+        let live = fetch_service_config(api, Service::Auth, project_id)
+            .await
+            .map_err(|e| PreviewError::ApiError(format!("Failed to get Auth config: {:?}", e)))?;
+        configs.push(snapshot_config(Service::Auth, live));
+    }
+    let dest = configs.pop().unwrap_or_default();
+    let source = configs.pop().unwrap_or_default();
+    let rules = transforms.rules_for(source_id, dest_id);
+
+    Ok((transform_source(rules, Service::Auth, source, &dest), dest))
+}
+
+/// Groups the differences between two Auth configs by third-party OAuth provider. Providers
+/// are named by their `external_<provider>_enabled` key; providers that match are left out.
+pub fn provider_diffs(source: &Value, dest: &Value) -> Result<Vec<ProviderDiff>, PreviewError> {
+    let providers = provider_names(source, dest);
+    let source_fields = provider_fields(source, &providers);
+    let dest_fields = provider_fields(dest, &providers);
+
+    let mut grouped: BTreeMap<&str, Vec<_>> = BTreeMap::new();
+    for diff in calculate_diff("Auth", source, dest)? {
+        if let Some(provider) = provider_of(&diff.key, &providers) {
+            grouped.entry(provider).or_default().push(diff);
+        }
+    }
+
+    Ok(grouped
+        .into_iter()
+        .map(|(provider, diffs)| {
+            let source = settings(source_fields.get(provider));
+            let dest = settings(dest_fields.get(provider));
+            let action = match (source.enabled, dest.enabled) {
+                (true, false) => ProviderAction::Enable,
+                (false, true) => ProviderAction::Disable,
+                _ => ProviderAction::Update,
+            };
+            let secret_required = source.enabled
+                && (action == ProviderAction::Enable
+                    || source.client_id != dest.client_id
+                    || !dest.secret_present);
+
+            ProviderDiff {
+                provider: provider.to_string(),
+                action,
+                source,
+                dest,
+                secret_required,
+                diffs,
+            }
+        })
+        .collect())
+}
+
+/// Builds the Auth settings to write for the requested providers: the source's settings
+/// without its secret, plus the secret supplied for the destination. Providers being enabled,
+/// getting a new client id or without a destination secret must have a secret supplied.
+fn provider_changes(
+    source: &Value,
+    dest: &Value,
+    request: &ProviderApplyRequest,
+) -> Result<Value, PreviewError> {
+    let diffs = provider_diffs(source, dest)?;
+    let selected: Vec<&ProviderDiff> = if request.providers.is_empty() {
+        diffs.iter().collect()
+    } else {
+        request
+            .providers
+            .iter()
+            .map(|provider| {
+                diffs
+                    .iter()
+                    .find(|diff| diff.provider == *provider)
+                    .ok_or_else(|| {
+                        PreviewError::BadRequest(format!(
+                            "Provider {} has no differences to migrate",
+                            provider
+                        ))
+                    })
+            })
+            .collect::<Result<_, _>>()?
+    };
+    if selected.is_empty() {
+        return Err(PreviewError::BadRequest(
+            "No provider settings differ".to_string(),
+        ));
+    }
+
+    let secrets: BTreeMap<&str, &str> = request
+        .secrets
+        .iter()
+        .filter(|(_, secret)| !secret.trim().is_empty())
+        .map(|(provider, secret)| (provider.as_str(), secret.as_str()))
+        .collect();
+    if let Some(unused) = secrets
+        .keys()
+        .find(|provider| !selected.iter().any(|diff| diff.provider == **provider))
+    {
+        return Err(PreviewError::BadRequest(format!(
+            "A secret was given for {}, which is not being migrated",
+            unused
+        )));
+    }
+    let missing: Vec<&str> = selected
+        .iter()
+        .filter(|diff| diff.secret_required && !secrets.contains_key(diff.provider.as_str()))
+        .map(|diff| diff.provider.as_str())
+        .collect();
+    if !missing.is_empty() {
+        return Err(PreviewError::BadRequest(format!(
+            "A client secret for the destination is required for: {}",
+            missing.join(", ")
+        )));
+    }
+
+    let providers = provider_names(source, dest);
+    let source_fields = provider_fields(source, &providers);
+    let mut changes = Map::new();
+    for diff in selected {
+        let provider = diff.provider.as_str();
+        for (field, value) in source_fields.get(provider).into_iter().flatten() {
+            if field != "secret" {
+                changes.insert(provider_key(provider, field), value.clone());
+            }
+        }
+        if let Some(secret) = secrets.get(provider) {
+            changes.insert(
+                provider_key(provider, "secret"),
+                Value::String(secret.to_string()),
+            );
+        }
+    }
+    Ok(Value::Object(changes))
+}
+
+/// Plans the Auth update for the requested providers, leaving every other setting as it is
+/// on the destination.
+pub fn plan_providers(
+    source: &Value,
+    dest: &Value,
+    request: &ProviderApplyRequest,
+    source_id: &str,
+    dest_id: &str,
+) -> Result<ServicePlan, PreviewError> {
+    let mut declared = dest.clone();
+    if let (Some(declared), Value::Object(changes)) = (
+        declared.as_object_mut(),
+        provider_changes(source, dest, request)?,
+    ) {
+        declared.extend(changes);
+    }
+
+    Ok(ServicePlan {
+        service: Service::Auth,
+        operations: plan_service(Service::Auth, &declared, dest, source_id, dest_id),
+        source: declared,
+        dest: dest.clone(),
+    })
+}
+
+fn provider_names(source: &Value, dest: &Value) -> BTreeSet<String> {
+    [source, dest]
+        .into_iter()
+        .filter_map(Value::as_object)
+        .flat_map(Map::keys)
+        .filter_map(|key| key.strip_prefix(PROVIDER_PREFIX)?.strip_suffix("_enabled"))
+        .map(str::to_string)
+        .collect()
+}
+
+/// The provider a key belongs to. The longest name wins, so `linkedin_oidc` keys are not
+/// mistaken for `linkedin` ones.
+fn provider_of<'a>(key: &str, providers: &'a BTreeSet<String>) -> Option<&'a str> {
+    let rest = key.strip_prefix(PROVIDER_PREFIX)?;
+    providers
+        .iter()
+        .filter(|provider| {
+            rest.strip_prefix(provider.as_str())
+                .is_some_and(|field| field.starts_with('_'))
+        })
+        .max_by_key(|provider| provider.len())
+        .map(String::as_str)
+}
+
+/// Each provider's settings, keyed by the part of the key after the provider name.
+fn provider_fields<'a>(
+    config: &Value,
+    providers: &'a BTreeSet<String>,
+) -> BTreeMap<&'a str, BTreeMap<String, Value>> {
+    let mut fields: BTreeMap<&str, BTreeMap<String, Value>> = BTreeMap::new();
+    for (key, value) in config.as_object().into_iter().flatten() {
+        if let Some(provider) = provider_of(key, providers) {
+            let field = &key[PROVIDER_PREFIX.len() + provider.len() + 1..];
+            fields
+                .entry(provider)
+                .or_default()
+                .insert(field.to_string(), value.clone());
+        }
+    }
+    fields
+}
+
+fn settings(fields: Option<&BTreeMap<String, Value>>) -> ProviderSettings {
+    let Some(fields) = fields else {
+        return ProviderSettings::default();
+    };
+    let text = |field: &str| {
+        fields
+            .get(field)
+            .and_then(Value::as_str)
+            .filter(|value| !value.is_empty())
+            .map(str::to_string)
+    };
+
+    ProviderSettings {
+        enabled: fields.get("enabled").and_then(Value::as_bool) == Some(true),
+        client_id: text("client_id"),
+        secret_present: text("secret").is_some(),
+        redirect_uri: text("redirect_uri"),
+        extra: fields
+            .iter()
+            .filter(|(field, _)| {
+                !matches!(
+                    field.as_str(),
+                    "enabled" | "client_id" | "secret" | "redirect_uri"
+                )
+            })
+            .map(|(field, value)| (field.clone(), value.clone()))
+            .collect(),
+    }
+}
+
+fn provider_key(provider: &str, field: &str) -> String {
+    format!("{}{}_{}", PROVIDER_PREFIX, provider, field)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn configs() -> (Value, Value) {
+        let source = json!({
+            "site_url": "https://staging.example.com",
+            "external_google_enabled": true,
+            "external_google_client_id": "google-staging",
+            "external_google_secret": "staging-secret",
+            "external_google_skip_nonce_check": false,
+            "external_github_enabled": true,
+            "external_github_client_id": "github-id",
+            "external_github_secret": "gh",
+            "external_github_redirect_uri": "https://staging.example.com/callback",
+            "external_linkedin_enabled": false,
+            "external_linkedin_oidc_enabled": true,
+            "external_linkedin_oidc_client_id": "li",
+            "external_linkedin_oidc_secret": "li-secret"
+        });
+        let dest = json!({
+            "site_url": "https://example.com",
+            "external_google_enabled": false,
+            "external_google_client_id": "",
+            "external_google_secret": "",
+            "external_google_skip_nonce_check": false,
+            "external_github_enabled": true,
+            "external_github_client_id": "github-id",
+            "external_github_secret": "gh-prod",
+            "external_github_redirect_uri": "https://example.com/callback",
+            "external_linkedin_enabled": false,
+            "external_linkedin_oidc_enabled": true,
+            "external_linkedin_oidc_client_id": "li",
+            "external_linkedin_oidc_secret": "li-prod"
+        });
+        (source, dest)
+    }
+
+    #[test]
+    fn test_diffs_are_grouped_by_provider() {
+        let (source, dest) = configs();
+
+        let diffs = provider_diffs(&source, &dest).unwrap();
+
+        let providers: Vec<&str> = diffs.iter().map(|diff| diff.provider.as_str()).collect();
+        assert_eq!(providers, vec!["github", "google", "linkedin_oidc"]);
+
+        let google = &diffs[1];
+        assert_eq!(google.action, ProviderAction::Enable);
+        assert!(google.secret_required);
+        assert!(google.source.secret_present);
+        assert!(!google.dest.secret_present);
+        assert_eq!(google.source.client_id.as_deref(), Some("google-staging"));
+        assert_eq!(google.source.extra["skip_nonce_check"], json!(false));
+
+        let github = &diffs[0];
+        assert_eq!(github.action, ProviderAction::Update);
+        assert!(!github.secret_required);
+        assert_eq!(
+            github
+                .diffs
+                .iter()
+                .map(|diff| diff.key.as_str())
+                .collect::<Vec<_>>(),
+            vec!["external_github_redirect_uri", "external_github_secret"]
+        );
+    }
+
+    #[test]
+    fn test_enabling_a_provider_needs_a_secret() {
+        let (source, dest) = configs();
+        let request = |secrets: &[(&str, &str)]| ProviderApplyRequest {
+            providers: vec!["google".to_string()],
+            secrets: secrets
+                .iter()
+                .map(|(provider, secret)| (provider.to_string(), secret.to_string()))
+                .collect(),
+        };
+
+        let missing = provider_changes(&source, &dest, &request(&[])).unwrap_err();
+        assert_eq!(
+            missing.to_string(),
+            "A client secret for the destination is required for: google"
+        );
+        assert!(provider_changes(&source, &dest, &request(&[("github", "x")])).is_err());
+
+        // A provider already enabled on the destination still needs one when it has none
+        let mut unset = dest.clone();
+        unset["external_github_secret"] = json!("");
+        let github = provider_diffs(&source, &unset)
+            .unwrap()
+            .into_iter()
+            .find(|diff| diff.provider == "github")
+            .unwrap();
+        assert_eq!(github.action, ProviderAction::Update);
+        assert!(github.secret_required);
+
+        let changes =
+            provider_changes(&source, &dest, &request(&[("google", "prod-secret")])).unwrap();
+        assert_eq!(
+            changes,
+            json!({
+                "external_google_enabled": true,
+                "external_google_client_id": "google-staging",
+                "external_google_secret": "prod-secret",
+                "external_google_skip_nonce_check": false
+            })
+        );
+    }
+
+    #[test]
+    fn test_updates_keep_the_destination_secret() {
+        let (source, dest) = configs();
+        let request = ProviderApplyRequest {
+            providers: vec!["github".to_string()],
+            secrets: BTreeMap::new(),
+        };
+
+        let changes = provider_changes(&source, &dest, &request).unwrap();
+
+        assert!(changes.get("external_github_secret").is_none());
+        assert_eq!(
+            changes["external_github_redirect_uri"],
+            "https://staging.example.com/callback"
+        );
+        assert!(
+            provider_changes(
+                &source,
+                &dest,
+                &ProviderApplyRequest {
+                    providers: vec!["apple".to_string()],
+                    secrets: BTreeMap::new(),
+                }
+            )
+            .is_err()
+        );
+    }
+}
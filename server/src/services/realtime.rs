@@ -0,0 +1,410 @@
+use crate::error::PreviewError;
+use crate::models::migrate::{ApplyStep, Operation};
+use crate::services::database::{query_config, quote_ident, quote_qualified_name};
+use crate::services::management_api::ManagementApi;
+
+use serde_json::{Map, Value};
+
+const PUBLICATION: &str = "supabase_realtime";
+
+// The publication's flags plus every published table with its column list and row
+// filter. `columns` is null when the whole table is published.
+const PUBLICATION_QUERY: &str = "select json_build_object(
+    'publication', (
+        select json_build_object(
+            'all_tables', p.puballtables,
+            'insert', p.pubinsert,
+            'update', p.pubupdate,
+            'delete', p.pubdelete,
+            'truncate', p.pubtruncate
+        )
+        from pg_publication p
+        where p.pubname = 'supabase_realtime'
+    ),
+    'tables', coalesce((
+        select json_object_agg(
+            quote_ident(n.nspname) || '.' || quote_ident(c.relname),
+            json_build_object(
+                'columns', case when pr.prattrs is null then null else (
+                    select json_agg(a.attname order by a.attnum)
+                    from pg_attribute a
+                    where a.attrelid = pr.prrelid and a.attnum = any(pr.prattrs::int2[])
+                ) end,
+                'row_filter', pg_get_expr(pr.prqual, pr.prrelid)
+            )
+            order by n.nspname, c.relname
+        )
+        from pg_publication p
+        join pg_publication_rel pr on pr.prpubid = p.oid
+        join pg_class c on c.oid = pr.prrelid
+        join pg_namespace n on n.oid = c.relnamespace
+        where p.pubname = 'supabase_realtime'
+    ), '{}'::json)
+) as config";
+
+const PUBLISH_ACTIONS: &[&str] = &["insert", "update", "delete", "truncate"];
+
+/// Returns the `supabase_realtime` publication membership together with the project's
+/// Realtime settings under `settings`.
+pub async fn fetch_realtime(api: &ManagementApi, project_id: &str) -> Result<Value, PreviewError> {
+    let mut config = query_config(api, project_id, PUBLICATION_QUERY).await?;
+
+    let settings_json = api
+        .get(format!("/projects/{}/config/realtime", project_id))
+        .await?;
+    let settings: Value = serde_json::from_str(&settings_json)?;
+
+    if let Some(obj) = config.as_object_mut() {
+        obj.insert("settings".to_string(), settings);
+    }
+
+    Ok(config)
+}
+
+/// Builds the statements that make the destination publication match the source: creating
+/// it when missing, aligning its publish actions and adding, dropping or redefining tables.
+/// A publication switching to or from `FOR ALL TABLES` would have to be recreated, which
+/// needs a superuser, so that difference is reported as skipped.
+pub fn plan_realtime(source: &Value, dest: &Value) -> Vec<Operation> {
+    let mut operations = Vec::new();
+
+    let Some(src_publication) = source.get("publication").filter(|p| !p.is_null()) else {
+        return operations;
+    };
+    let dst_publication = dest.get("publication").filter(|p| !p.is_null());
+
+    let all_tables = |publication: Option<&Value>| {
+        publication
+            .and_then(|p| p.get("all_tables"))
+            .and_then(Value::as_bool)
+            .unwrap_or(false)
+    };
+    let src_all_tables = all_tables(Some(src_publication));
+    let dst_all_tables = all_tables(dst_publication);
+
+    if src_all_tables != dst_all_tables {
+        operations.push(Operation::Skip {
+            key: "publication.all_tables".to_string(),
+            reason: "changing FOR ALL TABLES means recreating the publication as a superuser"
+                .to_string(),
+        });
+        if dst_publication.is_none() {
+            return operations;
+        }
+    }
+
+    if dst_publication.is_none() {
+        operations.push(Operation::Sql(ApplyStep {
+            key: "publication".to_string(),
+            statement: format!("create publication {}", PUBLICATION),
+        }));
+    }
+
+    let actions_differ = PUBLISH_ACTIONS
+        .iter()
+        .any(|a| dst_publication.and_then(|p| p.get(a)) != src_publication.get(a));
+    if actions_differ {
+        let publish: Vec<&str> = PUBLISH_ACTIONS
+            .iter()
+            .copied()
+            .filter(|a| {
+                src_publication
+                    .get(a)
+                    .and_then(Value::as_bool)
+                    .unwrap_or(false)
+            })
+            .collect();
+        operations.push(Operation::Sql(ApplyStep {
+            key: "publication".to_string(),
+            statement: format!(
+                "alter publication {} set (publish = '{}')",
+                PUBLICATION,
+                publish.join(", ")
+            ),
+        }));
+    }
+
+    // A FOR ALL TABLES publication has no table list to align
+    if src_all_tables || dst_all_tables {
+        return operations;
+    }
+
+    let empty = Map::new();
+    let src_tables = source
+        .get("tables")
+        .and_then(Value::as_object)
+        .unwrap_or(&empty);
+    let dst_tables = dest
+        .get("tables")
+        .and_then(Value::as_object)
+        .unwrap_or(&empty);
+
+    for (table, src_table) in src_tables {
+        let key = format!("tables.{}", table);
+        let statement = match dst_tables.get(table) {
+            None => add_table(table, src_table),
+            Some(dst_table) if dst_table != src_table => drop_table(table).and_then(|drop| {
+                add_table(table, src_table).map(|add| format!("{}; {}", drop, add))
+            }),
+            Some(_) => continue,
+        };
+        operations.push(table_operation(key, table, src_table, statement));
+    }
+
+    for table in dst_tables.keys() {
+        if !src_tables.contains_key(table) {
+            let key = format!("tables.{}", table);
+            operations.push(table_operation(key, table, &Value::Null, drop_table(table)));
+        }
+    }
+
+    operations
+}
+
+/// Returns the source settings that differ from the destination, suitable for a PATCH.
+pub fn realtime_settings_changes(source: &Value, dest: &Value) -> Option<Value> {
+    let src_settings = source.get("settings")?.as_object()?;
+    let dst_settings = dest.get("settings").and_then(Value::as_object);
+
+    let changes: Map<String, Value> = src_settings
+        .iter()
+        .filter(|(key, value)| dst_settings.and_then(|d| d.get(*key)) != Some(*value))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+
+    if changes.is_empty() {
+        None
+    } else {
+        Some(Value::Object(changes))
+    }
+}
+
+// Table names and row filters may come from a document, so names are parsed and quoted
+// again and filters must be a single expression before they are put into a statement.
+fn add_table(table: &str, config: &Value) -> Option<String> {
+    let mut statement = format!(
+        "alter publication {} add table {}",
+        PUBLICATION,
+        quote_qualified_name(table)?
+    );
+
+    if let Some(columns) = config.get("columns").and_then(Value::as_array) {
+        let columns: Vec<String> = columns
+            .iter()
+            .filter_map(Value::as_str)
+            .map(quote_ident)
+            .collect();
+        statement.push_str(&format!(" ({})", columns.join(", ")));
+    }
+
+    if let Some(filter) = config.get("row_filter").and_then(Value::as_str) {
+        if !is_single_expression(filter) {
+            return None;
+        }
+        statement.push_str(&format!(" where ({})", filter));
+    }
+
+    Some(statement)
+}
+
+fn drop_table(table: &str) -> Option<String> {
+    Some(format!(
+        "alter publication {} drop table {}",
+        PUBLICATION,
+        quote_qualified_name(table)?
+    ))
+}
+
+fn table_operation(
+    key: String,
+    table: &str,
+    config: &Value,
+    statement: Option<String>,
+) -> Operation {
+    match statement {
+        Some(statement) => Operation::Sql(ApplyStep { key, statement }),
+        None => Operation::Skip {
+            key,
+            reason: match config.get("row_filter").and_then(Value::as_str) {
+                Some(filter) if !is_single_expression(filter) => {
+                    format!("row filter {:?} is not a single expression", filter)
+                }
+                _ => format!("table name {:?} is not a valid identifier", table),
+            },
+        },
+    }
+}
+
+// Checks that a row filter stays inside the parentheses it is wrapped in: brackets balance,
+// and there are no statement separators or comments outside of quoted strings and names.
+// Backslashes and dollar quotes are rejected outright since `E''` and `$$` strings end
+// differently from plain ones.
+fn is_single_expression(filter: &str) -> bool {
+    if filter.contains(['\\', '$']) {
+        return false;
+    }
+
+    let mut depth = 0usize;
+    let mut quote = None;
+    let mut chars = filter.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if let Some(q) = quote {
+            if c == q {
+                if chars.peek() == Some(&q) {
+                    chars.next();
+                } else {
+                    quote = None;
+                }
+            }
+            continue;
+        }
+
+        match c {
+            '\'' | '"' => quote = Some(c),
+            '(' => depth += 1,
+            ')' if depth == 0 => return false,
+            ')' => depth -= 1,
+            ';' => return false,
+            '-' if chars.peek() == Some(&'-') => return false,
+            '/' if chars.peek() == Some(&'*') => return false,
+            _ => {}
+        }
+    }
+
+    quote.is_none() && depth == 0 && !filter.trim().is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn statements(operations: &[Operation]) -> Vec<&str> {
+        operations
+            .iter()
+            .map(|operation| match operation {
+                Operation::Sql(step) => step.statement.as_str(),
+                Operation::Skip { reason, .. } => reason.as_str(),
+                Operation::Api(write) => write.path.as_str(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_plan_realtime_tables() {
+        let source: Value = serde_json::from_str(
+            r#"{
+                "publication": {"all_tables": false, "insert": true, "update": true, "delete": true, "truncate": false},
+                "tables": {
+                    "public.messages": {"columns": null, "row_filter": null},
+                    "public.profiles": {"columns": ["id", "status"], "row_filter": "(status <> 'hidden'::text)"}
+                },
+                "settings": {"private_only": false}
+            }"#,
+        )
+        .unwrap();
+        let dest: Value = serde_json::from_str(
+            r#"{
+                "publication": {"all_tables": false, "insert": true, "update": true, "delete": true, "truncate": false},
+                "tables": {
+                    "public.profiles": {"columns": null, "row_filter": null},
+                    "public.audit": {"columns": null, "row_filter": null}
+                },
+                "settings": {"private_only": false}
+            }"#,
+        )
+        .unwrap();
+
+        let operations = plan_realtime(&source, &dest);
+
+        assert_eq!(
+            statements(&operations),
+            vec![
+                "alter publication supabase_realtime add table \"public\".\"messages\"",
+                "alter publication supabase_realtime drop table \"public\".\"profiles\"; alter publication supabase_realtime add table \"public\".\"profiles\" (\"id\", \"status\") where ((status <> 'hidden'::text))",
+                "alter publication supabase_realtime drop table \"public\".\"audit\"",
+            ]
+        );
+        assert!(realtime_settings_changes(&source, &dest).is_none());
+    }
+
+    #[test]
+    fn test_plan_creates_missing_publication() {
+        let source: Value = serde_json::from_str(
+            r#"{
+                "publication": {"all_tables": false, "insert": true, "update": true, "delete": false, "truncate": false},
+                "tables": {},
+                "settings": {"private_only": true, "max_concurrent_users": 200}
+            }"#,
+        )
+        .unwrap();
+        let dest: Value = serde_json::from_str(
+            r#"{"publication": null, "tables": {}, "settings": {"private_only": false, "max_concurrent_users": 200}}"#,
+        )
+        .unwrap();
+
+        let operations = plan_realtime(&source, &dest);
+
+        assert_eq!(
+            statements(&operations),
+            vec![
+                "create publication supabase_realtime",
+                "alter publication supabase_realtime set (publish = 'insert, update')",
+            ]
+        );
+
+        let changes = realtime_settings_changes(&source, &dest).unwrap();
+        assert_eq!(changes, serde_json::json!({"private_only": true}));
+    }
+
+    #[test]
+    fn test_plan_skips_unsafe_tables_and_all_tables() {
+        let source: Value = serde_json::from_str(
+            r#"{
+                "publication": {"all_tables": false, "insert": true, "update": true, "delete": true, "truncate": true},
+                "tables": {
+                    "public.todos; drop table users": {"columns": null, "row_filter": null},
+                    "public.notes": {"columns": null, "row_filter": "true); drop table users; --"}
+                }
+            }"#,
+        )
+        .unwrap();
+        let dest: Value = serde_json::from_str(
+            r#"{"publication": {"all_tables": false, "insert": true, "update": true, "delete": true, "truncate": true}, "tables": {}}"#,
+        )
+        .unwrap();
+
+        let operations = plan_realtime(&source, &dest);
+
+        assert_eq!(
+            statements(&operations),
+            vec![
+                "row filter \"true); drop table users; --\" is not a single expression",
+                "table name \"public.todos; drop table users\" is not a valid identifier",
+            ]
+        );
+        assert!(operations.iter().all(|o| o.written_keys().is_empty()));
+
+        let all_tables: Value = serde_json::from_str(
+            r#"{"publication": {"all_tables": true, "insert": true, "update": true, "delete": true, "truncate": true}, "tables": {}}"#,
+        )
+        .unwrap();
+        let operations = plan_realtime(&all_tables, &source);
+
+        assert_eq!(operations.len(), 1);
+        assert!(matches!(
+            &operations[0],
+            Operation::Skip { key, .. } if key == "publication.all_tables"
+        ));
+    }
+
+    #[test]
+    fn test_is_single_expression() {
+        assert!(is_single_expression("(status <> 'hidden'::text)"));
+        assert!(is_single_expression("(note = 'a;b (c')"));
+        assert!(!is_single_expression("true) or (true"));
+        assert!(!is_single_expression("E'\\'' ; drop table x; '"));
+        assert!(!is_single_expression("x = 1 -- comment"));
+        assert!(!is_single_expression(""));
+    }
+}
@@ -0,0 +1,471 @@
+use crate::error::PreviewError;
+use crate::models::AppState;
+use crate::models::migrate::{
+    ApiWrite, ApplyReport, ApplyResponse, ApplyResult, ApplyStep, Operation, PlannedService,
+    ProjectConfig,
+};
+use crate::models::snapshot::ProjectSnapshot;
+use crate::models::transform::TransformConfig;
+use crate::services::backup::create_backup;
+use crate::services::cron::plan_cron_jobs;
+use crate::services::database::run_query;
+use crate::services::diff::calculate_diff;
+use crate::services::extensions::plan_extensions;
+use crate::services::management_api::ManagementApi;
+use crate::services::realtime::{plan_realtime, realtime_settings_changes};
+use crate::services::redact::Redactor;
+use crate::services::roles::plan_roles;
+use crate::services::snapshot::{drop_unset_secrets, snapshot_config};
+use crate::services::transform::transform_source;
+use crate::services::triggers::{host_rewrites, plan_triggers};
+use crate::services::{Service, fetch_service_config};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+// Edge function fields that can be changed without redeploying the function source.
+const FUNCTION_FIELDS: &[&str] = &["name", "verify_jwt", "import_map"];
+
+/// The ordered operations needed to bring one service on the destination to its declared state.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServicePlan {
+    pub service: Service,
+    pub operations: Vec<Operation>,
+    /// The source or declared config the operations bring the destination to.
+    pub source: Value,
+    /// The destination config the operations were planned against.
+    pub dest: Value,
+}
+
+/// Fetches the live destination for every service in the document and plans the writes
+/// needed to reconcile it, after applying the transform rules for the document's project
+/// and the destination. Nothing is written.
+pub async fn plan_config(
+    api: &ManagementApi,
+    project_id: &str,
+    declared: &ProjectSnapshot,
+    transforms: &TransformConfig,
+) -> Result<Vec<ServicePlan>, PreviewError> {
+    let mut plans = Vec::new();
+    let rules = transforms.rules_for(&declared.project_ref, project_id);
+
+    for (service, declared_value) in &declared.services {
+        let live = fetch_service_config(api, *service, project_id)
+            .await
+            .map_err(|e| {
+                PreviewError::ApiError(format!("Failed to get {} config: {:?}", service, e))
+            })?;
+        let mut live = snapshot_config(*service, live);
+        let mut declared_value = declared_value.clone();
+        drop_unset_secrets(*service, &mut declared_value, &mut live);
+        let declared_value = transform_source(rules, *service, declared_value, &live);
+
+        plans.push(ServicePlan {
+            service: *service,
+            operations: plan_service(
+                *service,
+                &declared_value,
+                &live,
+                &declared.project_ref,
+                project_id,
+            ),
+            source: declared_value,
+            dest: live,
+        });
+    }
+
+    Ok(plans)
+}
+
+/// Plans the operations for a single service given its declared and live configs.
+pub fn plan_service(
+    service: Service,
+    declared: &Value,
+    live: &Value,
+    declared_ref: &str,
+    project_id: &str,
+) -> Vec<Operation> {
+    let sql = |steps: Vec<ApplyStep>| steps.into_iter().map(Operation::Sql).collect();
+
+    match service {
+        Service::Auth => api_patch(
+            "PATCH",
+            format!("/projects/{}/config/auth", project_id),
+            declared,
+            live,
+        ),
+        Service::Postgrest => api_patch(
+            "PATCH",
+            format!("/projects/{}/postgrest", project_id),
+            declared,
+            live,
+        ),
+        Service::Postgres => api_patch(
+            "PUT",
+            format!("/projects/{}/config/database/postgres", project_id),
+            declared,
+            live,
+        ),
+        Service::EdgeFunctions => plan_functions(declared, live, project_id),
+        Service::Secrets => plan_secrets(declared, live),
+        Service::Extensions => plan_extensions(declared, live),
+        Service::Roles => plan_roles(declared, live),
+        Service::Cron => sql(plan_cron_jobs(declared, live)),
+        Service::Triggers => {
+            // Without a recorded project ref there is no hostname to rewrite
+            let rewrites = if declared_ref.is_empty() {
+                Vec::new()
+            } else {
+                host_rewrites(declared_ref, project_id, &[])
+            };
+            plan_triggers(declared, live, &rewrites)
+        }
+        Service::Realtime => {
+            let mut operations = plan_realtime(declared, live);
+            if let Some(changes) = realtime_settings_changes(declared, live) {
+                operations.push(Operation::Api(ApiWrite {
+                    keys: object_keys(&changes, "settings."),
+                    method: "PATCH".to_string(),
+                    path: format!("/projects/{}/config/realtime", project_id),
+                    body: changes,
+                }));
+            }
+            operations
+        }
+    }
+}
+
+// Writes every declared top level key whose value differs from the live config in a single
+// request. Keys missing from the document are left untouched.
+fn api_patch(method: &str, path: String, declared: &Value, live: &Value) -> Vec<Operation> {
+    let Some(declared) = declared.as_object() else {
+        return Vec::new();
+    };
+
+    let changes: Map<String, Value> = declared
+        .iter()
+        .filter(|(key, value)| live.get(key.as_str()) != Some(*value))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+
+    if changes.is_empty() {
+        return Vec::new();
+    }
+
+    let body = Value::Object(changes);
+    vec![Operation::Api(ApiWrite {
+        keys: object_keys(&body, ""),
+        method: method.to_string(),
+        path,
+        body,
+    })]
+}
+
+fn plan_functions(declared: &Value, live: &Value, project_id: &str) -> Vec<Operation> {
+    let mut operations = Vec::new();
+    let live_functions = live.as_array();
+
+    for function in declared.as_array().into_iter().flatten() {
+        let Some(slug) = function.get("slug").and_then(Value::as_str) else {
+            continue;
+        };
+        let live_function =
+            live_functions.and_then(|f| f.iter().find(|l| l.get("slug") == function.get("slug")));
+
+        let Some(live_function) = live_function else {
+            operations.push(Operation::Skip {
+                key: slug.to_string(),
+                reason: "Function source is not part of the config; deploy it before applying"
+                    .to_string(),
+            });
+            continue;
+        };
+
+        let changes: Map<String, Value> = FUNCTION_FIELDS
+            .iter()
+            .filter_map(|field| function.get(*field).map(|v| (*field, v)))
+            .filter(|(field, value)| live_function.get(*field) != Some(*value))
+            .map(|(field, value)| (field.to_string(), value.clone()))
+            .collect();
+
+        if !changes.is_empty() {
+            let body = Value::Object(changes);
+            operations.push(Operation::Api(ApiWrite {
+                keys: object_keys(&body, &format!("{}.", slug)),
+                method: "PATCH".to_string(),
+                path: format!("/projects/{}/functions/{}", project_id, slug),
+                body,
+            }));
+        }
+    }
+
+    operations
+}
+
+fn plan_secrets(declared: &Value, live: &Value) -> Vec<Operation> {
+    let live_names: Vec<&Value> = live
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|s| s.get("name"))
+        .collect();
+
+    declared
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|s| s.get("name"))
+        .filter(|name| !live_names.contains(name))
+        .filter_map(Value::as_str)
+        .map(|name| Operation::Skip {
+            key: name.to_string(),
+            reason: "Secret values are not stored in config documents; set it manually".to_string(),
+        })
+        .collect()
+}
+
+fn object_keys(value: &Value, prefix: &str) -> Vec<String> {
+    value
+        .as_object()
+        .into_iter()
+        .flat_map(|o| o.keys())
+        .map(|k| format!("{}{}", prefix, k))
+        .collect()
+}
+
+/// The differences each plan resolves, leaving out services already in sync.
+pub fn plan_diffs(plans: &[ServicePlan]) -> Result<Vec<ProjectConfig>, PreviewError> {
+    let mut configs = Vec::new();
+    for plan in plans {
+        let name = plan.service.name();
+        let diffs = calculate_diff(name, &plan.source, &plan.dest)?;
+        if !diffs.is_empty() {
+            configs.push(ProjectConfig {
+                name: name.to_string(),
+                diffs,
+            });
+        }
+    }
+    Ok(configs)
+}
+
+/// Describes what executing the plans would do, in order, with sensitive values in
+/// Management API bodies masked.
+pub fn describe_plans(plans: Vec<ServicePlan>, redactor: &Redactor) -> Vec<PlannedService> {
+    plans
+        .into_iter()
+        .map(|plan| PlannedService {
+            service: plan.service.name().to_string(),
+            operations: plan
+                .operations
+                .into_iter()
+                .map(|operation| match operation {
+                    Operation::Api(mut write) => {
+                        redactor.redact_config(plan.service, &mut write.body);
+                        Operation::Api(write)
+                    }
+                    other => other,
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+/// Stores a backup of every destination value the plans are about to overwrite, then executes
+/// them in order. Nothing is written if the backup cannot be stored.
+pub async fn apply_plans(
+    app_state: &AppState,
+    api: &ManagementApi,
+    project_id: &str,
+    plans: Vec<ServicePlan>,
+) -> Result<ApplyReport, PreviewError> {
+    let backup_id = store_backup(app_state, project_id, &plans)?;
+
+    let mut services = Vec::new();
+    for plan in plans {
+        services.push(execute_plan(api, project_id, plan).await);
+    }
+
+    Ok(ApplyReport {
+        project_ref: project_id.to_string(),
+        backup_id,
+        services,
+    })
+}
+
+/// Stores a backup of the destination values the plans overwrite and returns its id, or
+/// `None` when the plans write nothing.
+pub fn store_backup(
+    app_state: &AppState,
+    project_id: &str,
+    plans: &[ServicePlan],
+) -> Result<Option<String>, PreviewError> {
+    let Some(backup) = create_backup(project_id, plans) else {
+        return Ok(None);
+    };
+
+    app_state.history.record_backup(&backup)?;
+    Ok(Some(backup.id))
+}
+
+/// Executes a service plan in order and reports the outcome for every key it touched.
+pub async fn execute_plan(
+    api: &ManagementApi,
+    project_id: &str,
+    plan: ServicePlan,
+) -> ApplyResponse {
+    let mut results = Vec::new();
+
+    for operation in plan.operations {
+        results.extend(execute_operation(api, project_id, operation).await);
+    }
+
+    ApplyResponse {
+        service: plan.service.name().to_string(),
+        results,
+    }
+}
+
+/// Executes a single operation, returning one result per key it covers.
+pub async fn execute_operation(
+    api: &ManagementApi,
+    project_id: &str,
+    operation: Operation,
+) -> Vec<ApplyResult> {
+    match operation {
+        Operation::Sql(step) => {
+            let outcome = run_query(api, project_id, &step.statement).await;
+            vec![ApplyResult {
+                key: step.key,
+                statement: step.statement,
+                success: outcome.is_ok(),
+                error: outcome.err().map(|e| format!("{:?}", e)),
+            }]
+        }
+        Operation::Api(write) => {
+            let outcome = match reqwest::Method::from_bytes(write.method.as_bytes()) {
+                Ok(method) => api
+                    .send(method, write.path.clone(), &write.body)
+                    .await
+                    .map(|_| ()),
+                Err(e) => Err(PreviewError::ApiError(format!("Invalid method: {:?}", e))),
+            };
+            let error = outcome.err().map(|e| format!("{:?}", e));
+            write
+                .keys
+                .into_iter()
+                .map(|key| ApplyResult {
+                    key,
+                    statement: format!("{} {}", write.method, write.path),
+                    success: error.is_none(),
+                    error: error.clone(),
+                })
+                .collect()
+        }
+        Operation::Skip { key, reason } => vec![ApplyResult {
+            key,
+            statement: String::new(),
+            success: false,
+            error: Some(reason),
+        }],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_plan_auth_only_differing_keys() {
+        let declared = json!({"site_url": "https://prod.example.com", "disable_signup": true});
+        let live = json!({
+            "site_url": "https://prod.example.com",
+            "disable_signup": false,
+            "jwt_exp": 3600
+        });
+
+        let operations = plan_service(Service::Auth, &declared, &live, "", "dst");
+
+        assert_eq!(operations.len(), 1);
+        let Operation::Api(write) = &operations[0] else {
+            panic!("expected an API write");
+        };
+        assert_eq!(write.method, "PATCH");
+        assert_eq!(write.path, "/projects/dst/config/auth");
+        assert_eq!(write.body, json!({"disable_signup": true}));
+        assert_eq!(write.keys, vec!["disable_signup"]);
+    }
+
+    #[test]
+    fn test_describe_plans_redacts_secrets() {
+        let declared = json!({
+            "smtp_pass": "hunter2",
+            "external_google_secret": "gsecret",
+            "site_url": "https://example.com"
+        });
+
+        let plans = vec![ServicePlan {
+            service: Service::Auth,
+            operations: plan_service(Service::Auth, &declared, &json!({}), "", "dst"),
+            source: json!({}),
+            dest: json!({}),
+        }];
+        let described = describe_plans(plans, &Redactor::default());
+
+        let Operation::Api(write) = &described[0].operations[0] else {
+            panic!("expected an API write");
+        };
+        for key in ["smtp_pass", "external_google_secret"] {
+            let masked = write.body[key].as_str().unwrap();
+            assert!(masked.starts_with("[REDACTED "));
+        }
+        assert_eq!(write.body["site_url"], "https://example.com");
+        assert_eq!(write.keys.len(), 3);
+    }
+
+    #[test]
+    fn test_plan_no_changes() {
+        let config = json!({"max_rows": 1000, "db_schema": "public"});
+
+        assert!(plan_service(Service::Postgrest, &config, &config, "", "dst").is_empty());
+    }
+
+    #[test]
+    fn test_plan_functions_and_secrets() {
+        let declared_functions = json!([
+            {"slug": "hello", "name": "hello", "verify_jwt": true},
+            {"slug": "new-fn", "name": "new-fn", "verify_jwt": true}
+        ]);
+        let live_functions = json!([
+            {"id": "x", "slug": "hello", "name": "hello", "verify_jwt": false, "version": 3}
+        ]);
+
+        let operations = plan_service(
+            Service::EdgeFunctions,
+            &declared_functions,
+            &live_functions,
+            "",
+            "dst",
+        );
+
+        assert_eq!(operations.len(), 2);
+        assert!(matches!(
+            &operations[0],
+            Operation::Api(write)
+                if write.path == "/projects/dst/functions/hello"
+                    && write.body == json!({"verify_jwt": true})
+        ));
+        assert!(matches!(&operations[1], Operation::Skip { key, .. } if key == "new-fn"));
+
+        let operations = plan_service(
+            Service::Secrets,
+            &json!([{"name": "STRIPE_KEY"}, {"name": "SENTRY_DSN"}]),
+            &json!([{"name": "STRIPE_KEY"}]),
+            "",
+            "dst",
+        );
+        assert_eq!(operations.len(), 1);
+        assert!(matches!(&operations[0], Operation::Skip { key, .. } if key == "SENTRY_DSN"));
+    }
+}
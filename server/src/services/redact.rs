@@ -0,0 +1,290 @@
+use crate::models::history::PreviewChange;
+use crate::models::merge::ServiceMerge;
+use crate::models::migrate::{DiffEntry, ProjectConfig};
+use crate::models::provider::ProviderDiff;
+use crate::services::Service;
+use crate::services::diff::{format_value, glob_match};
+
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::fmt::Write;
+
+/// Keys holding credentials, per service, as patterns on the last segment of a diff key.
+const SENSITIVE_KEYS: &[(Service, &[&str])] = &[
+    (
+        Service::Auth,
+        &[
+            "smtp_pass",
+            "external_*_secret",
+            "hook_*_secrets",
+            "sms_*_auth_token",
+            "sms_*_access_key",
+            "sms_*_api_key",
+            "sms_*_api_secret",
+            "security_captcha_secret",
+        ],
+    ),
+    (Service::Postgrest, &["jwt_secret"]),
+    (Service::Secrets, &["value"]),
+];
+
+const MASK_PREFIX: &str = "[REDACTED ";
+
+/// Masks credentials in diffs and configs before they leave the server, in responses,
+/// backups shown to users and logs. Planning, policy and history work on the real values.
+#[derive(Debug, Clone, Default)]
+pub struct Redactor {
+    /// Extra patterns from `REDACT_KEY_PATTERNS`, applying to every service.
+    patterns: Vec<String>,
+    /// Hashed with every masked value, so masks can be compared but not looked up offline.
+    salt: String,
+}
+
+/// Whether `key` is one of the known credentials of `service`, such as Auth's `smtp_pass`.
+/// Extra patterns from `REDACT_KEY_PATTERNS` are not included.
+pub fn is_sensitive_key(service: Service, key: &str) -> bool {
+    let key = key.to_lowercase();
+    let last = key.rsplit('.').next().unwrap_or_default();
+
+    SENSITIVE_KEYS
+        .iter()
+        .filter(|(known_service, _)| *known_service == service)
+        .flat_map(|(_, patterns)| patterns.iter())
+        .any(|pattern| glob_match(pattern, last))
+}
+
+/// Whether a value is a mask produced by a [`Redactor`].
+pub fn is_masked(value: &Value) -> bool {
+    value
+        .as_str()
+        .is_some_and(|value| value.starts_with(MASK_PREFIX) && value.ends_with(']'))
+}
+
+/// Parses a comma separated list of key patterns, such as `REDACT_KEY_PATTERNS`.
+pub fn parse_redact_patterns(list: Option<&str>) -> Vec<String> {
+    list.into_iter()
+        .flat_map(|list| list.split(','))
+        .map(|pattern| pattern.trim().to_lowercase())
+        .filter(|pattern| !pattern.is_empty())
+        .collect()
+}
+
+impl Redactor {
+    /// The salt has to outlive the process for masks of the same value to keep matching,
+    /// e.g. when comparing a preview with one recorded before a restart.
+    pub fn new(patterns: Vec<String>, salt: impl Into<String>) -> Self {
+        Self {
+            patterns,
+            salt: salt.into(),
+        }
+    }
+
+    /// Masks the values of sensitive keys in place. Both sides are masked with a salted hash,
+    /// so the entry still shows that they differ without revealing either value.
+    pub fn redact_diffs(&self, service_name: &str, diffs: &mut [DiffEntry]) {
+        for diff in diffs {
+            self.redact_diff(service_name, diff);
+        }
+    }
+
+    /// Masks the diffs of every service in a preview.
+    pub fn redact_configs(&self, configs: &mut [ProjectConfig]) {
+        for config in configs {
+            self.redact_diffs(&config.name, &mut config.diffs);
+        }
+    }
+
+    /// Masks both sides of every change between two previews.
+    pub fn redact_changes(&self, changes: &mut [PreviewChange]) {
+        for change in changes {
+            for diff in change.before.iter_mut().chain(&mut change.after) {
+                self.redact_diff(&change.service, diff);
+            }
+        }
+    }
+
+    /// Masks every side of a three-way comparison.
+    pub fn redact_merges(&self, merges: &mut [ServiceMerge]) {
+        for merge in merges {
+            for entry in &mut merge.entries {
+                for value in [
+                    &mut entry.baseline_value,
+                    &mut entry.source_value,
+                    &mut entry.dest_value,
+                ] {
+                    *value = self.redact_value(&merge.name, &entry.key, value);
+                }
+            }
+        }
+    }
+
+    /// Masks the Auth diffs grouped under each OAuth provider.
+    pub fn redact_providers(&self, providers: &mut [ProviderDiff]) {
+        for provider in providers {
+            self.redact_diffs(Service::Auth.name(), &mut provider.diffs);
+        }
+    }
+
+    /// Returns a diff value with sensitive content masked: the whole value when `key` is
+    /// sensitive, or the sensitive members of a JSON object or array otherwise.
+    pub fn redact_value(&self, service_name: &str, key: &str, value: &str) -> String {
+        let service = service_by_name(service_name);
+        if value == "null" {
+            return value.to_string();
+        }
+        if self.is_sensitive(service, key) {
+            return self.mask(value);
+        }
+
+        match serde_json::from_str::<Value>(value) {
+            Ok(mut parsed @ (Value::Object(_) | Value::Array(_))) => {
+                if self.redact_members(service, &mut parsed) {
+                    parsed.to_string()
+                } else {
+                    value.to_string()
+                }
+            }
+            _ => value.to_string(),
+        }
+    }
+
+    /// Masks the sensitive members of a service config or Management API body, e.g. before
+    /// returning a backup or a planned write.
+    pub fn redact_config(&self, service: Service, config: &mut Value) {
+        self.redact_members(Some(service), config);
+    }
+
+    /// Masks both values of a single diff of `service_name`.
+    pub fn redact_diff(&self, service_name: &str, diff: &mut DiffEntry) {
+        diff.source_value = self.redact_value(service_name, &diff.key, &diff.source_value);
+        diff.dest_value = self.redact_value(service_name, &diff.key, &diff.dest_value);
+    }
+
+    /// Returns whether anything was masked.
+    fn redact_members(&self, service: Option<Service>, value: &mut Value) -> bool {
+        match value {
+            Value::Object(map) => {
+                let mut redacted = false;
+                for (key, member) in map.iter_mut() {
+                    if self.is_sensitive(service, key) && !member.is_null() {
+                        *member = Value::String(self.mask(&format_value(member)));
+                        redacted = true;
+                    } else {
+                        redacted |= self.redact_members(service, member);
+                    }
+                }
+                redacted
+            }
+            Value::Array(items) => items.iter_mut().fold(false, |redacted, item| {
+                self.redact_members(service, item) | redacted
+            }),
+            _ => false,
+        }
+    }
+
+    fn is_sensitive(&self, service: Option<Service>, key: &str) -> bool {
+        let key = key.to_lowercase();
+        let last = key.rsplit('.').next().unwrap_or_default();
+
+        service.is_some_and(|service| is_sensitive_key(service, &key))
+            || self
+                .patterns
+                .iter()
+                .any(|pattern| glob_match(pattern, last) || glob_match(pattern, &key))
+    }
+
+    fn mask(&self, value: &str) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.salt.as_bytes());
+        hasher.update(value.as_bytes());
+
+        let hash =
+            hasher
+                .finalize()
+                .iter()
+                .take(6)
+                .fold(String::with_capacity(12), |mut hex, byte| {
+                    let _ = write!(hex, "{:02x}", byte);
+                    hex
+                });
+        format!("{}{}]", MASK_PREFIX, hash)
+    }
+}
+
+fn service_by_name(name: &str) -> Option<Service> {
+    Service::ALL
+        .into_iter()
+        .find(|service| service.name() == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diff(key: &str, source_value: &str, dest_value: &str) -> DiffEntry {
+        DiffEntry {
+            key: key.to_string(),
+            source_value: source_value.to_string(),
+            dest_value: dest_value.to_string(),
+            text_diff: None,
+        }
+    }
+
+    #[test]
+    fn test_known_auth_secrets_are_masked() {
+        let mut diffs = vec![
+            diff("smtp_pass", "hunter2", "hunter3"),
+            diff("external_google_secret", "abc", "null"),
+            diff("sms_twilio_auth_token", "same", "same"),
+            diff("site_url", "https://a.example.com", "https://b.example.com"),
+            diff("password_min_length", "8", "12"),
+        ];
+
+        let redactor = Redactor::new(Vec::new(), "salt");
+        redactor.redact_diffs("Auth", &mut diffs);
+
+        assert!(diffs[0].source_value.starts_with("[REDACTED "));
+        assert!(!diffs[0].source_value.contains("hunter"));
+        assert_ne!(diffs[0].source_value, diffs[0].dest_value);
+        assert_eq!(diffs[1].dest_value, "null");
+        assert_eq!(diffs[2].source_value, diffs[2].dest_value);
+        assert_eq!(diffs[3].source_value, "https://a.example.com");
+        assert_eq!(diffs[4].dest_value, "12");
+
+        // Masks only depend on the salt, so they survive a restart with the same salt
+        let restarted = Redactor::new(Vec::new(), "salt");
+        assert_eq!(
+            restarted.redact_value("Auth", "smtp_pass", "hunter2"),
+            diffs[0].source_value
+        );
+        let other = Redactor::new(Vec::new(), "other");
+        assert_ne!(
+            other.redact_value("Auth", "smtp_pass", "hunter2"),
+            diffs[0].source_value
+        );
+    }
+
+    #[test]
+    fn test_json_members_are_masked() {
+        let secret = r#"{"name":"STRIPE_KEY","value":"sk_live_123"}"#;
+
+        let redactor = Redactor::default();
+        let redacted = redactor.redact_value("Secrets", "[0]", secret);
+        assert!(redacted.contains("STRIPE_KEY"));
+        assert!(!redacted.contains("sk_live_123"));
+
+        // The same member name is not sensitive for other services
+        assert_eq!(redactor.redact_value("Auth", "hook", secret), secret);
+    }
+
+    #[test]
+    fn test_configured_patterns() {
+        let extra = parse_redact_patterns(Some("smtp_user, *.Api_Token"));
+
+        assert_eq!(extra, vec!["smtp_user", "*.api_token"]);
+        let redactor = Redactor::new(extra, "salt");
+        assert!(redactor.is_sensitive(Some(Service::Auth), "SMTP_USER"));
+        assert!(redactor.is_sensitive(None, "id:hello.api_token"));
+        assert!(!redactor.is_sensitive(Some(Service::Auth), "smtp_host"));
+    }
+}
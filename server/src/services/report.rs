@@ -0,0 +1,436 @@
+use crate::models::migrate::{DiffEntry, PreviewResponse, ProjectConfig};
+use crate::models::policy::{PolicyHit, Severity};
+
+use serde::Deserialize;
+use serde_json::Value;
+use std::fmt::Write;
+use std::str::FromStr;
+
+/// Output formats a preview can be rendered in.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportFormat {
+    #[default]
+    Json,
+    Markdown,
+    Html,
+}
+
+impl FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "json" => Ok(ReportFormat::Json),
+            "markdown" | "md" => Ok(ReportFormat::Markdown),
+            "html" => Ok(ReportFormat::Html),
+            _ => Err(format!(
+                "unknown report format `{}`, expected json, markdown or html",
+                value
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    /// Only the source has a value.
+    Added,
+    /// Only the destination has a value.
+    Removed,
+    Changed,
+}
+
+impl ChangeKind {
+    pub fn of(diff: &DiffEntry) -> Self {
+        match (diff.source_value.as_str(), diff.dest_value.as_str()) {
+            (_, "null") => ChangeKind::Added,
+            ("null", _) => ChangeKind::Removed,
+            _ => ChangeKind::Changed,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ChangeKind::Added => "added",
+            ChangeKind::Removed => "removed",
+            ChangeKind::Changed => "changed",
+        }
+    }
+}
+
+/// Renders a preview as Markdown, one table per service, for pasting into pull requests and
+/// change tickets.
+pub fn render_markdown(preview: &PreviewResponse) -> String {
+    let mut out = String::from("# Configuration diff\n\n");
+    out.push_str(&summary_line(&preview.configs));
+    out.push('\n');
+
+    if !preview.policy.is_empty() {
+        out.push_str(
+            "\n## Policy\n\n| Rule | Severity | Service | Key | Message |\n| --- | --- | --- | --- | --- |\n",
+        );
+        for hit in &preview.policy {
+            let _ = writeln!(
+                out,
+                "| {} | {} | {} | {} | {} |",
+                markdown_text(&hit.rule),
+                severity_label(hit.severity),
+                hit.service,
+                markdown_code(&hit.key),
+                markdown_text(&hit.message)
+            );
+        }
+    }
+
+    for config in &preview.configs {
+        let _ = write!(
+            out,
+            "\n## {}\n\n| Key | Source | Destination | Change |\n| --- | --- | --- | --- |\n",
+            config.name
+        );
+        for diff in &config.diffs {
+            let _ = writeln!(
+                out,
+                "| {} | {} | {} | {} |",
+                markdown_code(&diff.key),
+                markdown_value(&diff.source_value),
+                markdown_value(&diff.dest_value),
+                ChangeKind::of(diff).label()
+            );
+        }
+        for diff in &config.diffs {
+            if let Some(text_diff) = &diff.text_diff {
+                let fence = markdown_fence(text_diff);
+                let _ = write!(
+                    out,
+                    "\n<details><summary><code>{}</code> line diff</summary>\n\n{}diff\n{}{}\n\n</details>\n",
+                    html_escape(&diff.key),
+                    fence,
+                    text_diff,
+                    fence
+                );
+            }
+        }
+    }
+
+    out
+}
+
+/// Renders a preview as a standalone HTML page with a collapsible section per service.
+/// Object and array values are pretty-printed side by side.
+pub fn render_html(preview: &PreviewResponse) -> String {
+    let mut out = String::from(HTML_HEAD);
+    let _ = write!(
+        out,
+        "<h1>Configuration diff</h1>\n<p>{}</p>\n",
+        html_escape(&summary_line(&preview.configs))
+    );
+    if !preview.policy.is_empty() {
+        out.push_str(&html_policy(&preview.policy));
+    }
+
+    for config in &preview.configs {
+        let _ = write!(
+            out,
+            "<details open>\n<summary>{} <span class=\"count\">({})</span></summary>\n\
+             <table>\n<thead><tr><th>Key</th><th>Change</th><th>Source</th><th>Destination</th></tr></thead>\n<tbody>\n",
+            html_escape(&config.name),
+            config.diffs.len()
+        );
+        for diff in &config.diffs {
+            let kind = ChangeKind::of(diff);
+            let _ = writeln!(
+                out,
+                "<tr class=\"{label}\"><td><code>{}</code></td><td>{label}</td><td>{}</td><td>{}</td></tr>",
+                html_escape(&diff.key),
+                html_value(&diff.source_value),
+                html_value(&diff.dest_value),
+                label = kind.label()
+            );
+            if let Some(text_diff) = &diff.text_diff {
+                let _ = writeln!(
+                    out,
+                    "<tr class=\"text-diff\"><td colspan=\"4\"><pre>{}</pre></td></tr>",
+                    html_text_diff(text_diff)
+                );
+            }
+        }
+        out.push_str("</tbody>\n</table>\n</details>\n");
+    }
+
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+const HTML_HEAD: &str = "<!DOCTYPE html>
+<html lang=\"en\">
+<head>
+<meta charset=\"utf-8\">
+<title>Configuration diff</title>
+<style>
+body { font-family: system-ui, sans-serif; margin: 2rem; color: #1f2328; }
+summary { font-size: 1.2rem; font-weight: 600; cursor: pointer; margin: 1rem 0 0.5rem; }
+.count { color: #656d76; font-weight: normal; }
+table { border-collapse: collapse; width: 100%; table-layout: fixed; }
+th, td { border: 1px solid #d0d7de; padding: 0.4rem 0.6rem; text-align: left; vertical-align: top; }
+th:nth-child(1), th:nth-child(2) { width: 15%; }
+pre { margin: 0; white-space: pre-wrap; word-break: break-all; }
+tr.added td:nth-child(2) { color: #1a7f37; }
+tr.removed td:nth-child(2) { color: #cf222e; }
+tr.changed td:nth-child(2) { color: #9a6700; }
+tr.block td:nth-child(2) { color: #cf222e; font-weight: 600; }
+tr.require_approval td:nth-child(2) { color: #9a6700; font-weight: 600; }
+.none { color: #656d76; font-style: italic; }
+.text-diff pre { font-size: 0.85rem; }
+.text-diff .ins { background: #dafbe1; }
+.text-diff .del { background: #ffebe9; }
+.text-diff .hunk { color: #656d76; }
+</style>
+</head>
+<body>
+";
+
+fn summary_line(configs: &[ProjectConfig]) -> String {
+    let total: usize = configs.iter().map(|config| config.diffs.len()).sum();
+    if total == 0 {
+        return "No differences.".to_string();
+    }
+    format!(
+        "{} difference{} across {} service{}.",
+        total,
+        if total == 1 { "" } else { "s" },
+        configs.len(),
+        if configs.len() == 1 { "" } else { "s" }
+    )
+}
+
+fn severity_label(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Warn => "warn",
+        Severity::RequireApproval => "requires approval",
+        Severity::Block => "block",
+    }
+}
+
+fn html_policy(hits: &[PolicyHit]) -> String {
+    let mut out = String::from(
+        "<h2>Policy</h2>\n<table>\n<thead><tr><th>Rule</th><th>Severity</th><th>Key</th><th>Message</th></tr></thead>\n<tbody>\n",
+    );
+    for hit in hits {
+        let class = match hit.severity {
+            Severity::Warn => "warn",
+            Severity::RequireApproval => "require_approval",
+            Severity::Block => "block",
+        };
+        let _ = writeln!(
+            out,
+            "<tr class=\"{}\"><td>{}</td><td>{}</td><td><code>{} {}</code></td><td>{}</td></tr>",
+            class,
+            html_escape(&hit.rule),
+            severity_label(hit.severity),
+            html_escape(&hit.service),
+            html_escape(&hit.key),
+            html_escape(&hit.message)
+        );
+    }
+    out.push_str("</tbody>\n</table>\n");
+    out
+}
+
+fn markdown_text(value: &str) -> String {
+    value.replace('|', "\\|").replace('\n', " ")
+}
+
+fn markdown_value(value: &str) -> String {
+    if value == "null" {
+        "_(none)_".to_string()
+    } else {
+        markdown_code(value)
+    }
+}
+
+// Inline code keeps values verbatim; table cells only need pipes and line breaks escaped.
+// Values containing backticks fall back to escaped plain text.
+fn markdown_code(value: &str) -> String {
+    let cell = value.replace('|', "\\|").replace('\n', "<br>");
+    if value.contains('`') {
+        cell.replace('`', "\\`")
+    } else {
+        format!("`{}`", cell)
+    }
+}
+
+// A fence longer than any backtick run in the diff, so template contents cannot close it.
+fn markdown_fence(text: &str) -> String {
+    let longest = text
+        .split(|c| c != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or_default();
+    "`".repeat(longest.max(2) + 1)
+}
+
+fn html_text_diff(text_diff: &str) -> String {
+    text_diff
+        .lines()
+        .map(|line| {
+            let class = if line.starts_with("@@") {
+                Some("hunk")
+            } else if line.starts_with("+++") || line.starts_with("---") {
+                None
+            } else if line.starts_with('+') {
+                Some("ins")
+            } else if line.starts_with('-') {
+                Some("del")
+            } else {
+                None
+            };
+            match class {
+                Some(class) => format!("<span class=\"{}\">{}</span>", class, html_escape(line)),
+                None => html_escape(line),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn html_value(value: &str) -> String {
+    if value == "null" {
+        return "<span class=\"none\">(none)</span>".to_string();
+    }
+    match serde_json::from_str::<Value>(value) {
+        Ok(parsed @ (Value::Object(_) | Value::Array(_))) => format!(
+            "<pre>{}</pre>",
+            html_escape(&serde_json::to_string_pretty(&parsed).unwrap_or_default())
+        ),
+        _ => format!("<code>{}</code>", html_escape(value)),
+    }
+}
+
+fn html_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn preview() -> PreviewResponse {
+        PreviewResponse {
+            configs: vec![ProjectConfig {
+                name: "Auth".to_string(),
+                diffs: vec![
+                    DiffEntry {
+                        key: "site_url".to_string(),
+                        source_value: "https://a.example.com".to_string(),
+                        dest_value: "https://b.example.com".to_string(),
+                        text_diff: None,
+                    },
+                    DiffEntry {
+                        key: "uri_allow_list".to_string(),
+                        source_value: "a|b".to_string(),
+                        dest_value: "null".to_string(),
+                        text_diff: None,
+                    },
+                    DiffEntry {
+                        key: "hook".to_string(),
+                        source_value: "null".to_string(),
+                        dest_value: r#"{"enabled":true,"uri":"<pg>"}"#.to_string(),
+                        text_diff: None,
+                    },
+                ],
+            }],
+            policy: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_render_markdown() {
+        let markdown = render_markdown(&preview());
+
+        assert!(markdown.contains("3 differences across 1 service."));
+        assert!(markdown.contains("## Auth"));
+        assert!(markdown.contains(
+            "| `site_url` | `https://a.example.com` | `https://b.example.com` | changed |"
+        ));
+        assert!(markdown.contains("| `uri_allow_list` | `a\\|b` | _(none)_ | added |"));
+        assert!(markdown.contains("| removed |"));
+    }
+
+    #[test]
+    fn test_render_html_escapes_and_pretty_prints_objects() {
+        let html = render_html(&preview());
+
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<details open>"));
+        assert!(html.contains("&lt;pg&gt;"));
+        assert!(!html.contains("<pg>"));
+        assert!(html.contains("<pre>{\n  &quot;enabled&quot;: true"));
+        assert!(html.ends_with("</html>\n"));
+    }
+
+    #[test]
+    fn test_empty_preview() {
+        let empty = PreviewResponse {
+            configs: Vec::new(),
+            policy: Vec::new(),
+        };
+
+        assert!(render_markdown(&empty).contains("No differences."));
+        assert!(!render_markdown(&empty).contains("## Policy"));
+    }
+
+    #[test]
+    fn test_text_diffs_are_rendered() {
+        let mut preview = preview();
+        preview.configs[0].diffs[0].text_diff = Some(
+            "--- destination\n+++ source\n@@ -1,2 +1,2 @@\n <h2>Confirm</h2>\n-<p>old</p>\n+<p>new ```</p>\n"
+                .to_string(),
+        );
+
+        let markdown = render_markdown(&preview);
+        assert!(markdown.contains(
+            "<details><summary><code>site_url</code> line diff</summary>\n\n````diff\n--- destination\n"
+        ));
+        assert!(markdown.contains("+<p>new ```</p>\n````\n\n</details>"));
+
+        let html = render_html(&preview);
+        assert!(html.contains("<tr class=\"text-diff\"><td colspan=\"4\"><pre>--- destination\n"));
+        assert!(html.contains("<span class=\"del\">-&lt;p&gt;old&lt;/p&gt;</span>"));
+        assert!(html.contains("<span class=\"hunk\">@@ -1,2 +1,2 @@</span>"));
+    }
+
+    #[test]
+    fn test_policy_hits_are_rendered() {
+        let mut preview = preview();
+        preview.policy = vec![PolicyHit {
+            rule: "prod-signups".to_string(),
+            severity: Severity::Block,
+            service: "Auth".to_string(),
+            key: "disable_signup".to_string(),
+            source_value: "false".to_string(),
+            dest_value: "true".to_string(),
+            message: "Signups stay closed on <prod>".to_string(),
+        }];
+
+        assert!(render_markdown(&preview).contains(
+            "| prod-signups | block | Auth | `disable_signup` | Signups stay closed on <prod> |"
+        ));
+        let html = render_html(&preview);
+        assert!(html.contains("<h2>Policy</h2>"));
+        assert!(html.contains("<tr class=\"block\"><td>prod-signups</td>"));
+        assert!(html.contains("Signups stay closed on &lt;prod&gt;"));
+    }
+}
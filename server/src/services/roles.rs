@@ -0,0 +1,591 @@
+use crate::error::PreviewError;
+use crate::models::migrate::{ApplyStep, Operation};
+use crate::services::database::{
+    query_config, quote_function_signature, quote_ident, quote_qualified_name, sql_array,
+};
+use crate::services::management_api::ManagementApi;
+
+use serde_json::{Map, Value, json};
+
+// Roles created and managed by the Supabase platform. They exist on every project and are
+// never compared or migrated, but grants to the API roles (anon, authenticated,
+// service_role) are still reported since they are usually user defined.
+const MANAGED_ROLES: &[&str] = &[
+    "postgres",
+    "anon",
+    "authenticated",
+    "service_role",
+    "authenticator",
+    "dashboard_user",
+    "pgbouncer",
+    "pgsodium_keyholder",
+    "pgsodium_keyiduser",
+    "pgsodium_keymaker",
+    "supabase_admin",
+    "supabase_auth_admin",
+    "supabase_etl_admin",
+    "supabase_functions_admin",
+    "supabase_read_only_user",
+    "supabase_realtime_admin",
+    "supabase_replication_admin",
+    "supabase_storage_admin",
+    "cli_login_postgres",
+];
+
+const API_ROLES: &[&str] = &["anon", "authenticated", "service_role"];
+
+// Schemas owned by Supabase services or Postgres itself.
+const MANAGED_SCHEMAS: &[&str] = &[
+    "pg_catalog",
+    "pg_toast",
+    "information_schema",
+    "auth",
+    "storage",
+    "realtime",
+    "_realtime",
+    "_analytics",
+    "extensions",
+    "graphql",
+    "graphql_public",
+    "net",
+    "pgbouncer",
+    "pgsodium",
+    "pgsodium_masks",
+    "vault",
+    "cron",
+    "supabase_functions",
+    "supabase_migrations",
+];
+
+// Privileges that can be granted on each kind of object, as reported by `aclexplode`.
+const PRIVILEGE_KINDS: &[(&str, &[&str])] = &[
+    ("schema", &["CREATE", "USAGE"]),
+    (
+        "table",
+        &[
+            "DELETE",
+            "INSERT",
+            "MAINTAIN",
+            "REFERENCES",
+            "SELECT",
+            "TRIGGER",
+            "TRUNCATE",
+            "UPDATE",
+        ],
+    ),
+    ("function", &["EXECUTE"]),
+];
+
+fn roles_query() -> String {
+    let managed = sql_array(MANAGED_ROLES);
+    let api_roles = sql_array(API_ROLES);
+    let schemas = sql_array(MANAGED_SCHEMAS);
+
+    // Grants are aggregated as object -> grantee -> privilege -> true so that both the diff
+    // keys and the generated GRANT/REVOKE statements work per privilege.
+    let grants = |objects: &str| {
+        format!(
+            "coalesce((
+                select json_object_agg(t.object_name, t.grantees order by t.object_name)
+                from (
+                    select g.object_name, json_object_agg(g.grantee, g.privileges order by g.grantee) as grantees
+                    from (
+                        select o.object_name,
+                            case when a.grantee = 0 then 'PUBLIC' else pg_get_userbyid(a.grantee) end as grantee,
+                            json_object_agg(a.privilege_type, true order by a.privilege_type) as privileges
+                        from ({objects}) o
+                        cross join lateral aclexplode(o.acl) a
+                        group by 1, 2
+                    ) g
+                    where g.grantee = any({api_roles})
+                        or (g.grantee <> all({managed}) and g.grantee not like 'pg\\_%')
+                    group by g.object_name
+                ) t
+            ), '{{}}'::json)"
+        )
+    };
+
+    let schema_objects = format!(
+        "select quote_ident(n.nspname) as object_name, n.nspacl as acl
+        from pg_namespace n
+        where n.nspname <> all({schemas}) and n.nspname not like 'pg\\_%'"
+    );
+    let table_objects = format!(
+        "select quote_ident(n.nspname) || '.' || quote_ident(c.relname) as object_name, c.relacl as acl
+        from pg_class c
+        join pg_namespace n on n.oid = c.relnamespace
+        where c.relkind in ('r', 'v', 'm', 'p', 'f')
+            and n.nspname <> all({schemas}) and n.nspname not like 'pg\\_%'
+            and not exists (select 1 from pg_depend d where d.objid = c.oid and d.deptype = 'e')"
+    );
+    let function_objects = format!(
+        "select quote_ident(n.nspname) || '.' || quote_ident(p.proname)
+                || '(' || pg_get_function_identity_arguments(p.oid) || ')' as object_name,
+            p.proacl as acl
+        from pg_proc p
+        join pg_namespace n on n.oid = p.pronamespace
+        where n.nspname <> all({schemas}) and n.nspname not like 'pg\\_%'
+            and not exists (select 1 from pg_depend d where d.objid = p.oid and d.deptype = 'e')"
+    );
+
+    format!(
+        "select json_build_object(
+            'roles', coalesce((
+                select json_object_agg(r.rolname, json_build_object(
+                    'superuser', r.rolsuper,
+                    'inherit', r.rolinherit,
+                    'createrole', r.rolcreaterole,
+                    'createdb', r.rolcreatedb,
+                    'login', r.rolcanlogin,
+                    'replication', r.rolreplication,
+                    'bypassrls', r.rolbypassrls,
+                    'connection_limit', r.rolconnlimit,
+                    'member_of', coalesce((
+                        select json_object_agg(g.rolname, true order by g.rolname)
+                        from pg_auth_members m
+                        join pg_roles g on g.oid = m.roleid
+                        where m.member = r.oid
+                    ), '{{}}'::json)
+                ) order by r.rolname)
+                from pg_roles r
+                where r.rolname not like 'pg\\_%' and r.rolname <> all({managed})
+            ), '{{}}'::json),
+            'grants', json_build_object(
+                'schema', {schema_grants},
+                'table', {table_grants},
+                'function', {function_grants}
+            )
+        ) as config",
+        schema_grants = grants(&schema_objects),
+        table_grants = grants(&table_objects),
+        function_grants = grants(&function_objects),
+    )
+}
+
+pub async fn fetch_roles(api: &ManagementApi, project_id: &str) -> Result<Value, PreviewError> {
+    query_config(api, project_id, &roles_query()).await
+}
+
+/// Builds the operations that bring the destination's custom roles, memberships and grants
+/// in line with the source. Roles that only exist on the destination are left in place.
+/// Attributes only a superuser may change, and names or privileges that do not parse, are
+/// reported as skipped instead of being written.
+pub fn plan_roles(source: &Value, dest: &Value) -> Vec<Operation> {
+    let empty = Map::new();
+    let src_roles = source
+        .get("roles")
+        .and_then(Value::as_object)
+        .unwrap_or(&empty);
+    let dst_roles = dest
+        .get("roles")
+        .and_then(Value::as_object)
+        .unwrap_or(&empty);
+
+    let mut operations = Vec::new();
+
+    for (name, attrs) in src_roles {
+        let key = format!("roles.{}", name);
+        let dst_attrs = dst_roles.get(name);
+        let current = dst_attrs.unwrap_or(&Value::Null);
+
+        for (field, flag) in SUPERUSER_FLAGS {
+            if attribute(attrs, field) != attribute(current, field) {
+                operations.push(Operation::Skip {
+                    key: format!("{}.{}", key, field),
+                    reason: format!("{} can only be changed by a superuser", flag),
+                });
+            }
+        }
+
+        let options = role_options(attrs, current);
+        match dst_attrs {
+            None => operations.push(Operation::Sql(ApplyStep {
+                key,
+                statement: format!("create role {} {}", quote_ident(name), options)
+                    .trim_end()
+                    .to_string(),
+            })),
+            Some(_) if !options.is_empty() => operations.push(Operation::Sql(ApplyStep {
+                key,
+                statement: format!("alter role {} with {}", quote_ident(name), options),
+            })),
+            Some(_) => {}
+        }
+    }
+
+    for (name, attrs) in src_roles {
+        let src_members = attrs.get("member_of").and_then(Value::as_object);
+        let dst_members = dst_roles
+            .get(name)
+            .and_then(|a| a.get("member_of"))
+            .and_then(Value::as_object);
+
+        for group in src_members.into_iter().flat_map(|m| m.keys()) {
+            if !dst_members.is_some_and(|m| m.contains_key(group)) {
+                operations.push(Operation::Sql(ApplyStep {
+                    key: format!("roles.{}.member_of.{}", name, group),
+                    statement: format!("grant {} to {}", quote_ident(group), quote_ident(name)),
+                }));
+            }
+        }
+
+        for group in dst_members.into_iter().flat_map(|m| m.keys()) {
+            if !src_members.is_some_and(|m| m.contains_key(group)) {
+                operations.push(Operation::Sql(ApplyStep {
+                    key: format!("roles.{}.member_of.{}", name, group),
+                    statement: format!("revoke {} from {}", quote_ident(group), quote_ident(name)),
+                }));
+            }
+        }
+    }
+
+    for (kind, allowed) in PRIVILEGE_KINDS {
+        let src_grants = source
+            .get("grants")
+            .and_then(|g| g.get(kind))
+            .and_then(Value::as_object)
+            .unwrap_or(&empty);
+        let dst_grants = dest
+            .get("grants")
+            .and_then(|g| g.get(kind))
+            .and_then(Value::as_object)
+            .unwrap_or(&empty);
+
+        let mut revokes = Vec::new();
+
+        for (object, src_grantees) in src_grants {
+            let dst_grantees = dst_grants.get(object);
+            for_each_grantee_delta(src_grantees, dst_grantees, |grantee, privileges| {
+                let key = format!("grants.{}.{}.{}", kind, object, grantee);
+                operations.push(grant_operation(
+                    key,
+                    "grant",
+                    kind,
+                    allowed,
+                    object,
+                    grantee,
+                    &privileges,
+                ));
+            });
+        }
+
+        for (object, dst_grantees) in dst_grants {
+            let src_grantees = src_grants.get(object);
+            for_each_grantee_delta(dst_grantees, src_grantees, |grantee, privileges| {
+                let key = format!("grants.{}.{}.{}", kind, object, grantee);
+                revokes.push(grant_operation(
+                    key,
+                    "revoke",
+                    kind,
+                    allowed,
+                    object,
+                    grantee,
+                    &privileges,
+                ));
+            });
+        }
+
+        operations.extend(revokes);
+    }
+
+    operations
+}
+
+// Role attributes a non-superuser can set on roles it manages, and those only a superuser
+// can. Supabase projects are administered as the non-superuser `postgres` role.
+const ROLE_FLAGS: &[(&str, &str)] = &[
+    ("inherit", "INHERIT"),
+    ("createrole", "CREATEROLE"),
+    ("createdb", "CREATEDB"),
+    ("login", "LOGIN"),
+];
+
+const SUPERUSER_FLAGS: &[(&str, &str)] = &[
+    ("superuser", "SUPERUSER"),
+    ("replication", "REPLICATION"),
+    ("bypassrls", "BYPASSRLS"),
+];
+
+// Attribute values as `create role` sets them when no options are given, so a role that
+// is missing on the destination is compared against these.
+fn attribute(attrs: &Value, field: &str) -> Value {
+    attrs.get(field).cloned().unwrap_or_else(|| match field {
+        "inherit" => json!(true),
+        "connection_limit" => json!(-1),
+        _ => json!(false),
+    })
+}
+
+// Only the attributes that differ from `current`, so statements never touch flags the
+// caller may not be allowed to set.
+fn role_options(attrs: &Value, current: &Value) -> String {
+    let mut options: Vec<String> = ROLE_FLAGS
+        .iter()
+        .filter(|(field, _)| attribute(attrs, field) != attribute(current, field))
+        .map(|(field, flag)| {
+            if attribute(attrs, field).as_bool().unwrap_or(false) {
+                flag.to_string()
+            } else {
+                format!("NO{}", flag)
+            }
+        })
+        .collect();
+
+    let limit = attribute(attrs, "connection_limit");
+    if limit != attribute(current, "connection_limit")
+        && let Some(limit) = limit.as_i64()
+    {
+        options.push(format!("CONNECTION LIMIT {}", limit));
+    }
+
+    options.join(" ")
+}
+
+// Renders a GRANT or REVOKE with every name quoted, or a skip when the object name or a
+// privilege is not something the catalog query could have produced.
+fn grant_operation(
+    key: String,
+    action: &str,
+    kind: &str,
+    allowed: &[&str],
+    object: &str,
+    grantee: &str,
+    privileges: &[&str],
+) -> Operation {
+    let object_name = if kind == "function" {
+        quote_function_signature(object)
+    } else {
+        quote_qualified_name(object)
+    };
+    let Some(object_name) = object_name else {
+        return Operation::Skip {
+            key,
+            reason: format!("{} name {:?} is not a valid identifier", kind, object),
+        };
+    };
+
+    if let Some(privilege) = privileges.iter().find(|p| !allowed.contains(p)) {
+        return Operation::Skip {
+            key,
+            reason: format!("{} is not a {} privilege", privilege, kind),
+        };
+    }
+
+    let grantee = if grantee == "PUBLIC" {
+        "public".to_string()
+    } else {
+        quote_ident(grantee)
+    };
+
+    let preposition = if action == "grant" { "to" } else { "from" };
+    Operation::Sql(ApplyStep {
+        key,
+        statement: format!(
+            "{} {} on {} {} {} {}",
+            action,
+            privileges.join(", "),
+            kind,
+            object_name,
+            preposition,
+            grantee
+        ),
+    })
+}
+
+// Calls `f` with every grantee holding privileges in `from` that are missing in `other`.
+fn for_each_grantee_delta(from: &Value, other: Option<&Value>, mut f: impl FnMut(&str, Vec<&str>)) {
+    let Some(from) = from.as_object() else {
+        return;
+    };
+
+    for (grantee, privileges) in from {
+        let other_privileges = other.and_then(|o| o.get(grantee));
+        let missing: Vec<&str> = privileges
+            .as_object()
+            .into_iter()
+            .flat_map(|p| p.keys())
+            .filter(|p| other_privileges.and_then(|o| o.get(p.as_str())).is_none())
+            .map(String::as_str)
+            .collect();
+
+        if !missing.is_empty() {
+            f(grantee, missing);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn statements(operations: &[Operation]) -> Vec<&str> {
+        operations
+            .iter()
+            .map(|operation| match operation {
+                Operation::Sql(step) => step.statement.as_str(),
+                Operation::Skip { reason, .. } => reason.as_str(),
+                Operation::Api(write) => write.path.as_str(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_plan_creates_missing_roles_and_memberships() {
+        let source: Value = serde_json::from_str(
+            r#"{
+                "roles": {
+                    "reporting": {
+                        "superuser": false, "inherit": true, "createrole": false,
+                        "createdb": false, "login": true, "replication": false,
+                        "bypassrls": false, "connection_limit": -1,
+                        "member_of": {"analyst": true}
+                    }
+                },
+                "grants": {}
+            }"#,
+        )
+        .unwrap();
+        let dest: Value = serde_json::from_str(r#"{"roles": {}, "grants": {}}"#).unwrap();
+
+        let operations = plan_roles(&source, &dest);
+
+        assert_eq!(
+            statements(&operations),
+            vec![
+                "create role \"reporting\" LOGIN",
+                "grant \"analyst\" to \"reporting\"",
+            ]
+        );
+        assert_eq!(operations[0].written_keys(), vec!["roles.reporting"]);
+    }
+
+    #[test]
+    fn test_plan_alters_only_changed_attributes() {
+        let source: Value = serde_json::from_str(
+            r#"{
+                "roles": {
+                    "reporting": {
+                        "superuser": false, "inherit": true, "createrole": false,
+                        "createdb": false, "login": true, "replication": true,
+                        "bypassrls": false, "connection_limit": 10, "member_of": {}
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+        let dest: Value = serde_json::from_str(
+            r#"{
+                "roles": {
+                    "reporting": {
+                        "superuser": false, "inherit": true, "createrole": false,
+                        "createdb": false, "login": true, "replication": false,
+                        "bypassrls": false, "connection_limit": -1, "member_of": {}
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let operations = plan_roles(&source, &dest);
+
+        assert_eq!(
+            statements(&operations),
+            vec![
+                "REPLICATION can only be changed by a superuser",
+                "alter role \"reporting\" with CONNECTION LIMIT 10",
+            ]
+        );
+        assert!(operations[0].written_keys().is_empty());
+    }
+
+    #[test]
+    fn test_plan_grants_and_revokes() {
+        let source: Value = serde_json::from_str(
+            r#"{
+                "roles": {},
+                "grants": {
+                    "table": {
+                        "public.todos": {
+                            "anon": {"SELECT": true},
+                            "authenticated": {"INSERT": true, "SELECT": true}
+                        }
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+        let dest: Value = serde_json::from_str(
+            r#"{
+                "roles": {},
+                "grants": {
+                    "table": {
+                        "public.todos": {
+                            "anon": {"DELETE": true, "SELECT": true},
+                            "authenticated": {"SELECT": true}
+                        }
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let operations = plan_roles(&source, &dest);
+
+        assert_eq!(
+            statements(&operations),
+            vec![
+                "grant INSERT on table \"public\".\"todos\" to \"authenticated\"",
+                "revoke DELETE on table \"public\".\"todos\" from \"anon\"",
+            ]
+        );
+        assert_eq!(
+            operations[0].written_keys(),
+            vec!["grants.table.public.todos.authenticated"]
+        );
+    }
+
+    #[test]
+    fn test_plan_skips_unsafe_grants() {
+        let source: Value = serde_json::from_str(
+            r#"{
+                "grants": {
+                    "table": {
+                        "public.todos; drop table users": {"anon": {"SELECT": true}},
+                        "public.notes": {"anon": {"SELECT, DELETE": true}}
+                    },
+                    "function": {
+                        "public.add(a integer, b integer)": {"PUBLIC": {"EXECUTE": true}}
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+        let dest: Value = serde_json::from_str(r#"{"roles": {}, "grants": {}}"#).unwrap();
+
+        let operations = plan_roles(&source, &dest);
+
+        assert_eq!(
+            statements(&operations),
+            vec![
+                "SELECT, DELETE is not a table privilege",
+                "table name \"public.todos; drop table users\" is not a valid identifier",
+                "grant EXECUTE on function \"public\".\"add\"(a integer, b integer) to public",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_plan_leaves_matching_roles_alone() {
+        let config: Value = serde_json::from_str(
+            r#"{
+                "roles": {
+                    "reporting": {"login": true, "connection_limit": 5, "member_of": {}}
+                },
+                "grants": {"schema": {"app": {"reporting": {"USAGE": true}}}}
+            }"#,
+        )
+        .unwrap();
+
+        assert!(plan_roles(&config, &config).is_empty());
+    }
+}
@@ -0,0 +1,299 @@
+use crate::error::PreviewError;
+use crate::services::diff::glob_match;
+use crate::services::reconcile::{ServicePlan, plan_diffs, plan_service};
+
+use serde_json::{Map, Value};
+use std::collections::BTreeSet;
+
+/// Which keys of a diff to apply, parsed from patterns such as `EXTERNAL_GOOGLE_*` or
+/// `!SMTP_*`. Patterns match diff keys case-insensitively, and a pattern matching a key also
+/// matches the keys nested under it. Without include patterns every key is included.
+#[derive(Debug, Default)]
+pub struct KeySelection {
+    include: Vec<String>,
+    exclude: Vec<String>,
+}
+
+impl KeySelection {
+    pub fn parse(patterns: &[String]) -> Result<Self, PreviewError> {
+        let mut selection = KeySelection::default();
+        for pattern in patterns {
+            let (list, glob) = match pattern.strip_prefix('!') {
+                Some(glob) => (&mut selection.exclude, glob),
+                None => (&mut selection.include, pattern.as_str()),
+            };
+            if glob.trim().is_empty() {
+                return Err(PreviewError::BadRequest(format!(
+                    "Invalid key pattern: `{}`",
+                    pattern
+                )));
+            }
+            list.push(glob.trim().to_lowercase());
+        }
+        Ok(selection)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    pub fn selects(&self, key: &str) -> bool {
+        let key = key.to_lowercase();
+        (self.include.is_empty() || self.include.iter().any(|glob| matches(glob, &key)))
+            && !self.exclude.iter().any(|glob| matches(glob, &key))
+    }
+}
+
+/// Splits a comma separated list of key patterns, as passed in query strings.
+pub fn split_patterns(list: Option<&str>) -> Vec<String> {
+    list.into_iter()
+        .flat_map(|list| list.split(','))
+        .map(str::trim)
+        .filter(|pattern| !pattern.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Whether `glob` matches `key` or one of the keys it is nested under.
+fn matches(glob: &str, key: &str) -> bool {
+    glob_match(glob, key)
+        || key
+            .match_indices('.')
+            .any(|(index, _)| glob_match(glob, &key[..index]))
+}
+
+/// Narrows the plans to the selected keys, re-planning each service so unselected keys keep
+/// their destination value. Every pattern must match a current difference, so a selection
+/// made against an older diff is rejected rather than silently applying something else.
+pub fn select_plans(
+    patterns: &[String],
+    plans: Vec<ServicePlan>,
+    declared_ref: &str,
+    project_id: &str,
+) -> Result<Vec<ServicePlan>, PreviewError> {
+    let selection = KeySelection::parse(patterns)?;
+    if selection.is_empty() {
+        return Ok(plans);
+    }
+
+    let keys: Vec<String> = plan_diffs(&plans)?
+        .into_iter()
+        .flat_map(|config| config.diffs)
+        .map(|diff| diff.key.to_lowercase())
+        .collect();
+    for pattern in patterns {
+        let glob = pattern.trim_start_matches('!').trim().to_lowercase();
+        if !keys.iter().any(|key| matches(&glob, key)) {
+            return Err(PreviewError::BadRequest(format!(
+                "Key selection is out of date: `{}` matches no current difference",
+                pattern
+            )));
+        }
+    }
+    if !keys.iter().any(|key| selection.selects(key)) {
+        return Err(PreviewError::BadRequest(
+            "The key selection leaves nothing to apply".to_string(),
+        ));
+    }
+
+    Ok(plans
+        .into_iter()
+        .map(|plan| {
+            let source = select_value("", Some(&plan.source), Some(&plan.dest), &selection)
+                .unwrap_or(Value::Null);
+            ServicePlan {
+                service: plan.service,
+                operations: plan_service(
+                    plan.service,
+                    &source,
+                    &plan.dest,
+                    declared_ref,
+                    project_id,
+                ),
+                source,
+                dest: plan.dest,
+            }
+        })
+        .collect())
+}
+
+/// Builds the source value to apply, taking the destination value for every difference the
+/// selection leaves out. Keys follow diff keys; lists without ids are selected as a whole or
+/// by any of their `[index]` keys.
+fn select_value(
+    path: &str,
+    source: Option<&Value>,
+    dest: Option<&Value>,
+    selection: &KeySelection,
+) -> Option<Value> {
+    if source == dest {
+        return source.cloned();
+    }
+
+    match (source, dest) {
+        (Some(Value::Object(src)), Some(Value::Object(dst))) => {
+            let keys: BTreeSet<&String> = src.keys().chain(dst.keys()).collect();
+            let mut selected = Map::new();
+            for key in keys {
+                if let Some(value) =
+                    select_value(&join(path, key), src.get(key), dst.get(key), selection)
+                {
+                    selected.insert(key.clone(), value);
+                }
+            }
+            return Some(Value::Object(selected));
+        }
+        (Some(Value::Array(src)), Some(Value::Array(dst))) => {
+            if let (Some(src_ids), Some(dst_ids)) = (item_ids(src), item_ids(dst)) {
+                let mut ids: Vec<&str> = src_ids;
+                for id in dst_ids {
+                    if !ids.contains(&id) {
+                        ids.push(id);
+                    }
+                }
+                return Some(Value::Array(
+                    ids.into_iter()
+                        .filter_map(|id| {
+                            select_value(
+                                &join(path, &format!("id:{}", id)),
+                                find_by_id(src, id),
+                                find_by_id(dst, id),
+                                selection,
+                            )
+                        })
+                        .collect(),
+                ));
+            }
+
+            let indexed = (0..src.len().max(dst.len()))
+                .any(|index| selection.selects(&format!("{}[{}]", path, index)));
+            return if indexed || selection.selects(path) {
+                source.cloned()
+            } else {
+                dest.cloned()
+            };
+        }
+        _ => {}
+    }
+
+    let key = if path.is_empty() { "root" } else { path };
+    if selection.selects(key) {
+        source.cloned()
+    } else {
+        dest.cloned()
+    }
+}
+
+/// The `id` of every item, or `None` unless every item has one.
+fn item_ids(items: &[Value]) -> Option<Vec<&str>> {
+    items
+        .iter()
+        .map(|item| item.get("id").and_then(Value::as_str))
+        .collect()
+}
+
+fn find_by_id<'a>(items: &'a [Value], id: &str) -> Option<&'a Value> {
+    items
+        .iter()
+        .find(|item| item.get("id").and_then(Value::as_str) == Some(id))
+}
+
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::Service;
+    use serde_json::json;
+
+    fn patterns(patterns: &[&str]) -> Vec<String> {
+        patterns.iter().map(|pattern| pattern.to_string()).collect()
+    }
+
+    fn auth_plan() -> ServicePlan {
+        let source = json!({
+            "external_google_enabled": true,
+            "external_google_client_id": "google-id",
+            "smtp_host": "smtp.example.com",
+            "site_url": "https://stg.example.com"
+        });
+        let dest = json!({
+            "external_google_enabled": false,
+            "external_google_client_id": "",
+            "smtp_host": "",
+            "site_url": "https://example.com"
+        });
+        ServicePlan {
+            service: Service::Auth,
+            operations: plan_service(Service::Auth, &source, &dest, "stg", "prd"),
+            source,
+            dest,
+        }
+    }
+
+    #[test]
+    fn test_selection_patterns() {
+        let selection =
+            KeySelection::parse(&patterns(&["EXTERNAL_*", "id:hello", "!*_secret"])).unwrap();
+
+        assert!(selection.selects("external_google_enabled"));
+        assert!(!selection.selects("external_google_secret"));
+        assert!(selection.selects("id:hello.verify_jwt"));
+        assert!(!selection.selects("site_url"));
+
+        let excluding = KeySelection::parse(&patterns(&["!SMTP_*"])).unwrap();
+        assert!(excluding.selects("site_url"));
+        assert!(!excluding.selects("smtp_host"));
+
+        assert!(KeySelection::parse(&patterns(&["!"])).is_err());
+    }
+
+    #[test]
+    fn test_select_plans_keeps_unselected_destination_values() {
+        let plans = select_plans(
+            &patterns(&["EXTERNAL_GOOGLE_*", "!SMTP_*"]),
+            vec![auth_plan()],
+            "stg",
+            "prd",
+        )
+        .unwrap();
+
+        assert_eq!(
+            plans[0].source,
+            json!({
+                "external_google_enabled": true,
+                "external_google_client_id": "google-id",
+                "smtp_host": "",
+                "site_url": "https://example.com"
+            })
+        );
+        let keys: Vec<&str> = plans[0]
+            .operations
+            .iter()
+            .flat_map(|operation| operation.written_keys())
+            .collect();
+        assert!(keys.contains(&"external_google_client_id"));
+        assert!(!keys.contains(&"smtp_host"));
+        assert!(!keys.contains(&"site_url"));
+    }
+
+    #[test]
+    fn test_stale_selection_is_rejected() {
+        let stale = select_plans(
+            &patterns(&["EXTERNAL_GITHUB_*"]),
+            vec![auth_plan()],
+            "stg",
+            "prd",
+        );
+        assert!(matches!(stale, Err(PreviewError::BadRequest(_))));
+
+        let empty = select_plans(&patterns(&["!*"]), vec![auth_plan()], "stg", "prd");
+        assert!(matches!(empty, Err(PreviewError::BadRequest(_))));
+    }
+}
@@ -0,0 +1,326 @@
+use crate::error::PreviewError;
+use crate::models::migrate::ProjectConfig;
+use crate::models::snapshot::{ProjectSnapshot, SNAPSHOT_VERSION};
+use crate::models::transform::TransformConfig;
+use crate::services::diff::json_diff;
+use crate::services::management_api::ManagementApi;
+use crate::services::redact::{is_masked, is_sensitive_key};
+use crate::services::transform::transform_source;
+use crate::services::{Service, fetch_service_config, now_rfc3339};
+
+use serde_json::{Value, json};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Fetches every supported service for a project into a single snapshot document, leaving
+/// out credentials such as `smtp_pass` and `jwt_secret` so the document can be shared.
+pub async fn build_snapshot(
+    api: &ManagementApi,
+    project_id: &str,
+) -> Result<ProjectSnapshot, PreviewError> {
+    let mut snapshot = build_partial_snapshot(api, project_id, &Service::ALL).await?;
+    remove_secrets(&mut snapshot);
+    Ok(snapshot)
+}
+
+/// Fetches the given services for a project into a snapshot document. Credentials are kept,
+/// so the result is for planning against another project and is never written out.
+pub async fn build_partial_snapshot(
+    api: &ManagementApi,
+    project_id: &str,
+    selected: &[Service],
+) -> Result<ProjectSnapshot, PreviewError> {
+    let mut services = BTreeMap::new();
+
+    for &service in selected {
+        let config = fetch_service_config(api, service, project_id)
+            .await
+            .map_err(|e| {
+                PreviewError::ApiError(format!("Failed to get {} config: {:?}", service, e))
+            })?;
+        services.insert(service, snapshot_config(service, config));
+    }
+
+    Ok(ProjectSnapshot {
+        snapshot_version: SNAPSHOT_VERSION,
+        project_ref: project_id.to_string(),
+        created_at: now_rfc3339(),
+        tool_version: env!("CARGO_PKG_VERSION").to_string(),
+        services,
+    })
+}
+
+/// Parses an uploaded snapshot document, accepting either JSON or YAML.
+pub fn parse_snapshot(body: &str) -> Result<ProjectSnapshot, PreviewError> {
+    let snapshot: ProjectSnapshot = match serde_json::from_str(body) {
+        Ok(snapshot) => snapshot,
+        Err(_) => serde_yaml::from_str(body)
+            .map_err(|e| PreviewError::BadRequest(format!("Invalid snapshot document: {}", e)))?,
+    };
+
+    if snapshot.snapshot_version > SNAPSHOT_VERSION {
+        return Err(PreviewError::BadRequest(format!(
+            "Unsupported snapshot version {} (expected {} or lower)",
+            snapshot.snapshot_version, SNAPSHOT_VERSION
+        )));
+    }
+
+    Ok(snapshot)
+}
+
+/// Diffs two snapshot documents service by service, after applying the transform rules for
+/// their projects. A service missing from one side is compared against `null`.
+pub async fn diff_snapshots(
+    source: &ProjectSnapshot,
+    dest: &ProjectSnapshot,
+    transforms: &TransformConfig,
+) -> Result<Vec<ProjectConfig>, PreviewError> {
+    let rules = transforms.rules_for(&source.project_ref, &dest.project_ref);
+    let services: BTreeSet<Service> = source
+        .services
+        .keys()
+        .chain(dest.services.keys())
+        .copied()
+        .collect();
+    let mut configs = Vec::new();
+
+    for service in services {
+        let source_value = source
+            .services
+            .get(&service)
+            .cloned()
+            .unwrap_or(Value::Null);
+        let dest_value = dest.services.get(&service).cloned().unwrap_or(Value::Null);
+        let source_value = transform_source(rules, service, source_value, &dest_value);
+
+        if let Some(config) =
+            json_diff(service.name().to_string(), source_value, dest_value).await?
+        {
+            configs.push(config);
+        }
+    }
+
+    Ok(configs)
+}
+
+/// Normalizes a live service config into the form stored in snapshots. Secrets are reduced
+/// to their names so snapshot files never carry secret values.
+pub fn snapshot_config(service: Service, config: Value) -> Value {
+    match (service, config) {
+        (Service::Secrets, Value::Array(secrets)) => Value::Array(
+            secrets
+                .iter()
+                .filter_map(|secret| secret.get("name"))
+                .map(|name| json!({ "name": name }))
+                .collect(),
+        ),
+        (_, config) => config,
+    }
+}
+
+/// Leaves out the credentials a snapshot does not carry. Sensitive keys that are missing or
+/// masked on the snapshot side are removed from both sides, so they are neither compared
+/// nor written over the live value.
+pub fn drop_unset_secrets(service: Service, snapshot: &mut Value, live: &mut Value) {
+    let unset: Vec<String> = snapshot
+        .as_object()
+        .into_iter()
+        .chain(live.as_object())
+        .flat_map(|config| config.keys())
+        .filter(|key| is_sensitive_key(service, key))
+        .filter(|key| snapshot.get(key.as_str()).is_none_or(is_masked))
+        .cloned()
+        .collect();
+
+    for config in [snapshot, live] {
+        if let Some(config) = config.as_object_mut() {
+            for key in &unset {
+                config.remove(key);
+            }
+        }
+    }
+}
+
+fn remove_secrets(snapshot: &mut ProjectSnapshot) {
+    for (service, config) in snapshot.services.iter_mut() {
+        if let Some(config) = config.as_object_mut() {
+            config.retain(|key, _| !is_sensitive_key(*service, key));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_strips_secret_values() {
+        let secrets: Value = serde_json::from_str(
+            r#"[
+                {"name": "STRIPE_KEY", "value": "sk_live_123"},
+                {"name": "SUPABASE_URL", "value": "https://abc.supabase.co"}
+            ]"#,
+        )
+        .unwrap();
+
+        let config = snapshot_config(Service::Secrets, secrets);
+
+        assert_eq!(
+            config,
+            json!([{"name": "STRIPE_KEY"}, {"name": "SUPABASE_URL"}])
+        );
+    }
+
+    #[test]
+    fn test_snapshot_leaves_out_credentials() {
+        let mut services = BTreeMap::new();
+        services.insert(
+            Service::Auth,
+            json!({
+                "site_url": "https://example.com",
+                "smtp_pass": "hunter2",
+                "external_github_secret": "gh-secret"
+            }),
+        );
+        services.insert(
+            Service::Postgrest,
+            json!({"max_rows": 1000, "jwt_secret": "super-secret-jwt"}),
+        );
+        let mut snapshot = ProjectSnapshot {
+            snapshot_version: SNAPSHOT_VERSION,
+            project_ref: "abc".to_string(),
+            created_at: "2025-01-01T00:00:00Z".to_string(),
+            tool_version: "0.1.0".to_string(),
+            services,
+        };
+
+        remove_secrets(&mut snapshot);
+
+        let document = serde_json::to_string(&snapshot).unwrap();
+        assert!(!document.contains("smtp_pass") && !document.contains("hunter2"));
+        assert!(!document.contains("jwt_secret") && !document.contains("super-secret-jwt"));
+        assert_eq!(
+            snapshot.services[&Service::Auth],
+            json!({"site_url": "https://example.com"})
+        );
+        assert_eq!(snapshot.services[&Service::Postgrest]["max_rows"], 1000);
+    }
+
+    #[test]
+    fn test_unset_secrets_are_not_planned() {
+        let mut snapshot = json!({
+            "site_url": "https://example.com",
+            "smtp_pass": "[REDACTED 0123456789ab]",
+            "external_google_secret": "new-secret"
+        });
+        let mut live = json!({
+            "site_url": "https://old.example.com",
+            "smtp_pass": "hunter2",
+            "external_github_secret": "gh-secret",
+            "external_google_secret": "old-secret"
+        });
+
+        drop_unset_secrets(Service::Auth, &mut snapshot, &mut live);
+
+        assert_eq!(
+            snapshot,
+            json!({"site_url": "https://example.com", "external_google_secret": "new-secret"})
+        );
+        assert_eq!(
+            live,
+            json!({"site_url": "https://old.example.com", "external_google_secret": "old-secret"})
+        );
+    }
+
+    #[test]
+    fn test_parse_snapshot_json_and_yaml() {
+        let json_doc = r#"{
+            "snapshot_version": 1,
+            "project_ref": "abc",
+            "created_at": "2025-01-01T00:00:00Z",
+            "tool_version": "0.1.0",
+            "services": {"postgrest": {"max_rows": 1000}}
+        }"#;
+        let yaml_doc = "snapshot_version: 1
+project_ref: abc
+created_at: 2025-01-01T00:00:00Z
+tool_version: 0.1.0
+services:
+  postgrest:
+    max_rows: 1000
+";
+
+        for doc in [json_doc, yaml_doc] {
+            let snapshot = parse_snapshot(doc).unwrap();
+            assert_eq!(snapshot.project_ref, "abc");
+            assert_eq!(snapshot.services[&Service::Postgrest]["max_rows"], 1000);
+        }
+    }
+
+    #[test]
+    fn test_parse_snapshot_rejects_newer_version() {
+        let doc = r#"{
+            "snapshot_version": 99,
+            "project_ref": "abc",
+            "created_at": "2025-01-01T00:00:00Z",
+            "tool_version": "9.0.0",
+            "services": {}
+        }"#;
+
+        assert!(matches!(
+            parse_snapshot(doc),
+            Err(PreviewError::BadRequest(_))
+        ));
+    }
+
+    #[test]
+    fn test_snapshot_yaml_round_trip() {
+        let mut services = BTreeMap::new();
+        services.insert(Service::Auth, json!({"site_url": "https://example.com"}));
+        services.insert(Service::EdgeFunctions, json!([{"slug": "hello"}]));
+
+        let snapshot = ProjectSnapshot {
+            snapshot_version: SNAPSHOT_VERSION,
+            project_ref: "abc".to_string(),
+            created_at: "2025-01-01T00:00:00Z".to_string(),
+            tool_version: "0.1.0".to_string(),
+            services,
+        };
+
+        let yaml = serde_yaml::to_string(&snapshot).unwrap();
+        assert!(yaml.contains("edge_functions:"));
+
+        let parsed: ProjectSnapshot = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(
+            parsed.services[&Service::Auth]["site_url"],
+            "https://example.com"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_diff_snapshots() {
+        let snapshot = |services: BTreeMap<Service, Value>| ProjectSnapshot {
+            snapshot_version: SNAPSHOT_VERSION,
+            project_ref: "abc".to_string(),
+            created_at: "2025-01-01T00:00:00Z".to_string(),
+            tool_version: "0.1.0".to_string(),
+            services,
+        };
+        let source = snapshot(BTreeMap::from([
+            (Service::Auth, json!({"site_url": "https://a.example.com"})),
+            (Service::Postgrest, json!({"max_rows": 1000})),
+        ]));
+        let dest = snapshot(BTreeMap::from([
+            (Service::Auth, json!({"site_url": "https://b.example.com"})),
+            (Service::Postgrest, json!({"max_rows": 1000})),
+            (Service::Cron, json!({})),
+        ]));
+
+        let configs = diff_snapshots(&source, &dest, &TransformConfig::default())
+            .await
+            .unwrap();
+
+        let names: Vec<&str> = configs.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["Auth", "Cron"]);
+        assert_eq!(configs[0].diffs[0].key, "site_url");
+    }
+}
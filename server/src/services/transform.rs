@@ -0,0 +1,269 @@
+use crate::models::transform::{Transform, TransformConfig, TransformRule};
+use crate::services::diff::glob_match;
+use crate::services::{Service, load_config_file};
+
+use serde_json::Value;
+
+/// Reads the transform rules, accepting either JSON or YAML. Each project pair may only be
+/// listed once.
+pub fn load_transform_config(path: &str) -> Result<TransformConfig, String> {
+    let config: TransformConfig = load_config_file(path, "transform config")?;
+
+    for (index, pair) in config.pairs.iter().enumerate() {
+        if config.pairs[..index]
+            .iter()
+            .any(|other| other.source == pair.source && other.dest == pair.dest)
+        {
+            return Err(format!(
+                "Duplicate transform pair: {} to {}",
+                pair.source, pair.dest
+            ));
+        }
+        if pair.rules.iter().any(|rule| rule.key.is_empty()) {
+            return Err(format!(
+                "Transform rule with an empty key for {} to {}",
+                pair.source, pair.dest
+            ));
+        }
+    }
+
+    Ok(config)
+}
+
+/// Rewrites the environment specific values of a source config before it is diffed against
+/// or applied to `dest`. The first rule matching a key wins, and keys under a matched key
+/// are not matched again.
+pub fn transform_source(
+    rules: &[TransformRule],
+    service: Service,
+    mut source: Value,
+    dest: &Value,
+) -> Value {
+    let rules: Vec<&TransformRule> = rules
+        .iter()
+        .filter(|rule| {
+            rule.service
+                .is_none_or(|rule_service| rule_service == service)
+        })
+        .collect();
+    if !rules.is_empty() {
+        transform_children(&rules, "", &mut source, Some(dest));
+    }
+    source
+}
+
+/// Transforms the members of an object or array. Paths follow diff keys: object members
+/// are joined with dots, array items with an `id` are named `id:<id>` and others `[index]`.
+fn transform_children(
+    rules: &[&TransformRule],
+    path: &str,
+    value: &mut Value,
+    dest: Option<&Value>,
+) {
+    match value {
+        Value::Object(map) => map.retain(|key, child| {
+            let child_path = join(path, key);
+            transform_value(
+                rules,
+                &child_path,
+                child,
+                dest.and_then(|dest| dest.get(key)),
+            )
+        }),
+        Value::Array(items) => {
+            let mut index = 0;
+            items.retain_mut(|item| {
+                let (child_path, child_dest) = match item.get("id").and_then(Value::as_str) {
+                    Some(id) => (
+                        join(path, &format!("id:{}", id)),
+                        dest.and_then(|dest| find_by_id(dest, id)),
+                    ),
+                    None => (
+                        format!("{}[{}]", path, index),
+                        dest.and_then(|dest| dest.get(index)),
+                    ),
+                };
+                index += 1;
+                transform_value(rules, &child_path, item, child_dest)
+            });
+        }
+        _ => {}
+    }
+}
+
+/// Returns whether the value stays. A kept destination value the destination lacks is
+/// dropped from the source.
+fn transform_value(
+    rules: &[&TransformRule],
+    path: &str,
+    value: &mut Value,
+    dest: Option<&Value>,
+) -> bool {
+    let Some(rule) = rules.iter().find(|rule| glob_match(&rule.key, path)) else {
+        transform_children(rules, path, value, dest);
+        return true;
+    };
+
+    match &rule.transform {
+        Transform::Replace { from, to } => {
+            rewrite_strings(value, &|text| text.replace(from.as_str(), to))
+        }
+        Transform::Regex {
+            pattern,
+            replacement,
+        } => rewrite_strings(value, &|text| {
+            pattern
+                .0
+                .replace_all(text, replacement.as_str())
+                .into_owned()
+        }),
+        Transform::Fixed { value: fixed } => *value = fixed.clone(),
+        Transform::KeepDestination => match dest {
+            Some(dest) => *value = dest.clone(),
+            None => return false,
+        },
+    }
+    true
+}
+
+fn rewrite_strings(value: &mut Value, rewrite: &dyn Fn(&str) -> String) {
+    match value {
+        Value::String(text) => *text = rewrite(text),
+        Value::Array(items) => items
+            .iter_mut()
+            .for_each(|item| rewrite_strings(item, rewrite)),
+        Value::Object(map) => map
+            .values_mut()
+            .for_each(|item| rewrite_strings(item, rewrite)),
+        _ => {}
+    }
+}
+
+fn find_by_id<'a>(dest: &'a Value, id: &str) -> Option<&'a Value> {
+    dest.as_array()?
+        .iter()
+        .find(|item| item.get("id").and_then(Value::as_str) == Some(id))
+}
+
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::transform::TransformPattern;
+    use serde_json::json;
+
+    fn rule(key: &str, transform: Transform) -> TransformRule {
+        TransformRule {
+            service: None,
+            key: key.to_string(),
+            transform,
+        }
+    }
+
+    #[test]
+    fn test_transform_auth_settings() {
+        let rules = vec![
+            rule(
+                "site_url",
+                Transform::Replace {
+                    from: "staging.example.com".to_string(),
+                    to: "example.com".to_string(),
+                },
+            ),
+            rule(
+                "uri_allow_list",
+                Transform::Regex {
+                    pattern: TransformPattern::try_from(r"https://(\w+)\.staging\.".to_string())
+                        .unwrap(),
+                    replacement: "https://$1.".to_string(),
+                },
+            ),
+            rule(
+                "smtp_sender_name",
+                Transform::Fixed {
+                    value: json!("Example"),
+                },
+            ),
+            rule("hook_*_uri", Transform::KeepDestination),
+        ];
+        let source = json!({
+            "site_url": "https://staging.example.com",
+            "uri_allow_list": "https://app.staging.example.com,https://admin.staging.example.com",
+            "smtp_sender_name": "Example (staging)",
+            "hook_custom_access_token_uri": "pg-functions://staging/hook",
+            "hook_send_sms_uri": "pg-functions://staging/sms",
+            "jwt_exp": 3600
+        });
+        let dest = json!({
+            "site_url": "https://example.com",
+            "hook_custom_access_token_uri": "pg-functions://prod/hook"
+        });
+
+        let transformed = transform_source(&rules, Service::Auth, source, &dest);
+
+        assert_eq!(
+            transformed,
+            json!({
+                "site_url": "https://example.com",
+                "uri_allow_list": "https://app.example.com,https://admin.example.com",
+                "smtp_sender_name": "Example",
+                "hook_custom_access_token_uri": "pg-functions://prod/hook",
+                "jwt_exp": 3600
+            })
+        );
+    }
+
+    #[test]
+    fn test_rules_follow_diff_keys_and_services() {
+        let mut jwt = rule("id:*.verify_jwt", Transform::Fixed { value: json!(true) });
+        jwt.service = Some(Service::EdgeFunctions);
+        let rules = vec![jwt];
+        let source = json!([{"id": "a", "verify_jwt": false}, {"id": "b", "verify_jwt": false}]);
+
+        let transformed =
+            transform_source(&rules, Service::EdgeFunctions, source.clone(), &json!([]));
+        assert_eq!(
+            transformed,
+            json!([{"id": "a", "verify_jwt": true}, {"id": "b", "verify_jwt": true}])
+        );
+
+        // Rules scoped to another service leave the config alone
+        assert_eq!(
+            transform_source(&rules, Service::Auth, source.clone(), &json!([])),
+            source
+        );
+    }
+
+    #[test]
+    fn test_rules_for_pair() {
+        let config: TransformConfig = serde_yaml::from_str(
+            r#"
+pairs:
+  - source: stg
+    dest: prd
+    rules:
+      - key: site_url
+        transform: {kind: regex, pattern: "staging\\.", replacement: ""}
+      - key: smtp_admin_email
+        transform: {kind: keep_destination}
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(config.rules_for("stg", "prd").len(), 2);
+        assert!(config.rules_for("prd", "stg").is_empty());
+        assert!(
+            serde_yaml::from_str::<TransformConfig>(
+                "pairs: [{source: a, dest: b, rules: [{key: x, transform: {kind: regex, pattern: '(', replacement: ''}}]}]"
+            )
+            .is_err()
+        );
+    }
+}
@@ -0,0 +1,316 @@
+use crate::error::PreviewError;
+use crate::models::migrate::{ApplyStep, HostRewrite, Operation};
+use crate::services::database::{query_config, quote_ident, quote_qualified_name, sql_array};
+use crate::services::management_api::ManagementApi;
+
+use serde_json::{Map, Value, json};
+
+// Schemas whose triggers are installed and maintained by Supabase services. Triggers on
+// `auth` tables are kept since user triggers such as `on_auth_user_created` live there.
+const MANAGED_SCHEMAS: &[&str] = &[
+    "pg_catalog",
+    "information_schema",
+    "storage",
+    "realtime",
+    "_realtime",
+    "_analytics",
+    "extensions",
+    "graphql",
+    "graphql_public",
+    "net",
+    "pgbouncer",
+    "pgsodium",
+    "pgsodium_masks",
+    "vault",
+    "cron",
+    "supabase_functions",
+    "supabase_migrations",
+];
+
+// Database webhooks are triggers calling this function with
+// (url, method, headers, params, timeout_ms) as trigger arguments.
+const WEBHOOK_FUNCTION: &str = "supabase_functions.http_request";
+
+fn triggers_query() -> String {
+    format!(
+        "select coalesce((
+            select json_object_agg(t.table_name, t.triggers order by t.table_name)
+            from (
+                select quote_ident(n.nspname) || '.' || quote_ident(c.relname) as table_name,
+                    json_object_agg(tg.tgname, json_build_object(
+                        'timing', case
+                            when tg.tgtype & 2 = 2 then 'BEFORE'
+                            when tg.tgtype & 64 = 64 then 'INSTEAD OF'
+                            else 'AFTER'
+                        end,
+                        'events', array_to_string(array_remove(array[
+                            case when tg.tgtype & 4 = 4 then 'INSERT' end,
+                            case when tg.tgtype & 16 = 16 then 'UPDATE' end,
+                            case when tg.tgtype & 8 = 8 then 'DELETE' end,
+                            case when tg.tgtype & 32 = 32 then 'TRUNCATE' end
+                        ], null), ' OR '),
+                        'level', case when tg.tgtype & 1 = 1 then 'ROW' else 'STATEMENT' end,
+                        'function', quote_ident(pn.nspname) || '.' || quote_ident(p.proname),
+                        'arguments', case when tg.tgnargs = 0 then '[]'::json else to_json((
+                            string_to_array(
+                                replace(encode(tg.tgargs, 'escape'), '\\\\', '\\'),
+                                '\\000'
+                            )
+                        )[1:tg.tgnargs]) end,
+                        'enabled', tg.tgenabled <> 'D',
+                        'definition', pg_get_triggerdef(tg.oid)
+                    ) order by tg.tgname) as triggers
+                from pg_trigger tg
+                join pg_class c on c.oid = tg.tgrelid
+                join pg_namespace n on n.oid = c.relnamespace
+                join pg_proc p on p.oid = tg.tgfoid
+                join pg_namespace pn on pn.oid = p.pronamespace
+                where not tg.tgisinternal
+                    and n.nspname <> all({schemas}) and n.nspname not like 'pg\\_%'
+                    and not exists (select 1 from pg_depend d where d.objid = tg.oid and d.deptype = 'e')
+                group by 1
+            ) t
+        ), '{{}}'::json) as config",
+        schemas = sql_array(MANAGED_SCHEMAS),
+    )
+}
+
+pub async fn fetch_triggers(api: &ManagementApi, project_id: &str) -> Result<Value, PreviewError> {
+    let mut config = query_config(api, project_id, &triggers_query()).await?;
+    describe_webhooks(&mut config);
+    Ok(config)
+}
+
+// Adds a `webhook` object to every database webhook trigger so the target URL, method
+// and headers are compared as individual keys instead of positional arguments.
+fn describe_webhooks(config: &mut Value) {
+    let Some(tables) = config.as_object_mut() else {
+        return;
+    };
+
+    for trigger in tables
+        .values_mut()
+        .filter_map(Value::as_object_mut)
+        .flat_map(|triggers| triggers.values_mut())
+        .filter_map(Value::as_object_mut)
+    {
+        if trigger.get("function").and_then(Value::as_str) != Some(WEBHOOK_FUNCTION) {
+            continue;
+        }
+
+        let args: Vec<&str> = trigger
+            .get("arguments")
+            .and_then(Value::as_array)
+            .map(|a| a.iter().filter_map(Value::as_str).collect())
+            .unwrap_or_default();
+        let parse = |i: usize| {
+            args.get(i)
+                .map(|a| serde_json::from_str(a).unwrap_or_else(|_| json!(a)))
+                .unwrap_or(Value::Null)
+        };
+
+        let webhook = json!({
+            "url": args.first().copied(),
+            "method": args.get(1).copied(),
+            "headers": parse(2),
+            "params": parse(3),
+            "timeout_ms": parse(4),
+        });
+        trigger.insert("webhook".to_string(), webhook);
+    }
+}
+
+/// Maps project specific hostnames of the source onto the destination, followed by any
+/// caller supplied rewrites.
+pub fn host_rewrites(source_id: &str, dest_id: &str, extra: &[HostRewrite]) -> Vec<HostRewrite> {
+    let mut rewrites = vec![
+        HostRewrite {
+            from: format!("{}.supabase.co", source_id),
+            to: format!("{}.supabase.co", dest_id),
+        },
+        HostRewrite {
+            from: format!("{}.functions.supabase.co", source_id),
+            to: format!("{}.functions.supabase.co", dest_id),
+        },
+    ];
+    rewrites.extend(extra.iter().cloned());
+    rewrites
+}
+
+/// Builds the statements that recreate triggers present on the source but missing on the
+/// destination, rewriting hostnames in their definitions. Triggers whose rewritten
+/// definition differs from the destination, such as a webhook pointing at another URL, are
+/// dropped and recreated in a single step.
+pub fn plan_triggers(source: &Value, dest: &Value, rewrites: &[HostRewrite]) -> Vec<Operation> {
+    let empty = Map::new();
+    let src_tables = source.as_object().unwrap_or(&empty);
+    let dst_tables = dest.as_object().unwrap_or(&empty);
+
+    let mut operations = Vec::new();
+
+    for (table, triggers) in src_tables {
+        let dst_triggers = dst_tables.get(table).and_then(Value::as_object);
+
+        for (name, trigger) in triggers.as_object().unwrap_or(&empty) {
+            let Some(definition) = trigger.get("definition").and_then(Value::as_str) else {
+                continue;
+            };
+
+            let key = format!("{}.{}", table, name);
+            let statement = rewrites
+                .iter()
+                .fold(definition.to_string(), |def, r| def.replace(&r.from, &r.to));
+
+            let existing = dst_triggers
+                .and_then(|t| t.get(name))
+                .map(|t| t.get("definition").and_then(Value::as_str));
+            let statement = match existing {
+                None => statement,
+                Some(existing) if existing == Some(statement.as_str()) => continue,
+                Some(_) => {
+                    let Some(table_name) = quote_qualified_name(table) else {
+                        operations.push(Operation::Skip {
+                            key,
+                            reason: format!("table name {:?} is not a valid identifier", table),
+                        });
+                        continue;
+                    };
+                    format!(
+                        "drop trigger if exists {} on {}; {}",
+                        quote_ident(name),
+                        table_name,
+                        statement
+                    )
+                }
+            };
+
+            operations.push(Operation::Sql(ApplyStep { key, statement }));
+        }
+    }
+
+    operations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe_webhooks() {
+        let mut config: Value = serde_json::from_str(
+            r#"{
+                "public.orders": {
+                    "notify_orders": {
+                        "function": "supabase_functions.http_request",
+                        "arguments": [
+                            "https://abc.functions.supabase.co/notify",
+                            "POST",
+                            "{\"Content-type\":\"application/json\"}",
+                            "{}",
+                            "1000"
+                        ]
+                    },
+                    "set_updated_at": {"function": "public.moddatetime", "arguments": []}
+                }
+            }"#,
+        )
+        .unwrap();
+
+        describe_webhooks(&mut config);
+
+        let webhook = &config["public.orders"]["notify_orders"]["webhook"];
+        assert_eq!(webhook["url"], "https://abc.functions.supabase.co/notify");
+        assert_eq!(webhook["method"], "POST");
+        assert_eq!(webhook["headers"]["Content-type"], "application/json");
+        assert_eq!(webhook["timeout_ms"], 1000);
+        assert!(
+            config["public.orders"]["set_updated_at"]
+                .get("webhook")
+                .is_none()
+        );
+    }
+
+    fn statements(operations: &[Operation]) -> Vec<&str> {
+        operations
+            .iter()
+            .filter_map(|operation| match operation {
+                Operation::Sql(step) => Some(step.statement.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_plan_recreates_missing_triggers_with_rewrites() {
+        let source: Value = serde_json::from_str(
+            r#"{
+                "public.orders": {
+                    "notify_orders": {
+                        "definition": "CREATE TRIGGER notify_orders AFTER INSERT ON public.orders FOR EACH ROW EXECUTE FUNCTION supabase_functions.http_request('https://srcref.functions.supabase.co/notify', 'POST', '{}', '{}', '1000')"
+                    },
+                    "set_updated_at": {
+                        "definition": "CREATE TRIGGER set_updated_at BEFORE UPDATE ON public.orders FOR EACH ROW EXECUTE FUNCTION moddatetime('updated_at')"
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+        let dest: Value = serde_json::from_str(
+            r#"{
+                "public.orders": {
+                    "set_updated_at": {
+                        "definition": "CREATE TRIGGER set_updated_at BEFORE UPDATE ON public.orders FOR EACH ROW EXECUTE FUNCTION moddatetime('updated_at')"
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let rewrites = host_rewrites("srcref", "dstref", &[]);
+        let operations = plan_triggers(&source, &dest, &rewrites);
+
+        assert_eq!(operations.len(), 1);
+        assert_eq!(
+            operations[0].written_keys(),
+            vec!["public.orders.notify_orders"]
+        );
+        let statement = statements(&operations)[0];
+        assert!(statement.contains("https://dstref.functions.supabase.co/notify"));
+        assert!(!statement.contains("srcref"));
+    }
+
+    #[test]
+    fn test_plan_replaces_triggers_with_changed_urls() {
+        let source: Value = serde_json::from_str(
+            r#"{
+                "public.orders": {
+                    "notify_orders": {
+                        "definition": "CREATE TRIGGER notify_orders AFTER INSERT ON public.orders FOR EACH ROW EXECUTE FUNCTION supabase_functions.http_request('https://hooks.example.com/v2/orders', 'POST', '{}', '{}', '1000')"
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+        let dest: Value = serde_json::from_str(
+            r#"{
+                "public.orders": {
+                    "notify_orders": {
+                        "definition": "CREATE TRIGGER notify_orders AFTER INSERT ON public.orders FOR EACH ROW EXECUTE FUNCTION supabase_functions.http_request('https://hooks.example.com/v1/orders', 'POST', '{}', '{}', '1000')"
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let rewrites = host_rewrites("srcref", "dstref", &[]);
+        let operations = plan_triggers(&source, &dest, &rewrites);
+
+        assert_eq!(
+            statements(&operations),
+            vec![
+                "drop trigger if exists \"notify_orders\" on \"public\".\"orders\"; CREATE TRIGGER notify_orders AFTER INSERT ON public.orders FOR EACH ROW EXECUTE FUNCTION supabase_functions.http_request('https://hooks.example.com/v2/orders', 'POST', '{}', '{}', '1000')"
+            ]
+        );
+        assert!(plan_triggers(&source, &source, &rewrites).is_empty());
+    }
+}